@@ -61,7 +61,7 @@ mod tests {
         let axiom = "A";
         let rules = [('A', String::from("AB")), ('B', String::from("A"))];
 
-        let system = LSystem::new(&axiom, &rules);
+        let system = LSystem::new(axiom, &rules);
 
         assert_eq!(&system.expand(0), "A");
         assert_eq!(&system.expand(1), "AB");
@@ -0,0 +1,71 @@
+//! Fractals of the `examples` directory, loaded for the tests of the other crates.
+
+use lindenmayer_parser::Updatable;
+use lindenmayer_renderer::canvas::ExprContext;
+use lindenmayer_renderer::LSystemRenderer;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Upper bound of the iterations, so that the examples
+/// which are slow to expand (e.g. mandelbrot) keep the tests fast.
+pub const MAX_ITERATIONS: usize = 60;
+
+/// Frames drawn by the tests, with the time at 60 frames per second.
+pub const FRAMES: [(f64, f64); 2] = [(0.0, 0.0), (60.0, 1000.0)];
+
+/// Paths of the examples, sorted by name.
+pub fn paths() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+
+    let mut paths = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lsys"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    paths
+}
+
+/// Name and renderer of every example, ready to draw.
+pub fn all() -> Vec<(String, LSystemRenderer)> {
+    paths()
+        .iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            (name, load(path))
+        })
+        .collect()
+}
+
+pub fn load(path: &Path) -> LSystemRenderer {
+    let text = fs::read_to_string(path).unwrap();
+
+    let mut renderer = LSystemRenderer::default();
+    for line in text.lines().filter(|line| !line.is_empty()) {
+        renderer.update(line).unwrap();
+    }
+    renderer.iter = renderer.iter.min(MAX_ITERATIONS);
+    renderer.update_expr();
+    renderer.update_program();
+    renderer.update_rng();
+
+    renderer
+}
+
+/// Context of a frame.
+pub fn variables(frame: f64, time: f64) -> ExprContext<'static> {
+    let mut variables = ExprContext::new();
+    variables.var("FRAME", frame);
+    variables.var("TIME", time);
+
+    variables
+}
+
+/// FNV-1a, which does not change between versions of Rust.
+pub fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -7,13 +7,33 @@ use std::{
 };
 
 use lindenmayer_renderer::{
+    budget::{Budget, RenderStatus},
     canvas::{Canvas, ExprContext},
-    LSystemRenderer,
+    validation, LSystemRenderer,
 };
 use lindenmayer_renderer_cairo::CairoCanvas;
 
 use crate::helpers::*;
 
+// Limits of a frame, so that a heavy one does not block the main loop for too long.
+// They can be changed with the environment variables, the deadline is in milliseconds
+const FRAME_DEADLINE: Duration = Duration::from_secs(2);
+const MAX_PRIMITIVES: usize = 2_000_000;
+const FRAME_DEADLINE_ENV: &str = "LSYS_FRAME_DEADLINE";
+const MAX_PRIMITIVES_ENV: &str = "LSYS_MAX_PRIMITIVES";
+
+// Value of a limit from the environment, the default one if it is missing or invalid
+fn limit<T: std::str::FromStr>(env: &str, default: T) -> T {
+    let Ok(value) = std::env::var(env) else {
+        return default;
+    };
+
+    value.trim().parse().unwrap_or_else(|_| {
+        log::warn!("Invalid {env}: {value}");
+        default
+    })
+}
+
 pub struct LSystemAnimator {
     renderer: Rc<RefCell<LSystemRenderer>>, // Just RefCell<T> ?
     drawing_area: Rc<DrawingArea>,
@@ -30,6 +50,8 @@ pub struct LSystemAnimator {
     // from the Instant time is added to the u128.
     // The Instant is None whether the time does not need to increment
     animation_time: Rc<RefCell<(u128, Option<Instant>)>>,
+    // Unbalanced push and pop symbols, shown after the frame is drawn
+    stack_issues: Rc<RefCell<String>>,
 }
 
 impl LSystemAnimator {
@@ -42,8 +64,13 @@ impl LSystemAnimator {
         elapsed_label: Rc<Label>,
         length_label: Rc<Label>,
     ) -> Self {
+        let frame_deadline = limit(FRAME_DEADLINE_ENV, FRAME_DEADLINE.as_millis() as u64);
+        let frame_deadline = Duration::from_millis(frame_deadline);
+        let max_primitives = limit(MAX_PRIMITIVES_ENV, MAX_PRIMITIVES);
+
         let frame_count = Rc::new(RefCell::new(0));
         let animation_time = Rc::new(RefCell::new((0, None::<Instant>)));
+        let stack_issues = Rc::new(RefCell::new(String::new()));
 
         (*drawing_area).set_draw_func(clone!(@weak renderer,
             @weak frame_count,
@@ -51,6 +78,7 @@ impl LSystemAnimator {
             @weak frame_label,
             @strong time_label,
             @weak animation_time,
+            @weak stack_issues,
             @strong elapsed_label => move |widget, context, _i, _j| {
             let renderer = &renderer.borrow_mut();
 
@@ -103,9 +131,14 @@ impl LSystemAnimator {
             variables.var("FRAME", frame as f64);
             variables.var("TIME", time as f64);
 
-            // Draw fractal
+            // Draw fractal, what has been drawn when the budget runs out is shown anyway
+            let budget = Budget {
+                max_primitives: Some(max_primitives),
+                deadline: Some(start + frame_deadline),
+                ..Default::default()
+            };
             let cairo_canvas: &mut dyn Canvas = &mut CairoCanvas(cr);
-            let draw_res = cairo_canvas.draw_fractal(renderer, &mut variables);
+            let draw_res = cairo_canvas.draw_fractal_within(renderer, &mut variables, &budget);
 
             let elapsed = start.elapsed();
 
@@ -120,10 +153,13 @@ impl LSystemAnimator {
                 elapsed_label.set_text(&elapsed_text);
             }));
 
-            if let Err(err) = draw_res {
-                set_error_status(&main_context, &status_label, &meval_error_msg(&err));
-                return;
-            }
+            let status = match draw_res {
+                Ok(status) => status,
+                Err(err) => {
+                    set_error_status(&main_context, &status_label, &render_error_msg(&err));
+                    return;
+                }
+            };
 
             // Draw the surface to the context of the widget
             if context.set_source_surface(&surface, 0.0, 0.0).is_err() {
@@ -135,7 +171,15 @@ impl LSystemAnimator {
                 return;
             }
 
-            hide_status(&main_context, &status_label);
+            let stack_issues = stack_issues.borrow();
+            match status {
+                RenderStatus::Complete if stack_issues.is_empty() => hide_status(&main_context, &status_label),
+                RenderStatus::Complete => set_error_status(&main_context, &status_label, &stack_issues),
+                RenderStatus::Partial(reason) => {
+                    let message = format!("Render stopped early: {reason}");
+                    set_error_status(&main_context, &status_label, &message);
+                }
+            }
         }));
 
         let is_playing = RefCell::new(Rc::new(RefCell::new(false)));
@@ -148,6 +192,7 @@ impl LSystemAnimator {
             length_label,
             frame_count,
             animation_time,
+            stack_issues,
         }
     }
 
@@ -164,8 +209,15 @@ impl LSystemAnimator {
         let elapsed = start.elapsed();
         log::debug!("String expansion took: {elapsed:?}");
 
+        self_renderer.update_program();
         self_renderer.update_rng();
 
+        let stack_issues: Vec<String> = validation::check_stack(&self_renderer)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        *self.stack_issues.borrow_mut() = stack_issues.join("\n");
+
         let length_text = format!("Length: {}", self_renderer.expression.len());
         self.length_label.set_text(&length_text);
 
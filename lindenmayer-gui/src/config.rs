@@ -1,5 +1,8 @@
 use lindenmayer_parser::*;
 
+/// Value shown for the commands which are set without arguments.
+pub const ON: &str = "on";
+
 #[derive(Debug)]
 pub struct ConfigLines {
     pub axiom: (String, bool),
@@ -12,6 +15,19 @@ pub struct ConfigLines {
     pub canvas: (String, bool),
     pub seed: (String, bool),
     pub injections: (String, bool),
+    pub camera: (String, bool),
+    pub camera_yaw: (String, bool),
+    pub camera_pitch: (String, bool),
+    pub camera_distance: (String, bool),
+    pub autofit: (String, bool),
+    pub curves: (String, bool),
+    pub initial_cap: (String, bool),
+    pub initial_join: (String, bool),
+    pub initial_miter_limit: (String, bool),
+    pub initial_dash: (String, bool),
+    pub tropism: (String, bool),
+    pub view: (String, bool),
+    pub strict: (String, bool),
 }
 
 impl Default for ConfigLines {
@@ -26,6 +42,20 @@ impl Default for ConfigLines {
         let canvas = (String::from("750,750"), false);
         let seed = (String::from("Default Seed"), false);
         let injections = (String::from(""), false);
+        // The renderer defaults are used when there is no value
+        let camera = (String::from(""), false);
+        let camera_yaw = (String::from(""), false);
+        let camera_pitch = (String::from(""), false);
+        let camera_distance = (String::from(""), false);
+        let autofit = (String::from(""), false);
+        let curves = (String::from(""), false);
+        let initial_cap = (String::from(""), false);
+        let initial_join = (String::from(""), false);
+        let initial_miter_limit = (String::from(""), false);
+        let initial_dash = (String::from(""), false);
+        let tropism = (String::from(""), false);
+        let view = (String::from(""), false);
+        let strict = (String::from(""), false);
 
         ConfigLines {
             axiom,
@@ -38,42 +68,77 @@ impl Default for ConfigLines {
             canvas,
             seed,
             injections,
+            camera,
+            camera_yaw,
+            camera_pitch,
+            camera_distance,
+            autofit,
+            curves,
+            initial_cap,
+            initial_join,
+            initial_miter_limit,
+            initial_dash,
+            tropism,
+            view,
+            strict,
         }
     }
 }
 
 impl ConfigLines {
     pub fn update(&mut self, line: &str, error: bool) {
-        let index = {
-            let index = line.find(' ');
-            if let Some(value) = index {
-                value
-            } else {
-                return;
-            }
+        let stripped_line = match line.split_once(' ') {
+            Some((_, arguments)) => arguments.trim().to_string(),
+            None if is_command(line, AUTOFIT) || is_command(line, STRICT) => ON.to_string(),
+            None => return,
         };
-        let stripped_line = line[index..].trim().to_string();
 
-        if line.starts_with(AXIOM) {
+        if is_command(line, AXIOM) {
             self.axiom = (stripped_line, error);
-        } else if line.starts_with(ITER) {
+        } else if is_command(line, ITER) {
             self.iter = (stripped_line, error);
-        } else if line.starts_with(INITIAL_POS) {
+        } else if is_command(line, INITIAL_POS) {
             self.initial_pos = (stripped_line, error);
-        } else if line.starts_with(INITIAL_ROT) {
+        } else if is_command(line, INITIAL_ROT) {
             self.initial_rot = (stripped_line, error);
-        } else if line.starts_with(INITIAL_THICKNESS) {
+        } else if is_command(line, INITIAL_THICKNESS) {
             self.initial_thickness = (stripped_line, error);
-        } else if line.starts_with(BACKGROUND) {
+        } else if is_command(line, BACKGROUND) {
             self.background_color = (stripped_line, error);
-        } else if line.starts_with(INITIAL_COLOR) {
+        } else if is_command(line, INITIAL_COLOR) {
             self.initial_color = (stripped_line, error);
-        } else if line.starts_with(CANVAS) {
+        } else if is_command(line, CANVAS) {
             self.canvas = (stripped_line, error);
-        } else if line.starts_with(SEED) {
+        } else if is_command(line, SEED) {
             self.seed = (stripped_line, error);
-        } else if line.starts_with(INJECT) {
+        } else if is_command(line, INJECT) {
             self.injections = (stripped_line, error);
+        } else if is_command(line, CAMERA) {
+            self.camera = (stripped_line, error);
+        } else if is_command(line, CAMERA_YAW) {
+            self.camera_yaw = (stripped_line, error);
+        } else if is_command(line, CAMERA_PITCH) {
+            self.camera_pitch = (stripped_line, error);
+        } else if is_command(line, CAMERA_DISTANCE) {
+            self.camera_distance = (stripped_line, error);
+        } else if is_command(line, AUTOFIT) {
+            self.autofit = (stripped_line, error);
+        } else if is_command(line, CURVES) {
+            self.curves = (stripped_line, error);
+        } else if is_command(line, INITIAL_CAP) {
+            self.initial_cap = (stripped_line, error);
+        } else if is_command(line, INITIAL_JOIN) {
+            self.initial_join = (stripped_line, error);
+        } else if is_command(line, INITIAL_MITER_LIMIT) {
+            self.initial_miter_limit = (stripped_line, error);
+        } else if is_command(line, INITIAL_DASH) {
+            self.initial_dash = (stripped_line, error);
+        } else if is_command(line, TROPISM) {
+            self.tropism = (stripped_line, error);
+        } else if is_command(line, VIEW) {
+            self.view = (stripped_line, error);
+        } else if is_command(line, STRICT) {
+            self.strict = (stripped_line, error);
         }
     }
 }
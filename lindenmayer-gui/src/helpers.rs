@@ -1,6 +1,8 @@
-use crate::{animations::LSystemAnimator, logic::update_renderer};
+use crate::{animations::LSystemAnimator, config::ON, logic::update_renderer};
 use gtk::{glib::*, prelude::*, *};
 use lindenmayer_parser::*;
+use lindenmayer_renderer::canvas::RenderError;
+use lindenmayer_renderer::program::EvalError;
 use lindenmayer_renderer::meval;
 use lindenmayer_renderer::LSystemRenderer;
 use std::{
@@ -36,6 +38,15 @@ pub fn meval_error_msg(error: &meval::Error) -> String {
     }
 }
 
+pub fn render_error_msg(error: &RenderError) -> String {
+    match error {
+        RenderError::Expression(EvalError::Expression(error)) => meval_error_msg(error),
+        RenderError::Expression(error) => error.to_string(),
+        RenderError::Operation(error) => error.to_string(),
+        RenderError::Canvas(error) => format!("Drawing error: {error}"),
+    }
+}
+
 pub fn add_text_input(container: &gtk::Box, placeholder: &str) -> Entry {
     let text_input = Entry::builder().placeholder_text(placeholder).build();
 
@@ -120,6 +131,15 @@ pub fn clear_editor(
     }
 }
 
+// Empty entries are left out, so that the renderer keeps its default value
+fn config_line(command: &str, value: &str) -> String {
+    match value.trim() {
+        "" => String::new(),
+        ON => command.to_string(),
+        _ => format!("{command} {value}"),
+    }
+}
+
 // Use to insert Entries in the "Configuration" section.
 pub fn create_config_entry(
     conf: &Rc<RefCell<ConfEditor>>,
@@ -137,7 +157,7 @@ pub fn create_config_entry(
 
     entry.set_text(value);
     let index = conf_ref.configurations.len();
-    conf_ref.configurations.push(format!("{command} {value}"));
+    conf_ref.configurations.push(config_line(command, value));
     let command_ = command.to_string();
 
     if error {
@@ -150,7 +170,7 @@ pub fn create_config_entry(
         let mut conf_ref = conf.borrow_mut();
         if let Some(value) = conf_ref.configurations.get_mut(index) {
             let content: String = input.text().into();
-            *value = format!("{command_} {content}");
+            *value = config_line(&command_, &content);
 
             let mut new_renderer = LSystemRenderer::default();
 
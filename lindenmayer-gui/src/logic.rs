@@ -366,6 +366,84 @@ fn init_config_editor(
             &config_lines.injections.0,
             config_lines.injections.1,
         ),
+        (
+            "camera",
+            "Camera",
+            &config_lines.camera.0,
+            config_lines.camera.1,
+        ),
+        (
+            "camera_yaw",
+            "Camera Yaw",
+            &config_lines.camera_yaw.0,
+            config_lines.camera_yaw.1,
+        ),
+        (
+            "camera_pitch",
+            "Camera Pitch",
+            &config_lines.camera_pitch.0,
+            config_lines.camera_pitch.1,
+        ),
+        (
+            "camera_distance",
+            "Camera Distance",
+            &config_lines.camera_distance.0,
+            config_lines.camera_distance.1,
+        ),
+        (
+            "autofit",
+            "Autofit",
+            &config_lines.autofit.0,
+            config_lines.autofit.1,
+        ),
+        (
+            "curves",
+            "Curves",
+            &config_lines.curves.0,
+            config_lines.curves.1,
+        ),
+        (
+            "initial_cap",
+            "Initial Cap",
+            &config_lines.initial_cap.0,
+            config_lines.initial_cap.1,
+        ),
+        (
+            "initial_join",
+            "Initial Join",
+            &config_lines.initial_join.0,
+            config_lines.initial_join.1,
+        ),
+        (
+            "initial_miter_limit",
+            "Initial Miter Limit",
+            &config_lines.initial_miter_limit.0,
+            config_lines.initial_miter_limit.1,
+        ),
+        (
+            "initial_dash",
+            "Initial Dash",
+            &config_lines.initial_dash.0,
+            config_lines.initial_dash.1,
+        ),
+        (
+            "tropism",
+            "Tropism",
+            &config_lines.tropism.0,
+            config_lines.tropism.1,
+        ),
+        (
+            "view",
+            "View",
+            &config_lines.view.0,
+            config_lines.view.1,
+        ),
+        (
+            "strict",
+            "Strict",
+            &config_lines.strict.0,
+            config_lines.strict.1,
+        ),
     ];
 
     for value in values {
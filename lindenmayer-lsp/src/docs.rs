@@ -0,0 +1,207 @@
+use lindenmayer_parser::*;
+
+/// Configuration commands, in the order they usually appear in a file.
+pub const COMMANDS: &[&str] = &[
+    AXIOM,
+    ITER,
+    INITIAL_POS,
+    INITIAL_ROT,
+    INITIAL_THICKNESS,
+    BACKGROUND,
+    INITIAL_COLOR,
+    INITIAL_CAP,
+    INITIAL_JOIN,
+    INITIAL_MITER_LIMIT,
+    INITIAL_DASH,
+    CANVAS,
+    SEED,
+    INJECT,
+    ANIMATE,
+    FUNCTION,
+    PALETTE,
+    CAMERA,
+    CAMERA_YAW,
+    CAMERA_PITCH,
+    CAMERA_DISTANCE,
+    AUTOFIT,
+    CURVES,
+    TROPISM,
+    VIEW,
+    STRICT,
+];
+
+/// Keywords which can start an operation.
+pub const OPERATIONS: &[&str] = &[
+    FORWARD,
+    JUMP,
+    DOT,
+    ROTATE,
+    PITCH,
+    ROLL,
+    THICKNESS,
+    TROPISM,
+    CAP,
+    JOIN,
+    MITER_LIMIT,
+    DASH,
+    IGNORE,
+    PUSH,
+    POP,
+    BEGIN_POLYGON,
+    VERTEX,
+    END_POLYGON,
+    COLOR,
+    TEXT,
+    IF,
+    ELSE,
+    REPEAT,
+];
+
+pub fn command(name: &str) -> Option<&'static str> {
+    let doc = match name {
+        AXIOM => "`axiom <symbols>`\n\nInitial string of the L-System.",
+        ITER => "`iter <n>`\n\nNumber of times the rules are applied to the axiom.",
+        INITIAL_POS => "`initial_pos <x>,<y>`\n\nStarting position of the turtle.",
+        INITIAL_ROT => "`initial_rot <radians>`\n\nStarting rotation of the turtle.",
+        INITIAL_THICKNESS => "`initial_thickness <width>`\n\nStarting line width.",
+        BACKGROUND => "`background <color>`\n\nBackground color, in any CSS format.",
+        INITIAL_COLOR => {
+            "`initial_color <color>`\n\nStarting color of the turtle, in any CSS format."
+        }
+        INITIAL_CAP => "`initial_cap <butt|round|square>`\n\nStarting shape of the line ends.",
+        INITIAL_JOIN => "`initial_join <miter|round|bevel>`\n\nStarting shape of the corners of a path.",
+        INITIAL_MITER_LIMIT => {
+            "`initial_miter_limit <limit>`\n\nStarting ratio above which miter joins are beveled (10 by default)."
+        }
+        INITIAL_DASH => {
+            "`initial_dash <length>, ...`\n\nStarting lengths of the dashes and of the gaps, or `solid`."
+        }
+        CANVAS => "`canvas <width>,<height>`\n\nSize of the canvas in pixels.",
+        SEED => "`seed <text>`\n\nSeed of the random numbers returned by `rand`.",
+        INJECT => {
+            "`inject <index>,<symbols> ...`\n\n\
+            Inserts symbols into the expanded string at the given indices."
+        }
+        ANIMATE => {
+            "`animate <name> <time>:<value> ... [interpolation] [playback]`\n\n\
+            Keyframe track of a variable. Times are given in `s`, `ms` or `f` (frames).\n\n\
+            Interpolations: `linear`, `step`, `cubic`, `ease-in`, `ease-out`, `ease-in-out`.\n\
+            Playback: `once`, `loop`, `ping-pong`."
+        }
+        FUNCTION => "`fn <name>(<params>) = <expression>`\n\nFunction usable in every expression.",
+        PALETTE => {
+            "`palette <name> <color> ...`\n\n\
+            List of colors, used with `palette(name, i)` and `gradient(name, t)`."
+        }
+        CAMERA => {
+            "`camera <orthographic|perspective>`\n\n\
+            Makes the turtle 3D, its geometry is projected by a camera orbiting the center of the canvas."
+        }
+        CAMERA_YAW => "`camera_yaw <expression>`\n\nHorizontal orbit angle of the camera, in radians.",
+        CAMERA_PITCH => "`camera_pitch <expression>`\n\nVertical orbit angle of the camera, in radians.",
+        CAMERA_DISTANCE => {
+            "`camera_distance <expression>`\n\nDistance of the perspective camera from the center."
+        }
+        AUTOFIT => {
+            "`autofit [margin] [screen]`\n\n\
+            Scales and centers the drawing to fill the canvas, leaving a margin (20 by default).\n\
+            With `screen`, line widths and dots are not scaled."
+        }
+        TROPISM => {
+            "`tropism <x>,<y>[,<z>] <susceptibility>`\n\n\
+            After every `forward` the heading bends towards the direction, e.g. `0,1` for the gravity. \
+            The susceptibility is an expression, so it can be animated with `FRAME` or `TIME`."
+        }
+        VIEW => {
+            "`view <zoom>, <x>, <y>[, <angle>]`\n\n\
+            Zooms and rotates the whole drawing around the point, which is shown at the center of the canvas. \
+            The parameters are expressions, so they can be animated."
+        }
+        STRICT => {
+            "`strict [max_depth]`\n\n\
+            A `pop` without a matching `push`, or a `push` deeper than the maximum depth, \
+            stops the rendering with an error instead of being ignored."
+        }
+        CURVES => {
+            "`curves <joined|catmull-rom|bezier> [tension]`\n\n\
+            Joins the consecutive lines of a branch into a single path. \
+            `catmull-rom` smooths it through the turtle positions, `bezier` uses them as control points. \
+            The tension (0.5 by default) sets how round the curves are."
+        }
+        _ => return None,
+    };
+
+    Some(doc)
+}
+
+pub fn operation(name: &str) -> Option<&'static str> {
+    let doc = match name {
+        FORWARD => "`forward <length>`\n\nMoves the turtle forward, drawing a line.",
+        JUMP => "`jump <length>`\n\nMoves the turtle forward without drawing.",
+        DOT => "`dot <radius>`\n\nDraws a filled circle at the position of the turtle.",
+        ROTATE => "`rotate <radians>`\n\nRotates the turtle, around its up vector in 3D.",
+        PITCH => "`pitch <radians>`\n\nRotates the 3D turtle around its left vector.",
+        ROLL => "`roll <radians>`\n\nRotates the 3D turtle around its heading.",
+        THICKNESS => "`thickness <width>`\n\nSets the line width.",
+        TROPISM => {
+            "`tropism <susceptibility>`\n\nSets how much the current branch bends towards the tropism."
+        }
+        CAP => "`cap <butt|round|square>`\n\nSets the shape of the line ends.",
+        JOIN => "`join <miter|round|bevel>`\n\nSets the shape of the corners of a path.",
+        MITER_LIMIT => "`miter_limit <limit>`\n\nSets the ratio above which miter joins are beveled.",
+        DASH => {
+            "`dash <length>, ...`\n\nSets the lengths of the dashes and of the gaps between them, \
+            `dash solid` goes back to solid lines."
+        }
+        IGNORE => "`ignore <n>`\n\nSkips the next `n` symbols.",
+        PUSH => "`push`\n\nSaves the state of the turtle and of the variables.",
+        POP => "`pop`\n\nRestores the last state saved by `push`.",
+        BEGIN_POLYGON => "`begin_polygon`\n\nStarts recording the vertices of a polygon.",
+        VERTEX => "`vertex`\n\nAdds the position of the turtle to the current polygon.",
+        END_POLYGON => {
+            "`end_polygon`\n\nFills the current polygon with the current color. \
+            Polygons started inside a `push`/`pop` branch end with it."
+        }
+        COLOR => "`color <color>`\n\nSets the color, either in a CSS format or as an expression like `hsl(...)`.",
+        TEXT => {
+            "`text \"<text>\" [size <size>] [anchor <start|middle|end>] [heading]`\n\n\
+            Writes a label at the position of the turtle. Expressions between braces are replaced \
+            by their value, e.g. `{DEPTH}` or `{POSX:.0}`, and `{SYMBOL}` by the current symbol. \
+            With `heading` the text follows the direction of the turtle."
+        }
+        IF | ELSE => {
+            "`if <condition> { ... } else { ... }`\n\n\
+            Executes the first block when the condition is not zero, the second one otherwise."
+        }
+        REPEAT => "`repeat <count> { ... }`\n\nExecutes the block `count` times.",
+        _ => return None,
+    };
+
+    Some(doc)
+}
+
+pub fn builtin(name: &str) -> Option<&'static str> {
+    let doc = match name {
+        "INDEX" => "`INDEX`\n\nIndex of the current symbol.",
+        "LENGTH" => "`LENGTH`\n\nLength of the expanded string.",
+        "DEPTH" => "`DEPTH`\n\nNumber of states saved by `push`.",
+        "POSX" => "`POSX`\n\nHorizontal position of the turtle.",
+        "POSY" => "`POSY`\n\nVertical position of the turtle.",
+        "POSZ" => "`POSZ`\n\nDepth of the 3D turtle.",
+        "FRAME" => "`FRAME`\n\nCurrent frame of the animation.",
+        "TIME" => "`TIME`\n\nMilliseconds since the start of the animation.",
+        "rand" => "`rand()`\n\nRandom number in [0, 1), depends on the seed.",
+        "rgb" => "`rgb(r, g, b)`\n\nOpaque color, channels in [0, 255].",
+        "rgba" => "`rgba(r, g, b, a)`\n\nColor with channels in [0, 255] and alpha in [0, 1].",
+        "hsl" => {
+            "`hsl(h, s, l)`\n\nColor from a hue in degrees, saturation and lightness in [0, 1]."
+        }
+        "hsv" => "`hsv(h, s, v)`\n\nColor from a hue in degrees, saturation and value in [0, 1].",
+        "mix" => "`mix(c1, c2, t)`\n\nInterpolates two colors in the OKLab color space.",
+        "palette" => "`palette(name, i)`\n\nColor `i` of a palette, the index wraps around.",
+        "gradient" => "`gradient(name, t)`\n\nColor along a palette, `t` goes from 0 to 1.",
+        _ => return None,
+    };
+
+    Some(doc)
+}
@@ -0,0 +1,355 @@
+use crate::docs;
+use lindenmayer_parser::*;
+use lindenmayer_renderer::functions::{BUILTIN_CONSTANTS, BUILTIN_FUNCTIONS, TURTLE_VARIABLES};
+use lindenmayer_renderer::validation::{self, StackSource};
+use lindenmayer_renderer::LSystemRenderer;
+use lsp_types::*;
+
+/// An open .lsys file, parsed line by line like the GUI does.
+pub struct Document {
+    lines: Vec<String>,
+    results: Vec<Result<LineType>>,
+    // Unbalanced push and pop symbols, with the line they were found on
+    warnings: Vec<(usize, String)>,
+}
+
+impl Document {
+    pub fn new(text: &str) -> Self {
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+
+        // Later lines can depend on the earlier ones (e.g. functions)
+        let mut renderer = LSystemRenderer::default();
+        let results = lines
+            .iter()
+            .map(|line| renderer.update(line.as_str()))
+            .collect();
+
+        let mut document = Self {
+            lines,
+            results,
+            warnings: vec![],
+        };
+        document.warnings = validation::check_stack(&renderer)
+            .into_iter()
+            .filter_map(|issue| {
+                Some((document.stack_source_line(issue.source)?, issue.to_string()))
+            })
+            .collect();
+
+        document
+    }
+
+    /// Range spanning the whole text.
+    pub fn range(&self) -> Range {
+        Range::new(
+            Position::new(0, 0),
+            Position::new(self.lines.len() as u32, 0),
+        )
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let errors = self.results.iter().enumerate().filter_map(|(i, result)| {
+            let error = result.as_ref().err()?;
+            Some((i, DiagnosticSeverity::ERROR, error.to_string()))
+        });
+        let warnings = self
+            .warnings
+            .iter()
+            .map(|(i, message)| (*i, DiagnosticSeverity::WARNING, message.clone()));
+
+        errors
+            .chain(warnings)
+            .map(|(i, severity, message)| {
+                // Underline the line without the surrounding whitespace
+                let line = &self.lines[i];
+                let start = line.len() - line.trim_start().len();
+                let end = line.trim_end().len();
+
+                Diagnostic {
+                    range: Range::new(position(line, i, start), position(line, i, end)),
+                    severity: Some(severity),
+                    source: Some("lindenmayer".to_string()),
+                    message,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    pub fn hover(&self, pos: Position) -> Option<Hover> {
+        let line = self.lines.get(pos.line as usize)?;
+        let (start, word) = word_at(line, pos.character)?;
+        let first_word = line[..start].trim().is_empty();
+
+        let doc = if first_word {
+            docs::command(word).map(str::to_string)
+        } else {
+            None
+        }
+        .or_else(|| {
+            docs::operation(word)
+                .filter(|_| is_operation(line))
+                .map(str::to_string)
+        })
+        .or_else(|| docs::builtin(word).map(str::to_string))
+        .or_else(|| {
+            // Show the declaration of user-defined names
+            let declaration = self.declaration(word)?;
+            Some(format!("```\n{}\n```", self.lines[declaration].trim()))
+        })?;
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: doc,
+            }),
+            range: None,
+        })
+    }
+
+    pub fn completion(&self, pos: Position) -> Vec<CompletionItem> {
+        let Some(line) = self.lines.get(pos.line as usize) else {
+            return vec![];
+        };
+        let prefix = &line[..byte_index(line, pos.character)];
+
+        let item = |label: &str, kind| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            ..Default::default()
+        };
+
+        // The first word of a line is a command
+        if prefix.trim_start().chars().all(is_identifier) {
+            return docs::COMMANDS
+                .iter()
+                .map(|command| item(command, CompletionItemKind::KEYWORD))
+                .collect();
+        }
+
+        let mut items = vec![];
+
+        if is_operation(line) {
+            items.extend(
+                docs::OPERATIONS
+                    .iter()
+                    .map(|operation| item(operation, CompletionItemKind::KEYWORD)),
+            );
+        }
+
+        for (i, line) in self.lines.iter().enumerate() {
+            if let Some(name) = self.declared_name(i) {
+                let kind = if is_command(line, FUNCTION) {
+                    CompletionItemKind::FUNCTION
+                } else {
+                    CompletionItemKind::VARIABLE
+                };
+                items.push(item(name, kind));
+            }
+        }
+
+        items.extend(
+            BUILTIN_FUNCTIONS
+                .iter()
+                .map(|name| item(name, CompletionItemKind::FUNCTION)),
+        );
+        items.extend(
+            BUILTIN_CONSTANTS
+                .iter()
+                .map(|name| item(name, CompletionItemKind::CONSTANT)),
+        );
+        items.extend(
+            TURTLE_VARIABLES
+                .iter()
+                .map(|name| item(name, CompletionItemKind::VARIABLE)),
+        );
+
+        items
+    }
+
+    /// Goes from a symbol of a rule or of the axiom to its operations,
+    /// and from a name to its declaration.
+    pub fn definition(&self, pos: Position) -> Option<Range> {
+        let i = pos.line as usize;
+        let line = self.lines.get(i)?;
+        let index = byte_index(line, pos.character);
+
+        let is_rule = matches!(self.results[i], Ok(LineType::Rule));
+        let is_axiom = is_command(line, AXIOM) && index > AXIOM.len();
+
+        let target = if is_rule || is_axiom {
+            let symbol = line[index..].chars().next()?;
+            self.operation_line(symbol)?
+        } else {
+            let (_, word) = word_at(line, pos.character)?;
+            self.declaration(word)?
+        };
+
+        let target_line = &self.lines[target];
+        Some(Range::new(
+            position(target_line, target, 0),
+            position(target_line, target, target_line.len()),
+        ))
+    }
+
+    /// Normalizes the spacing of the lines which can be parsed,
+    /// the others are only trimmed.
+    pub fn format(&self) -> String {
+        let mut result = String::new();
+        let mut blank = true;
+
+        for (line, ty) in self.lines.iter().zip(&self.results) {
+            let line = line.trim();
+
+            // Collapse consecutive blank lines
+            if line.is_empty() {
+                if !blank {
+                    result.push('\n');
+                }
+                blank = true;
+                continue;
+            }
+            blank = false;
+
+            match ty {
+                Ok(LineType::Rule) => {
+                    let (symbol, rule) = line.split_once(RULE_DECLARATION).unwrap_or((line, ""));
+                    result.push_str(&format!(
+                        "{} {RULE_DECLARATION} {}",
+                        symbol.trim(),
+                        rule.trim()
+                    ));
+                }
+                // Text labels are kept as they are
+                Ok(LineType::Operation) if !line.contains(QUOTE) => {
+                    let mut chars = line.chars();
+                    let symbol = chars.next().unwrap_or_default();
+                    let operations = chars.as_str();
+                    let operations = operations
+                        .strip_prefix(OP_DECLARATION)
+                        .unwrap_or(operations)
+                        .split(OP_SEPARATOR)
+                        .map(|operation| operation.trim())
+                        .collect::<Vec<_>>()
+                        .join(&format!("{OP_SEPARATOR} "));
+
+                    result.push_str(&format!(
+                        "{symbol}{OP_DECLARATION} {}",
+                        operations.trim_end()
+                    ));
+                }
+                Ok(LineType::Variable) if is_plain_variable(line) => {
+                    let (name, value) = line.split_once(VAR_DECLARATION).unwrap_or((line, ""));
+                    result.push_str(&format!(
+                        "{} {VAR_DECLARATION} {}",
+                        name.trim(),
+                        value.trim()
+                    ));
+                }
+                _ => result.push_str(line),
+            }
+            result.push('\n');
+        }
+
+        // Remove the trailing blank line
+        if result.ends_with("\n\n") {
+            result.pop();
+        }
+
+        result
+    }
+
+    // The last line declaring the axiom, rule or injections, which is the one in effect
+    fn stack_source_line(&self, source: StackSource) -> Option<usize> {
+        (0..self.lines.len()).rev().find(|&i| {
+            let line = self.lines[i].trim_start();
+            match (source, &self.results[i]) {
+                (StackSource::Axiom, Ok(LineType::Config)) => is_command(line, AXIOM),
+                (StackSource::Injection(_), Ok(LineType::Config)) => is_command(line, INJECT),
+                (StackSource::Rule(symbol), Ok(LineType::Rule)) => line.starts_with(symbol),
+                _ => false,
+            }
+        })
+    }
+
+    // The last operation line of a symbol, which is the one in effect
+    fn operation_line(&self, symbol: char) -> Option<usize> {
+        (0..self.lines.len()).rev().find(|&i| {
+            matches!(self.results[i], Ok(LineType::Operation))
+                && self.lines[i].trim_start().starts_with(symbol)
+        })
+    }
+
+    // The last line declaring a variable, a function or a palette
+    fn declaration(&self, name: &str) -> Option<usize> {
+        (0..self.lines.len())
+            .rev()
+            .find(|&i| self.declared_name(i) == Some(name))
+    }
+
+    fn declared_name(&self, i: usize) -> Option<&str> {
+        if !matches!(self.results[i], Ok(LineType::Variable)) {
+            return None;
+        }
+
+        let line = self.lines[i].trim();
+        let line = [ANIMATE, PALETTE, FUNCTION]
+            .iter()
+            .find_map(|keyword| line.strip_prefix(keyword)?.strip_prefix(' '))
+            .unwrap_or(line)
+            .trim_start();
+
+        let end = line.find(|c| !is_identifier(c)).unwrap_or(line.len());
+        Some(&line[..end]).filter(|name| !name.is_empty())
+    }
+}
+
+// Variables declared as `NAME = value`
+fn is_plain_variable(line: &str) -> bool {
+    let keyword = line.split(' ').next();
+    keyword != Some(ANIMATE) && keyword != Some(PALETTE) && keyword != Some(FUNCTION)
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_operation(line: &str) -> bool {
+    let mut chars = line.trim_start().chars();
+    chars.next();
+    chars.as_str().starts_with(OP_DECLARATION)
+}
+
+// The identifier under the cursor and its byte offset
+fn word_at(line: &str, character: u32) -> Option<(usize, &str)> {
+    let index = byte_index(line, character);
+
+    let start = line[..index]
+        .rfind(|c| !is_identifier(c))
+        .map(|i| i + line[i..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(0);
+    let end = line[index..]
+        .find(|c| !is_identifier(c))
+        .map(|i| index + i)
+        .unwrap_or(line.len());
+
+    Some((start, &line[start..end])).filter(|(_, word)| !word.is_empty())
+}
+
+// LSP positions count UTF-16 code units
+fn byte_index(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+
+    line.len()
+}
+
+fn position(line: &str, i: usize, byte: usize) -> Position {
+    let character = line[..byte].encode_utf16().count();
+    Position::new(i as u32, character as u32)
+}
@@ -0,0 +1,141 @@
+pub mod docs;
+pub mod document;
+
+use document::Document;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, Formatting, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::*;
+use std::collections::HashMap;
+
+pub type Error = Box<dyn std::error::Error + Sync + Send>;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        definition_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// Serves the client on the other side of the connection until it shuts down.
+pub fn run(connection: &Connection) -> Result<(), Error> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut documents = HashMap::new();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+
+                let response = handle_request(&documents, request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut documents, notification) {
+                    let diagnostics = documents
+                        .get(&uri)
+                        .map(Document::diagnostics)
+                        .unwrap_or_default();
+                    let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+
+                    connection
+                        .sender
+                        .send(Message::Notification(Notification::new(
+                            PublishDiagnostics::METHOD.to_string(),
+                            params,
+                        )))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the document whose diagnostics have changed
+fn handle_notification(
+    documents: &mut HashMap<Url, Document>,
+    notification: Notification,
+) -> Option<Url> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams =
+                notification.extract(DidOpenTextDocument::METHOD).ok()?;
+            let document = params.text_document;
+            documents.insert(document.uri.clone(), Document::new(&document.text));
+            Some(document.uri)
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams =
+                notification.extract(DidChangeTextDocument::METHOD).ok()?;
+            // The whole text is sent on every change
+            let change = params.content_changes.into_iter().last()?;
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), Document::new(&change.text));
+            Some(uri)
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams =
+                notification.extract(DidCloseTextDocument::METHOD).ok()?;
+            let uri = params.text_document.uri;
+            documents.remove(&uri);
+            Some(uri)
+        }
+        _ => None,
+    }
+}
+
+fn handle_request(documents: &HashMap<Url, Document>, request: Request) -> Response {
+    let document = |uri: &Url| documents.get(uri);
+
+    match request.method.as_str() {
+        HoverRequest::METHOD => respond::<HoverRequest>(request, |params| {
+            let position = params.text_document_position_params;
+            document(&position.text_document.uri)?.hover(position.position)
+        }),
+        Completion::METHOD => respond::<Completion>(request, |params| {
+            let position = params.text_document_position;
+            let items = document(&position.text_document.uri)?.completion(position.position);
+            Some(CompletionResponse::Array(items))
+        }),
+        GotoDefinition::METHOD => respond::<GotoDefinition>(request, |params| {
+            let position = params.text_document_position_params;
+            let uri = position.text_document.uri;
+            let range = document(&uri)?.definition(position.position)?;
+            Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+        }),
+        Formatting::METHOD => respond::<Formatting>(request, |params| {
+            let document = document(&params.text_document.uri)?;
+            // Replace the whole document
+            Some(vec![TextEdit::new(document.range(), document.format())])
+        }),
+        _ => Response::new_err(
+            request.id,
+            ErrorCode::MethodNotFound as i32,
+            format!("unknown method {}", request.method),
+        ),
+    }
+}
+
+fn respond<R>(request: Request, handler: impl FnOnce(R::Params) -> R::Result) -> Response
+where
+    R: lsp_types::request::Request,
+{
+    let id = request.id.clone();
+
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((_, params)) => Response::new_ok(id, handler(params)),
+        Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+    }
+}
@@ -0,0 +1,13 @@
+use lsp_server::Connection;
+
+fn main() -> Result<(), lindenmayer_lsp::Error> {
+    // The client talks to the server over stdin and stdout
+    let (connection, io_threads) = Connection::stdio();
+
+    lindenmayer_lsp::run(&connection)?;
+
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, Formatting, GotoDefinition, HoverRequest, Initialize, Shutdown,
+};
+use lsp_types::*;
+use serde_json::Value;
+use std::thread;
+
+const SOURCE: &str = "axiom F
+iter 3   
+
+LINE=10
+fn wave(x) = sin(x) * LINE
+
+
+F:forward LINE;rotate wave(INDEX)
++: rotate 0.5
+F -> F+F
+X: frobnicate 3
+";
+
+struct Client {
+    connection: Connection,
+    next_id: i32,
+    uri: Url,
+}
+
+impl Client {
+    fn request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), R::METHOD.to_string(), params);
+        self.connection.sender.send(request.into()).unwrap();
+
+        loop {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) if response.id == id => {
+                    assert!(response.error.is_none(), "{:?}", response.error);
+                    return response.result.unwrap_or_default();
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(&self, params: N::Params) {
+        let notification = Notification::new(N::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        loop {
+            if let Message::Notification(notification) = self.connection.receiver.recv().unwrap() {
+                if notification.method == PublishDiagnostics::METHOD {
+                    let params: PublishDiagnosticsParams =
+                        serde_json::from_value(notification.params).unwrap();
+                    return params.diagnostics;
+                }
+            }
+        }
+    }
+
+    fn position(&self, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(self.uri.clone()),
+            Position::new(line, character),
+        )
+    }
+}
+
+fn open(text: &str) -> (Client, thread::JoinHandle<()>) {
+    let (client, server) = Connection::memory();
+    let handle = thread::spawn(move || lindenmayer_lsp::run(&server).unwrap());
+
+    let mut client = Client {
+        connection: client,
+        next_id: 0,
+        uri: Url::parse("file:///tmp/test.lsys").unwrap(),
+    };
+
+    client.request::<Initialize>(InitializeParams::default());
+    client.notify::<Initialized>(InitializedParams {});
+    client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(
+            client.uri.clone(),
+            "lsys".to_string(),
+            1,
+            text.to_string(),
+        ),
+    });
+
+    (client, handle)
+}
+
+fn close(mut client: Client, handle: thread::JoinHandle<()>) {
+    client.request::<Shutdown>(());
+    client.notify::<Exit>(());
+    handle.join().unwrap();
+}
+
+#[test]
+fn diagnostics() {
+    let (client, handle) = open(SOURCE);
+
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].range,
+        Range::new(Position::new(10, 0), Position::new(10, 15))
+    );
+    assert_eq!(diagnostics[0].message, "unknown operation");
+
+    // Fixing the line clears the diagnostic
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(client.uri.clone(), 2),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: SOURCE.replace("frobnicate", "forward"),
+        }],
+    });
+    assert!(client.diagnostics().is_empty());
+
+    // Unbalanced branches are warnings on their rule
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(client.uri.clone(), 3),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: SOURCE.replace("frobnicate 3", "pop").replace("F+F", "F+FX"),
+        }],
+    });
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range.start.line, 9);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(
+        diagnostics[0].message,
+        "rule 'F': pop without a matching push at index 3"
+    );
+
+    close(client, handle);
+}
+
+#[test]
+fn hover() {
+    let (mut client, handle) = open(SOURCE);
+
+    let hover = |client: &mut Client, line, character| {
+        let params = HoverParams {
+            text_document_position_params: client.position(line, character),
+            work_done_progress_params: Default::default(),
+        };
+        client.request::<HoverRequest>(params)["contents"]["value"]
+            .as_str()
+            .map(str::to_string)
+    };
+
+    // Command, operation, built-in and user function
+    assert!(hover(&mut client, 1, 2).unwrap().starts_with("`iter <n>`"));
+    assert!(hover(&mut client, 7, 4)
+        .unwrap()
+        .starts_with("`forward <length>`"));
+    assert!(hover(&mut client, 7, 28).unwrap().starts_with("`INDEX`"));
+    assert!(hover(&mut client, 7, 23)
+        .unwrap()
+        .contains("fn wave(x) = sin(x) * LINE"));
+    assert_eq!(hover(&mut client, 2, 0), None);
+
+    close(client, handle);
+}
+
+#[test]
+fn completion() {
+    let (mut client, handle) = open(SOURCE);
+
+    let mut complete = |line, character| {
+        let params = CompletionParams {
+            text_document_position: client.position(line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+        let items: Vec<CompletionItem> =
+            serde_json::from_value(client.request::<Completion>(params)).unwrap();
+        items.into_iter().map(|item| item.label).collect::<Vec<_>>()
+    };
+
+    let commands = complete(2, 0);
+    assert!(commands.contains(&"initial_pos".to_string()));
+    assert!(!commands.contains(&"forward".to_string()));
+
+    let operations = complete(8, 3);
+    for label in ["forward", "repeat", "LINE", "wave", "hsl", "pi", "DEPTH"] {
+        assert!(operations.contains(&label.to_string()), "{label}");
+    }
+
+    close(client, handle);
+}
+
+#[test]
+fn definition() {
+    let (mut client, handle) = open(SOURCE);
+
+    let mut definition = |line, character| {
+        let params = GotoDefinitionParams {
+            text_document_position_params: client.position(line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        serde_json::from_value::<Option<Location>>(client.request::<GotoDefinition>(params))
+            .unwrap()
+            .map(|location| location.range.start.line)
+    };
+
+    // Symbols of rules and of the axiom
+    assert_eq!(definition(9, 6), Some(8));
+    assert_eq!(definition(9, 5), Some(7));
+    assert_eq!(definition(0, 6), Some(7));
+    // Names in expressions
+    assert_eq!(definition(7, 11), Some(3));
+    assert_eq!(definition(7, 23), Some(4));
+    // Symbol without operations
+    assert_eq!(definition(9, 2), None);
+
+    close(client, handle);
+}
+
+#[test]
+fn formatting() {
+    let (mut client, handle) = open(SOURCE);
+
+    let params = DocumentFormattingParams {
+        text_document: TextDocumentIdentifier::new(client.uri.clone()),
+        options: Default::default(),
+        work_done_progress_params: Default::default(),
+    };
+    let edits: Vec<TextEdit> =
+        serde_json::from_value(client.request::<Formatting>(params)).unwrap();
+
+    assert_eq!(edits.len(), 1);
+    assert_eq!(
+        edits[0].new_text,
+        "axiom F
+iter 3
+
+LINE = 10
+fn wave(x) = sin(x) * LINE
+
+F: forward LINE; rotate wave(INDEX)
++: rotate 0.5
+F -> F+F
+X: frobnicate 3
+"
+    );
+
+    close(client, handle);
+}
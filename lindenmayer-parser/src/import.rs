@@ -1,5 +1,11 @@
 use crate::*;
-use lindenmayer_renderer::{LSystemRenderer, Operation, Color};
+use lindenmayer_renderer::{meval, LSystemRenderer, Operation, Color, Palette, Label, LabelPart, Strict, Tropism};
+use lindenmayer_renderer::canvas::{LineCap, LineJoin, TextAnchor};
+use lindenmayer_renderer::animation::{Interpolation, Playback, TimeUnit, Track};
+use lindenmayer_renderer::camera::Projection;
+use lindenmayer_renderer::curves::{Curves, Smoothing};
+use lindenmayer_renderer::display_list::{Autofit, Viewport};
+use lindenmayer_renderer::functions::{self, Function};
 use std::str::FromStr;
 
 pub type Result<T> = std::result::Result<T, ParsingError>;
@@ -20,8 +26,37 @@ pub enum ParsingError {
     InvalidExpression,
     InvalidTuple,
     InvalidColor,
+    InvalidKeyframe,
+    UnbalancedBraces,
+    NestingTooDeep,
+    ShadowedName,
+    RecursiveFunction,
 }
 
+impl std::fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            ParsingError::UnknownAction => "unknown command",
+            ParsingError::InvalidFormat => "invalid format",
+            ParsingError::InvalidInteger => "invalid integer",
+            ParsingError::InvalidFloatingPoint => "invalid number",
+            ParsingError::InvalidOperation => "unknown operation",
+            ParsingError::InvalidExpression => "invalid expression",
+            ParsingError::InvalidTuple => "invalid tuple",
+            ParsingError::InvalidColor => "invalid color",
+            ParsingError::InvalidKeyframe => "invalid keyframe",
+            ParsingError::UnbalancedBraces => "unbalanced braces",
+            ParsingError::NestingTooDeep => "blocks are nested too deeply",
+            ParsingError::ShadowedName => "name shadows a built-in",
+            ParsingError::RecursiveFunction => "function calls itself",
+        };
+
+        write!(f, "{message}")
+    }
+}
+
+impl std::error::Error for ParsingError {}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum LineType {
     Config,
@@ -40,56 +75,149 @@ impl Updatable<&str> for LSystemRenderer {
             return Ok(LineType::Nothing);
         }
 
-        if line.starts_with(AXIOM) {
+        if is_command(line, AXIOM) {
             self.lsystem.axiom = parse_axiom(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(ITER) {
+        if is_command(line, ITER) {
             self.iter = parse_iter(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(INITIAL_ROT) {
+        if is_command(line, INITIAL_ROT) {
             self.initial_rot = parse_initial_rot(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(INITIAL_POS) {
+        if is_command(line, INITIAL_POS) {
             self.initial_pos = parse_initial_pos(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(INITIAL_THICKNESS) {
+        if is_command(line, INITIAL_THICKNESS) {
             self.initial_thickness = parse_initial_thickness(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(BACKGROUND) {
+        if is_command(line, BACKGROUND) {
             self.background_color = parse_background_color(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(INITIAL_COLOR) {
+        if is_command(line, INITIAL_COLOR) {
             self.initial_color = parse_initial_color(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(CANVAS) {
+        if is_command(line, INITIAL_CAP) {
+            self.initial_stroke.cap = parse_line_cap(parse_argument(line, INITIAL_CAP)?)?;
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, INITIAL_JOIN) {
+            self.initial_stroke.join = parse_line_join(parse_argument(line, INITIAL_JOIN)?)?;
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, INITIAL_MITER_LIMIT) {
+            self.initial_stroke.miter_limit = parse_miter_limit(line)?;
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, INITIAL_DASH) {
+            self.initial_stroke.dashes = parse_initial_dash(line)?.into();
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, CANVAS) {
             self.canvas = parse_canvas(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(SEED) {
+        if is_command(line, SEED) {
             self.seed = parse_seed(line)?;
             return Ok(LineType::Config);
         }
 
-        if line.starts_with(INJECT) {
+        if is_command(line, INJECT) {
             self.injections = parse_injections(line)?;
             return Ok(LineType::Config);
         }
 
+        if is_command(line, AUTOFIT) {
+            self.autofit = Some(parse_autofit(line)?);
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, VIEW) {
+            self.viewport = Some(parse_viewport(line)?);
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, TROPISM) {
+            self.tropism = Some(parse_tropism(line)?);
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, CURVES) {
+            self.curves = Some(parse_curves(line)?);
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, STRICT) {
+            self.strict = Some(parse_strict(line)?);
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, CAMERA_YAW) {
+            self.camera.yaw = parse_camera_expression(line, CAMERA_YAW)?;
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, CAMERA_PITCH) {
+            self.camera.pitch = parse_camera_expression(line, CAMERA_PITCH)?;
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, CAMERA_DISTANCE) {
+            self.camera.distance = parse_camera_expression(line, CAMERA_DISTANCE)?;
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, CAMERA) {
+            self.camera.projection = Some(parse_camera(line)?);
+            return Ok(LineType::Config);
+        }
+
+        if is_command(line, ANIMATE) {
+            let (name, track) = parse_animation(line)?;
+            self.animations.insert(name, track);
+            return Ok(LineType::Variable);
+        }
+
+        if is_command(line, PALETTE) {
+            let (name, colors) = parse_palette(line)?;
+            self.palettes.insert(name, colors);
+            return Ok(LineType::Variable);
+        }
+
+        if is_function(line) {
+            let (name, function) = parse_function(line)?;
+            let old = self.functions.insert(name.clone(), function);
+
+            if functions::is_recursive(&self.functions, &name) {
+                // Restore the previous definition
+                match old {
+                    Some(old) => self.functions.insert(name, old),
+                    None => self.functions.remove(&name),
+                };
+                return Err(ParsingError::RecursiveFunction);
+            }
+
+            return Ok(LineType::Variable);
+        }
+
         if line.contains(RULE_DECLARATION) {
             let (character, rule) = parse_rule(line)?;
             self.lsystem.rules.insert(character, rule);
@@ -97,8 +225,9 @@ impl Updatable<&str> for LSystemRenderer {
         }
 
         if line.contains(OP_DECLARATION) {
-            let (character, operations) = parse_char_operation(line)?;
+            let (character, operations, sources) = parse_char_operation(line)?;
             self.operations.insert(character, operations);
+            self.sources.insert(character, sources);
             return Ok(LineType::Operation);
         }
 
@@ -114,16 +243,31 @@ impl Updatable<&str> for LSystemRenderer {
     fn get_line_type(&self, line: &str) -> LineType {
         if line.is_empty() {
             LineType::Nothing
-        } else if line.starts_with(AXIOM)
-            || line.starts_with(ITER)
-            || line.starts_with(INITIAL_ROT)
-            || line.starts_with(INITIAL_POS)
-            || line.starts_with(INITIAL_THICKNESS)
-            || line.starts_with(BACKGROUND)
-            || line.starts_with(INITIAL_COLOR)
-            || line.starts_with(CANVAS)
-            || line.starts_with(SEED)
-            || line.starts_with(INJECT)
+        } else if is_command(line, ANIMATE) || is_command(line, PALETTE) || is_function(line) {
+            LineType::Variable
+        } else if is_command(line, AXIOM)
+            || is_command(line, ITER)
+            || is_command(line, INITIAL_ROT)
+            || is_command(line, INITIAL_POS)
+            || is_command(line, INITIAL_THICKNESS)
+            || is_command(line, BACKGROUND)
+            || is_command(line, INITIAL_COLOR)
+            || is_command(line, INITIAL_CAP)
+            || is_command(line, INITIAL_JOIN)
+            || is_command(line, INITIAL_MITER_LIMIT)
+            || is_command(line, INITIAL_DASH)
+            || is_command(line, CANVAS)
+            || is_command(line, SEED)
+            || is_command(line, INJECT)
+            || is_command(line, CAMERA)
+            || is_command(line, CAMERA_YAW)
+            || is_command(line, CAMERA_PITCH)
+            || is_command(line, CAMERA_DISTANCE)
+            || is_command(line, AUTOFIT)
+            || is_command(line, CURVES)
+            || is_command(line, TROPISM)
+            || is_command(line, VIEW)
+            || is_command(line, STRICT)
         {
             LineType::Config
         } else if line[1..].trim().starts_with(RULE_DECLARATION) {
@@ -138,6 +282,12 @@ impl Updatable<&str> for LSystemRenderer {
     }
 }
 
+/// Whether the line starts with the command as a whole word,
+/// so that e.g. a variable named `iterations` is not taken for `iter`.
+pub fn is_command(line: &str, command: &str) -> bool {
+    line.split(' ').next() == Some(command)
+}
+
 fn parse_axiom(line: &str) -> Result<String> {
     let parts = line.split(' ').collect::<Vec<_>>();
     let axiom = parts.get(1).ok_or(ParsingError::InvalidFormat)?;
@@ -204,7 +354,7 @@ fn parse_injections(line: &str) -> Result<Vec<(u32, String)>> {
     let mut result = vec![];
 
     for part in parts {
-        let tuple: (u32, String) = parse_tuple_arg(&part)?;
+        let tuple: (u32, String) = parse_tuple_arg(part)?;
         if tuple.1.is_empty() {
             return Err(ParsingError::InvalidTuple);
         }
@@ -214,14 +364,305 @@ fn parse_injections(line: &str) -> Result<Vec<(u32, String)>> {
     Ok(result)
 }
 
-fn parse_char_operation(line: &str) -> Result<(char, Vec<Operation>)> {
+fn parse_autofit(line: &str) -> Result<Autofit> {
+    let mut autofit = Autofit::default();
+
+    // Both the margin and the unit of the widths are optional
+    let options = line.get(AUTOFIT.len()..).unwrap_or_default();
+    for option in options.split_whitespace() {
+        if option == SCREEN_UNITS {
+            autofit.scale_widths = false;
+        } else {
+            autofit.margin = option
+                .parse::<f64>()
+                .map_err(|_| ParsingError::InvalidFloatingPoint)?;
+        }
+    }
+
+    Ok(autofit)
+}
+
+fn parse_strict(line: &str) -> Result<Strict> {
+    // The maximum depth is optional
+    let max_depth = match line.get(STRICT.len()..).unwrap_or_default().trim() {
+        "" => None,
+        max_depth => Some(
+            max_depth
+                .parse::<usize>()
+                .map_err(|_| ParsingError::InvalidInteger)?,
+        ),
+    };
+
+    Ok(Strict { max_depth })
+}
+
+fn parse_curves(line: &str) -> Result<Curves> {
+    let mut options = line
+        .get((CURVES.len() + 1)..)
+        .ok_or(ParsingError::InvalidFormat)?
+        .split_whitespace();
+
+    let smoothing = match options.next() {
+        Some(JOINED) => Smoothing::Joined,
+        Some(CATMULL_ROM) => Smoothing::CatmullRom,
+        Some(BEZIER) => Smoothing::Bezier,
+        _ => return Err(ParsingError::InvalidFormat),
+    };
+
+    // The tension is optional
+    let tension = match options.next() {
+        Some(tension) => tension
+            .parse::<f64>()
+            .map_err(|_| ParsingError::InvalidFloatingPoint)?,
+        None => Curves::default().tension,
+    };
+
+    if options.next().is_some() {
+        return Err(ParsingError::InvalidFormat);
+    }
+
+    Ok(Curves { smoothing, tension })
+}
+
+// view <zoom>, <x>, <y>[, <angle>]
+fn parse_viewport(line: &str) -> Result<Viewport> {
+    let mut arguments = split_arguments(parse_argument(line, VIEW)?)
+        .into_iter()
+        .map(|argument| parse_expression(argument.trim()))
+        .collect::<Result<Vec<_>>>()?;
+
+    // The angle is optional
+    if arguments.len() == 3 {
+        arguments.push(parse_expression("0")?);
+    }
+
+    let [zoom, x, y, angle]: [meval::Expr; 4] = arguments
+        .try_into()
+        .map_err(|_| ParsingError::InvalidFormat)?;
+
+    Ok(Viewport {
+        zoom,
+        center: (x, y),
+        angle,
+    })
+}
+
+// tropism <x>,<y>[,<z>] <susceptibility>
+fn parse_tropism(line: &str) -> Result<Tropism> {
+    let (direction, susceptibility) = parse_argument(line, TROPISM)?
+        .split_once(' ')
+        .ok_or(ParsingError::InvalidFormat)?;
+
+    let components = direction
+        .split(TUPLE_SEPARATOR)
+        .map(|component| component.parse::<f64>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| ParsingError::InvalidTuple)?;
+
+    let direction = match components[..] {
+        [x, y] => [x, y, 0.0],
+        [x, y, z] => [x, y, z],
+        _ => return Err(ParsingError::InvalidTuple),
+    };
+
+    Ok(Tropism {
+        direction,
+        susceptibility: parse_expression(susceptibility.trim())?,
+    })
+}
+
+fn parse_camera(line: &str) -> Result<Projection> {
+    let projection = line
+        .get((CAMERA.len() + 1)..)
+        .ok_or(ParsingError::InvalidFormat)?;
+
+    match projection.trim() {
+        ORTHOGRAPHIC => Ok(Projection::Orthographic),
+        PERSPECTIVE => Ok(Projection::Perspective),
+        _ => Err(ParsingError::InvalidFormat),
+    }
+}
+
+fn parse_camera_expression(line: &str, command: &str) -> Result<meval::Expr> {
+    let expr = line
+        .get((command.len() + 1)..)
+        .ok_or(ParsingError::InvalidFormat)?;
+
+    parse_expression(expr.trim())
+}
+
+fn parse_animation(line: &str) -> Result<(String, Track)> {
+    let mut parts = line
+        .get((ANIMATE.len() + 1)..)
+        .ok_or(ParsingError::InvalidFormat)?
+        .split_whitespace();
+
+    let name = parts.next().ok_or(ParsingError::InvalidFormat)?.to_string();
+
+    let mut keyframes = vec![];
+    let mut unit = None;
+    let mut interpolation = Interpolation::Linear;
+    let mut playback = Playback::Once;
+
+    for part in parts {
+        match part {
+            LINEAR => interpolation = Interpolation::Linear,
+            STEP => interpolation = Interpolation::Step,
+            CUBIC => interpolation = Interpolation::Cubic,
+            EASE_IN => interpolation = Interpolation::EaseIn,
+            EASE_OUT => interpolation = Interpolation::EaseOut,
+            EASE_IN_OUT => interpolation = Interpolation::EaseInOut,
+            ONCE => playback = Playback::Once,
+            LOOP => playback = Playback::Loop,
+            PING_PONG => playback = Playback::PingPong,
+            _ => {
+                let (time, key_unit, value) = parse_keyframe(part)?;
+
+                // Every keyframe of a track must use the same time domain
+                if unit.is_some_and(|unit| unit != key_unit) {
+                    return Err(ParsingError::InvalidKeyframe);
+                }
+
+                unit = Some(key_unit);
+                keyframes.push((time, value));
+            }
+        }
+    }
+
+    let unit = unit.ok_or(ParsingError::InvalidKeyframe)?;
+
+    Ok((name, Track::new(keyframes, unit, interpolation, playback)))
+}
+
+fn parse_keyframe(keyframe: &str) -> Result<(f64, TimeUnit, f64)> {
+    let (time, value) = keyframe
+        .split_once(KEYFRAME_SEPARATOR)
+        .ok_or(ParsingError::InvalidKeyframe)?;
+
+    // The order matters since "ms" ends with "s"
+    let (time, unit, scale) = if let Some(time) = time.strip_suffix(MILLISECONDS) {
+        (time, TimeUnit::Millis, 1.0)
+    } else if let Some(time) = time.strip_suffix(SECONDS) {
+        (time, TimeUnit::Millis, 1000.0)
+    } else if let Some(time) = time.strip_suffix(FRAMES) {
+        (time, TimeUnit::Frames, 1.0)
+    } else {
+        return Err(ParsingError::InvalidKeyframe);
+    };
+
+    let time = time
+        .parse::<f64>()
+        .map_err(|_| ParsingError::InvalidKeyframe)?;
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| ParsingError::InvalidKeyframe)?;
+
+    Ok((time * scale, unit, value))
+}
+
+fn parse_palette(line: &str) -> Result<(String, Palette)> {
+    let line = line
+        .get((PALETTE.len() + 1)..)
+        .ok_or(ParsingError::InvalidFormat)?
+        .trim();
+    let (name, colors) = line.split_once(' ').ok_or(ParsingError::InvalidFormat)?;
+    let name = parse_identifier(name)?;
+
+    // Colors are separated by spaces, except inside of parentheses
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in colors.char_indices().chain([(colors.len(), ' ')]) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ' ' if depth == 0 => {
+                let color = colors[start..i].trim();
+                if !color.is_empty() {
+                    let color = csscolorparser::parse(color).map_err(|_| ParsingError::InvalidColor)?;
+                    result.push((color.r, color.g, color.b, color.a));
+                }
+                start = i;
+            }
+            _ => {}
+        }
+    }
+
+    if result.is_empty() {
+        return Err(ParsingError::InvalidFormat);
+    }
+
+    Ok((name, result))
+}
+
+fn is_function(line: &str) -> bool {
+    line.split(' ').next() == Some(FUNCTION)
+}
+
+fn parse_function(line: &str) -> Result<(String, Function)> {
+    let line = line
+        .get((FUNCTION.len() + 1)..)
+        .ok_or(ParsingError::InvalidFormat)?;
+
+    let (signature, body) = line
+        .split_once(VAR_DECLARATION)
+        .ok_or(ParsingError::InvalidFormat)?;
+    let (name, params) = signature
+        .trim()
+        .strip_suffix(')')
+        .and_then(|signature| signature.split_once('('))
+        .ok_or(ParsingError::InvalidFormat)?;
+
+    let name = parse_identifier(name)?;
+    let params = if params.trim().is_empty() {
+        vec![]
+    } else {
+        params
+            .split(TUPLE_SEPARATOR)
+            .map(parse_identifier)
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    // Every parameter must be unique
+    if (1..params.len()).any(|i| params[..i].contains(&params[i])) {
+        return Err(ParsingError::InvalidFormat);
+    }
+
+    let body = parse_expression(body.trim())?;
+
+    Ok((name, Function { params, body }))
+}
+
+fn parse_identifier(name: &str) -> Result<String> {
+    let name = name.trim();
+
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+
+    if !valid {
+        return Err(ParsingError::InvalidFormat);
+    }
+
+    if functions::is_reserved(name) {
+        return Err(ParsingError::ShadowedName);
+    }
+
+    Ok(name.to_string())
+}
+
+// The operations of a symbol, with their source text
+fn parse_char_operation(line: &str) -> Result<(char, Vec<Operation>, Vec<String>)> {
     if line.len() == 1 {
         return Err(ParsingError::InvalidFormat);
     }
     let character = line.chars().next().ok_or(ParsingError::InvalidFormat)?;
     let op = line.split_at(2).1.trim().to_string();
-    let op = parse_operations(&op)?;
-    Ok((character, op))
+    let operations = parse_operations(&op, 0)?;
+    let sources = operation_sources(&op)?;
+    Ok((character, operations, sources))
 }
 
 fn parse_rule(line: &str) -> Result<(char, String)> {
@@ -237,17 +678,13 @@ fn parse_rule(line: &str) -> Result<(char, String)> {
     Ok((character, rule))
 }
 
-fn parse_variable(line: &str) -> Result<(String, f64)> {
+fn parse_variable(line: &str) -> Result<(String, meval::Expr)> {
     let parts: Vec<&str> = line.split(VAR_DECLARATION).map(|s| s.trim()).collect();
     let name = parts
         .first()
         .ok_or(ParsingError::InvalidFormat)?
         .to_string();
-    let value: f64 = parts
-        .get(1)
-        .ok_or(ParsingError::InvalidFormat)?
-        .parse()
-        .map_err(|_| ParsingError::InvalidFloatingPoint)?;
+    let value = parse_expression(parts.get(1).ok_or(ParsingError::InvalidFormat)?)?;
     Ok((name, value))
 }
 
@@ -269,6 +706,85 @@ fn parse_initial_color(line: &str) -> Result<(f64, f64, f64, f64)> {
     Ok((color.r, color.g, color.b, color.a))
 }
 
+// The text after the command and a space
+fn parse_argument<'a>(line: &'a str, command: &str) -> Result<&'a str> {
+    line.get((command.len() + 1)..)
+        .map(str::trim)
+        .ok_or(ParsingError::InvalidFormat)
+}
+
+fn parse_line_cap(cap: &str) -> Result<LineCap> {
+    match cap {
+        BUTT => Ok(LineCap::Butt),
+        ROUND => Ok(LineCap::Round),
+        SQUARE => Ok(LineCap::Square),
+        _ => Err(ParsingError::InvalidFormat),
+    }
+}
+
+fn parse_line_join(join: &str) -> Result<LineJoin> {
+    match join {
+        MITER => Ok(LineJoin::Miter),
+        ROUND => Ok(LineJoin::Round),
+        BEVEL => Ok(LineJoin::Bevel),
+        _ => Err(ParsingError::InvalidFormat),
+    }
+}
+
+fn parse_miter_limit(line: &str) -> Result<f64> {
+    let limit = parse_argument(line, INITIAL_MITER_LIMIT)?
+        .parse::<f64>()
+        .map_err(|_| ParsingError::InvalidFloatingPoint)?;
+
+    if limit < 1.0 {
+        return Err(ParsingError::InvalidFloatingPoint);
+    }
+
+    Ok(limit)
+}
+
+fn parse_initial_dash(line: &str) -> Result<Vec<f64>> {
+    let dashes = parse_argument(line, INITIAL_DASH)?;
+    if dashes == SOLID {
+        return Ok(vec![]);
+    }
+
+    let dashes = dashes
+        .split(TUPLE_SEPARATOR)
+        .map(|dash| dash.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| ParsingError::InvalidFloatingPoint)?;
+
+    let valid = dashes.iter().all(|dash| *dash >= 0.0) && dashes.iter().sum::<f64>() > 0.0;
+    if !valid {
+        return Err(ParsingError::InvalidFloatingPoint);
+    }
+
+    Ok(dashes)
+}
+
+// Splits on the separators which are not inside parentheses
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in arguments.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&arguments[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&arguments[start..]);
+
+    result
+}
+
 fn parse_canvas(line: &str) -> Result<(i32, i32)> {
     let start = CANVAS.len() + 1;
     let line = line.get(start..).ok_or(ParsingError::InvalidFormat)?;
@@ -281,18 +797,145 @@ fn parse_canvas(line: &str) -> Result<(i32, i32)> {
     Ok(size)
 }
 
-fn parse_operations(operations: &str) -> Result<Vec<Operation>> {
+fn parse_operations(operations: &str, depth: usize) -> Result<Vec<Operation>> {
+    if depth > MAX_NESTING {
+        return Err(ParsingError::NestingTooDeep);
+    }
+
     let mut result = vec![];
 
-    let operations = operations.split(';');
+    let operations = split_operations(operations)?;
     for operation in operations {
-        result.push(parse_operation(operation.trim())?);
+        result.push(parse_operation(operation.trim(), depth)?);
+    }
+
+    Ok(result)
+}
+
+// Splits the operations on the separators which are not inside a block
+fn split_operations(operations: &str) -> Result<Vec<&str>> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut quoted = false;
+
+    for (i, c) in operations.char_indices() {
+        match c {
+            // Text is taken as it is
+            QUOTE => quoted = !quoted,
+            _ if quoted => {}
+            BLOCK_START => depth += 1,
+            BLOCK_END => {
+                if depth == 0 {
+                    return Err(ParsingError::UnbalancedBraces);
+                }
+                depth -= 1;
+            }
+            OP_SEPARATOR if depth == 0 => {
+                result.push(&operations[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return Err(ParsingError::UnbalancedBraces);
     }
+    result.push(&operations[start..]);
 
     Ok(result)
 }
 
-fn parse_operation(operation: &str) -> Result<Operation> {
+// Source text of the operations, each one followed by the ones of its blocks,
+// the else branch after the then branch
+fn operation_sources(operations: &str) -> Result<Vec<String>> {
+    let block_sources = |block: &str| {
+        if block.is_empty() {
+            Ok(vec![])
+        } else {
+            operation_sources(block)
+        }
+    };
+
+    let mut sources = vec![];
+    for operation in split_operations(operations)? {
+        let operation = operation.trim();
+        sources.push(operation.to_string());
+
+        if is_command(operation, IF) {
+            let (_, then_block, rest) = split_block(&operation[IF.len()..])?;
+            sources.extend(block_sources(then_block)?);
+
+            if let Some(rest) = rest.strip_prefix(ELSE).map(str::trim) {
+                if rest.starts_with(BLOCK_START) {
+                    sources.extend(block_sources(split_block(rest)?.1)?);
+                } else {
+                    // else if
+                    sources.extend(operation_sources(rest)?);
+                }
+            }
+        } else if is_command(operation, REPEAT) {
+            let (_, block, _) = split_block(&operation[REPEAT.len()..])?;
+            sources.extend(block_sources(block)?);
+        }
+    }
+
+    Ok(sources)
+}
+
+// Splits "<head> { <body> } <rest>" into its three parts
+fn split_block(operation: &str) -> Result<(&str, &str, &str)> {
+    let mut start = None;
+    let mut depth = 0;
+    let mut quoted = false;
+
+    for (i, c) in operation.char_indices() {
+        match c {
+            // Text is taken as it is
+            QUOTE => quoted = !quoted,
+            _ if quoted => {}
+            BLOCK_START => {
+                start.get_or_insert(i);
+                depth += 1;
+            }
+            BLOCK_END => {
+                let Some(start) = start else {
+                    return Err(ParsingError::UnbalancedBraces);
+                };
+
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((
+                        operation[..start].trim(),
+                        operation[(start + 1)..i].trim(),
+                        operation[(i + 1)..].trim(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match start {
+        Some(_) => Err(ParsingError::UnbalancedBraces),
+        None => Err(ParsingError::InvalidFormat),
+    }
+}
+
+fn parse_block(block: &str, depth: usize) -> Result<Vec<Operation>> {
+    if block.is_empty() {
+        return Ok(vec![]);
+    }
+
+    parse_operations(block, depth + 1)
+}
+
+fn parse_expression(expr: &str) -> Result<meval::Expr> {
+    expr.parse().map_err(|_| ParsingError::InvalidExpression)
+}
+
+fn parse_operation(operation: &str, depth: usize) -> Result<Operation> {
     let parts: Vec<&str> = operation.split(' ').collect();
     let part0 = parts.first().ok_or(ParsingError::InvalidFormat)?;
 
@@ -311,10 +954,74 @@ fn parse_operation(operation: &str) -> Result<Operation> {
         JUMP => Ok(Operation::Jump(parse_expr!())),
         DOT => Ok(Operation::Dot(parse_expr!())),
         ROTATE => Ok(Operation::Rotate(parse_expr!())),
+        PITCH => Ok(Operation::Pitch(parse_expr!())),
+        ROLL => Ok(Operation::Roll(parse_expr!())),
         THICKNESS => Ok(Operation::Thickness(parse_expr!())),
+        TROPISM => Ok(Operation::Tropism(parse_expr!())),
+        CAP => Ok(Operation::LineCap(parse_line_cap(parse_argument(operation, CAP)?)?)),
+        JOIN => Ok(Operation::LineJoin(parse_line_join(parse_argument(operation, JOIN)?)?)),
+        MITER_LIMIT => Ok(Operation::MiterLimit(parse_expr!())),
+        DASH => {
+            let dashes = parse_argument(operation, DASH)?;
+            if dashes == SOLID {
+                return Ok(Operation::Dash(vec![]));
+            }
+
+            let dashes = split_arguments(dashes)
+                .into_iter()
+                .map(|dash| parse_expression(dash.trim()))
+                .collect::<Result<_>>()?;
+            Ok(Operation::Dash(dashes))
+        }
         IGNORE => Ok(Operation::Ignore(parse_expr!())),
         PUSH => Ok(Operation::PushStack),
         POP => Ok(Operation::PopStack),
+        BEGIN_POLYGON => Ok(Operation::BeginPolygon),
+        VERTEX => Ok(Operation::Vertex),
+        END_POLYGON => Ok(Operation::EndPolygon),
+        IF => {
+            let (condition, then_block, rest) = split_block(&operation[IF.len()..])?;
+
+            let else_branch = if rest.is_empty() {
+                vec![]
+            } else {
+                let rest = rest
+                    .strip_prefix(ELSE)
+                    .ok_or(ParsingError::InvalidFormat)?
+                    .trim();
+
+                if rest.starts_with(BLOCK_START) {
+                    let (_, else_block, rest) = split_block(rest)?;
+                    if !rest.is_empty() {
+                        return Err(ParsingError::InvalidFormat);
+                    }
+                    parse_block(else_block, depth)?
+                } else if is_command(rest, IF) {
+                    vec![parse_operation(rest, depth + 1)?]
+                } else {
+                    // Anything else needs a block
+                    return Err(ParsingError::InvalidFormat);
+                }
+            };
+
+            Ok(Operation::If(
+                parse_expression(condition)?,
+                parse_block(then_block, depth)?,
+                else_branch,
+            ))
+        }
+        REPEAT => {
+            let (count, block, rest) = split_block(&operation[REPEAT.len()..])?;
+            if !rest.is_empty() {
+                return Err(ParsingError::InvalidFormat);
+            }
+
+            Ok(Operation::Repeat(
+                parse_expression(count)?,
+                parse_block(block, depth)?,
+            ))
+        }
+        TEXT => Ok(Operation::Text(parse_label(parse_argument(operation, TEXT)?)?)),
         COLOR => Ok(Operation::SetColor({
             let color = operation
                 .get((part0.len() + 1)..)
@@ -324,8 +1031,8 @@ fn parse_operation(operation: &str) -> Result<Operation> {
 
             if let Ok(color) = csscolor {
                 Color::Static((color.r, color.g, color.b, color.a))
-            } else if color.starts_with("rgb") {
-                // Check if it's an expression starting with "rgb"
+            } else if color.contains('(') {
+                // Check if it's an expression calling a color function
                 let expr = color
                     .parse()
                     .map_err(|_| ParsingError::InvalidExpression)?;
@@ -355,6 +1062,83 @@ fn parse_operation(operation: &str) -> Result<Operation> {
     }
 }
 
+// "<text>" [size <expr>] [anchor <start|middle|end>] [heading]
+fn parse_label(label: &str) -> Result<Label> {
+    let text = label.strip_prefix(QUOTE).ok_or(ParsingError::InvalidFormat)?;
+    let end = text.find(QUOTE).ok_or(ParsingError::InvalidFormat)?;
+    let (text, options) = (&text[..end], &text[(end + 1)..]);
+
+    let mut label = Label {
+        parts: parse_label_parts(text)?,
+        size: parse_expression(DEFAULT_TEXT_SIZE)?,
+        anchor: TextAnchor::Start,
+        follow_heading: false,
+    };
+
+    let mut words = options.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        match word {
+            SIZE => {
+                // The expression goes on until the next option
+                let mut size = vec![];
+                while let Some(word) = words.next_if(|word| ![SIZE, ANCHOR, HEADING].contains(word)) {
+                    size.push(word);
+                }
+                label.size = parse_expression(&size.join(" "))?;
+            }
+            ANCHOR => {
+                label.anchor = match words.next() {
+                    Some(START) => TextAnchor::Start,
+                    Some(MIDDLE) => TextAnchor::Middle,
+                    Some(END) => TextAnchor::End,
+                    _ => return Err(ParsingError::InvalidFormat),
+                };
+            }
+            HEADING => label.follow_heading = true,
+            _ => return Err(ParsingError::InvalidFormat),
+        }
+    }
+
+    Ok(label)
+}
+
+// Literal text with expressions between braces, e.g. "depth {DEPTH:.0}"
+fn parse_label_parts(text: &str) -> Result<Vec<LabelPart>> {
+    let mut parts = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        if start > 0 {
+            parts.push(LabelPart::Literal(rest[..start].to_string()));
+        }
+
+        // Closing braces before the placeholder are literal
+        let end = rest[start..]
+            .find(PLACEHOLDER_END)
+            .map(|end| start + end)
+            .ok_or(ParsingError::UnbalancedBraces)?;
+        let placeholder = rest[(start + 1)..end].trim();
+
+        let part = match placeholder.rsplit_once(PRECISION_SEPARATOR) {
+            _ if placeholder == SYMBOL => LabelPart::Symbol,
+            Some((expr, decimals)) => LabelPart::Value(
+                parse_expression(expr)?,
+                decimals.parse().map_err(|_| ParsingError::InvalidInteger)?,
+            ),
+            None => LabelPart::Value(parse_expression(placeholder)?, DEFAULT_DECIMALS),
+        };
+        parts.push(part);
+
+        rest = &rest[(end + 1)..];
+    }
+
+    if !rest.is_empty() {
+        parts.push(LabelPart::Literal(rest.to_string()));
+    }
+
+    Ok(parts)
+}
+
 fn parse_tuple_arg<T1: FromStr, T2: FromStr>(line: &str) -> Result<(T1, T2)> {
     let parts = line.split(TUPLE_SEPARATOR).collect::<Vec<_>>();
 
@@ -374,3 +1158,407 @@ fn parse_tuple_arg<T1: FromStr, T2: FromStr>(line: &str) -> Result<(T1, T2)> {
 
     Ok((v1, v2))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_commands() {
+        let mut renderer = LSystemRenderer::default();
+
+        // Variables whose name starts with a command
+        for line in ["iterations = 3", "seeds = 2", "canvasWidth = 1"] {
+            assert_eq!(renderer.get_line_type(line), LineType::Variable);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Variable);
+        }
+        assert_eq!(renderer.variables["iterations"], "3".parse().unwrap());
+
+        assert_eq!(renderer.update("iter 4").unwrap(), LineType::Config);
+        assert_eq!(renderer.iter, 4);
+    }
+
+    #[test]
+    fn animate() {
+        let mut renderer = LSystemRenderer::default();
+
+        let line = "animate angle 0f:0 60f:90 ease-in loop";
+        assert_eq!(renderer.get_line_type(line), LineType::Variable);
+        assert_eq!(renderer.update(line).unwrap(), LineType::Variable);
+        assert_eq!(
+            renderer.animations["angle"],
+            Track::new(
+                vec![(0.0, 0.0), (60.0, 90.0)],
+                TimeUnit::Frames,
+                Interpolation::EaseIn,
+                Playback::Loop
+            )
+        );
+
+        // Seconds are converted to milliseconds
+        renderer.update("animate size 2s:1 500ms:0").unwrap();
+        assert_eq!(renderer.animations["size"].keyframes, vec![(500.0, 0.0), (2000.0, 1.0)]);
+        assert_eq!(renderer.animations["size"].unit, TimeUnit::Millis);
+
+        // Keyframes of a track cannot mix frames and time
+        assert!(matches!(
+            renderer.update("animate size 0f:0 1s:1"),
+            Err(ParsingError::InvalidKeyframe)
+        ));
+        assert!(matches!(
+            renderer.update("animate size linear"),
+            Err(ParsingError::InvalidKeyframe)
+        ));
+
+        renderer.update("animated = 1").unwrap();
+        assert_eq!(renderer.variables["animated"], "1".parse().unwrap());
+    }
+
+    #[test]
+    fn palettes() {
+        let mut renderer = LSystemRenderer::default();
+
+        let line = "palette leaves #00ff00 rgba(0, 128, 0, 0.5) red";
+        assert_eq!(renderer.get_line_type(line), LineType::Variable);
+        assert_eq!(renderer.update(line).unwrap(), LineType::Variable);
+
+        let leaves = &renderer.palettes["leaves"];
+        assert_eq!(leaves.len(), 3);
+        assert_eq!(leaves[0], (0.0, 1.0, 0.0, 1.0));
+        assert_eq!(leaves[1].3, 0.5);
+        assert_eq!(leaves[2], (1.0, 0.0, 0.0, 1.0));
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("palette leaves"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("palette leaves #00ff00 nocolor"), ParsingError::InvalidColor));
+
+        renderer.update("paletteSize = 3").unwrap();
+        assert_eq!(renderer.variables["paletteSize"], "3".parse().unwrap());
+    }
+
+    #[test]
+    fn camera() {
+        let mut renderer = LSystemRenderer::default();
+
+        for line in ["camera perspective", "camera_yaw FRAME * 0.02", "camera_pitch -0.3", "camera_distance 1200"] {
+            assert_eq!(renderer.get_line_type(line), LineType::Config);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+        }
+        assert_eq!(renderer.camera.projection, Some(Projection::Perspective));
+        assert_eq!(renderer.camera.yaw, "FRAME * 0.02".parse().unwrap());
+        assert_eq!(renderer.camera.pitch, "-0.3".parse().unwrap());
+        assert_eq!(renderer.camera.distance, "1200".parse().unwrap());
+
+        renderer.update("camera orthographic").unwrap();
+        assert_eq!(renderer.camera.projection, Some(Projection::Orthographic));
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("camera fisheye"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("camera_yaw"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("camera_pitch 1 +"), ParsingError::InvalidExpression));
+
+        // Variables whose name starts with a camera command
+        for line in ["cameraX = 1", "camera_yawSpeed = 2"] {
+            assert_eq!(renderer.get_line_type(line), LineType::Variable);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Variable);
+        }
+
+        let operations = parse_operations("pitch BRANCH; roll 2.1", 0).unwrap();
+        assert!(matches!(operations[..], [Operation::Pitch(_), Operation::Roll(_)]));
+    }
+
+    #[test]
+    fn autofit() {
+        let autofit = |line| {
+            let mut renderer = LSystemRenderer::default();
+            assert_eq!(renderer.get_line_type(line), LineType::Config);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+            renderer.autofit.unwrap()
+        };
+
+        assert_eq!(autofit("autofit"), Autofit::default());
+        assert_eq!(autofit("autofit 5"), Autofit { margin: 5.0, scale_widths: true });
+        assert_eq!(autofit("autofit screen 0"), Autofit { margin: 0.0, scale_widths: false });
+
+        let mut renderer = LSystemRenderer::default();
+        assert!(matches!(renderer.update("autofit wide"), Err(ParsingError::InvalidFloatingPoint)));
+
+        renderer.update("autofitMargin = 3").unwrap();
+        assert_eq!(renderer.variables["autofitMargin"], "3".parse().unwrap());
+        assert_eq!(renderer.autofit, None);
+    }
+
+    #[test]
+    fn polygons() {
+        let mut renderer = LSystemRenderer::default();
+
+        let line = "{: begin_polygon; vertex";
+        assert_eq!(renderer.get_line_type(line), LineType::Operation);
+        assert_eq!(renderer.update(line).unwrap(), LineType::Operation);
+        assert!(matches!(renderer.operations[&'{'][..], [Operation::BeginPolygon, Operation::Vertex]));
+
+        let operations = parse_operations("jump 10; vertex; end_polygon", 0).unwrap();
+        assert!(matches!(operations[..], [Operation::Jump(_), Operation::Vertex, Operation::EndPolygon]));
+
+        assert!(matches!(parse_operations("begin_polygons", 0), Err(ParsingError::InvalidOperation)));
+    }
+
+    #[test]
+    fn curves() {
+        let curves = |line| {
+            let mut renderer = LSystemRenderer::default();
+            assert_eq!(renderer.get_line_type(line), LineType::Config);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+            renderer.curves.unwrap()
+        };
+
+        assert_eq!(curves("curves joined").smoothing, Smoothing::Joined);
+        assert_eq!(curves("curves bezier").tension, Curves::default().tension);
+        assert_eq!(
+            curves("curves catmull-rom 0.8"),
+            Curves { smoothing: Smoothing::CatmullRom, tension: 0.8 }
+        );
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("curves"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("curves spline"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("curves bezier tight"), ParsingError::InvalidFloatingPoint));
+        assert!(matches!(invalid("curves bezier 0.5 1"), ParsingError::InvalidFormat));
+
+        let mut renderer = LSystemRenderer::default();
+        renderer.update("curvesTension = 1").unwrap();
+        assert_eq!(renderer.variables["curvesTension"], "1".parse().unwrap());
+    }
+
+    #[test]
+    fn stroke_style() {
+        let mut renderer = LSystemRenderer::default();
+
+        let lines = ["initial_cap round", "initial_join bevel", "initial_miter_limit 4", "initial_dash 5, 2.5"];
+        for line in lines {
+            assert_eq!(renderer.get_line_type(line), LineType::Config);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+        }
+        assert_eq!(renderer.initial_stroke.cap, LineCap::Round);
+        assert_eq!(renderer.initial_stroke.join, LineJoin::Bevel);
+        assert_eq!(renderer.initial_stroke.miter_limit, 4.0);
+        assert_eq!(renderer.initial_stroke.dashes[..], [5.0, 2.5]);
+
+        renderer.update("initial_dash solid").unwrap();
+        assert!(renderer.initial_stroke.dashes.is_empty());
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("initial_cap bevel"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("initial_join square"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("initial_miter_limit 0.5"), ParsingError::InvalidFloatingPoint));
+        assert!(matches!(invalid("initial_dash 0, 0"), ParsingError::InvalidFloatingPoint));
+        assert!(matches!(invalid("initial_dash -1, 2"), ParsingError::InvalidFloatingPoint));
+
+        renderer.update("initial_capacity = 2").unwrap();
+        assert_eq!(renderer.variables["initial_capacity"], "2".parse().unwrap());
+
+        let operations = parse_operations("cap square; join round; miter_limit 2 * DEPTH; dash 4, DEPTH; dash solid", 0).unwrap();
+        let [Operation::LineCap(LineCap::Square), Operation::LineJoin(LineJoin::Round), Operation::MiterLimit(_), Operation::Dash(dashes), Operation::Dash(solid)] =
+            &operations[..]
+        else {
+            panic!("{operations:?}");
+        };
+        assert_eq!(dashes.len(), 2);
+        assert!(solid.is_empty());
+    }
+
+    #[test]
+    fn text() {
+        let operations = parse_operations(r#"text "{SYMBOL} at {DEPTH:.0} } {x + 1}" size 2 * DEPTH anchor middle heading"#, 0).unwrap();
+        let [Operation::Text(label)] = &operations[..] else {
+            panic!("{operations:?}");
+        };
+        let [LabelPart::Symbol, LabelPart::Literal(at), LabelPart::Value(_, 0), LabelPart::Literal(brace), LabelPart::Value(_, DEFAULT_DECIMALS)] =
+            &label.parts[..]
+        else {
+            panic!("{label:?}");
+        };
+        assert_eq!(at, " at ");
+        assert_eq!(brace, " } ");
+        assert_eq!(label.size, "2 * DEPTH".parse().unwrap());
+        assert_eq!(label.anchor, TextAnchor::Middle);
+        assert!(label.follow_heading);
+
+        let label = |text| match parse_operation(text, 0) {
+            Ok(Operation::Text(label)) => Ok(label),
+            other => Err(other.unwrap_err()),
+        };
+        assert_eq!(label(r#"text "plain""#).unwrap().size, DEFAULT_TEXT_SIZE.parse().unwrap());
+        assert!(matches!(label(r#"text "{DEPTH""#), Err(ParsingError::UnbalancedBraces)));
+        assert!(matches!(label(r#"text "{DEPTH:.two}""#), Err(ParsingError::InvalidInteger)));
+        assert!(matches!(label(r#"text "unclosed"#), Err(ParsingError::InvalidFormat)));
+        assert!(matches!(label(r#"text "a" anchor top"#), Err(ParsingError::InvalidFormat)));
+        assert!(matches!(label("text plain"), Err(ParsingError::InvalidFormat)));
+    }
+
+    #[test]
+    fn tropism() {
+        let mut renderer = LSystemRenderer::default();
+
+        let line = "tropism 0,1 0.2 * DEPTH";
+        assert_eq!(renderer.get_line_type(line), LineType::Config);
+        assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+        let tropism = renderer.tropism.as_ref().unwrap();
+        assert_eq!(tropism.direction, [0.0, 1.0, 0.0]);
+        assert_eq!(tropism.susceptibility, "0.2 * DEPTH".parse().unwrap());
+
+        renderer.update("tropism 1,0,-1 0.1").unwrap();
+        assert_eq!(renderer.tropism.as_ref().unwrap().direction, [1.0, 0.0, -1.0]);
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("tropism 0,1"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("tropism 1 0.2"), ParsingError::InvalidTuple));
+        assert!(matches!(invalid("tropism 0,down 0.2"), ParsingError::InvalidTuple));
+
+        renderer.update("tropismStrength = 0.3").unwrap();
+        assert_eq!(renderer.variables["tropismStrength"], "0.3".parse().unwrap());
+
+        let operations = parse_operations("tropism 0.5 * DEPTH", 0).unwrap();
+        assert!(matches!(operations[..], [Operation::Tropism(_)]));
+    }
+
+    #[test]
+    fn view() {
+        let mut renderer = LSystemRenderer::default();
+
+        let line = "view 2 ^ (FRAME / 60), 375, max(1, 2) * 100";
+        assert_eq!(renderer.get_line_type(line), LineType::Config);
+        assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+        let viewport = renderer.viewport.as_ref().unwrap();
+        assert_eq!(viewport.zoom, "2 ^ (FRAME / 60)".parse().unwrap());
+        assert_eq!(viewport.center.1, "max(1, 2) * 100".parse().unwrap());
+        assert_eq!(viewport.angle, "0".parse().unwrap());
+
+        renderer.update("view 1, 0, 0, FRAME * 0.01").unwrap();
+        assert_eq!(renderer.viewport.as_ref().unwrap().angle, "FRAME * 0.01".parse().unwrap());
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("view 1, 2"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("view 1, 2, 3, 4, 5"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("view 1, 2 +, 3"), ParsingError::InvalidExpression));
+
+        // e.g. the angle of view of the camera
+        renderer.update("viewAngle = 1").unwrap();
+        assert_eq!(renderer.variables["viewAngle"], "1".parse().unwrap());
+    }
+
+    #[test]
+    fn strict() {
+        let mut renderer = LSystemRenderer::default();
+
+        for line in ["strict", "strict 64"] {
+            assert_eq!(renderer.get_line_type(line), LineType::Config);
+            assert_eq!(renderer.update(line).unwrap(), LineType::Config);
+        }
+        assert_eq!(renderer.strict, Some(Strict { max_depth: Some(64) }));
+
+        renderer.update("strict").unwrap();
+        assert_eq!(renderer.strict, Some(Strict { max_depth: None }));
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("strict -1"), ParsingError::InvalidInteger));
+        assert!(matches!(invalid("strict deep"), ParsingError::InvalidInteger));
+
+        let mut renderer = LSystemRenderer::default();
+        renderer.update("strictness = 1").unwrap();
+        assert_eq!(renderer.variables["strictness"], "1".parse().unwrap());
+        assert_eq!(renderer.strict, None);
+    }
+
+    #[test]
+    fn blocks() {
+        let operations = parse_operations("if DEPTH - 1 { repeat 2 { forward 1; rotate 2 } } else { push }", 0).unwrap();
+        let [Operation::If(_, then_branch, else_branch)] = &operations[..] else {
+            panic!("{operations:?}");
+        };
+        let [Operation::Repeat(_, body)] = &then_branch[..] else {
+            panic!("{then_branch:?}");
+        };
+        assert!(matches!(body[..], [Operation::Forward(_), Operation::Rotate(_)]));
+        assert!(matches!(else_branch[..], [Operation::PushStack]));
+
+        // else if is an if inside of the else branch
+        let operations = parse_operations("if X { push } else if Y { pop } else { forward 1 }; pop", 0).unwrap();
+        let [Operation::If(_, _, else_branch), Operation::PopStack] = &operations[..] else {
+            panic!("{operations:?}");
+        };
+        let [Operation::If(_, then_branch, else_branch)] = &else_branch[..] else {
+            panic!("{else_branch:?}");
+        };
+        assert!(matches!(then_branch[..], [Operation::PopStack]));
+        assert!(matches!(else_branch[..], [Operation::Forward(_)]));
+
+        // The sources of the blocks follow the one of their operation
+        let sources = operation_sources("if X { push } else if Y { pop } else { forward 1; repeat 2 { dot 1 } }; pop");
+        assert_eq!(
+            sources.unwrap(),
+            [
+                "if X { push } else if Y { pop } else { forward 1; repeat 2 { dot 1 } }",
+                "push",
+                "if Y { pop } else { forward 1; repeat 2 { dot 1 } }",
+                "pop",
+                "forward 1",
+                "repeat 2 { dot 1 }",
+                "dot 1",
+                "pop",
+            ]
+        );
+
+        // Braces and separators inside of text do not count
+        let operations = parse_operations(r#"if 1 { text "a; {DEPTH} }" }; push"#, 0);
+        assert!(matches!(operations.as_deref(), Ok([Operation::If(..), Operation::PushStack])));
+
+        let invalid = |operations| parse_operations(operations, 0).unwrap_err();
+        assert!(matches!(invalid("if 1 { push } else pop"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("if 1 { push"), ParsingError::UnbalancedBraces));
+        assert!(matches!(invalid("repeat 2 } push {"), ParsingError::UnbalancedBraces));
+        assert!(matches!(invalid("repeat 2 { push } pop"), ParsingError::InvalidFormat));
+    }
+
+    #[test]
+    fn functions() {
+        let mut renderer = LSystemRenderer::default();
+
+        let line = "fn wobble(x, k) = sin(x * k) / k";
+        assert_eq!(renderer.get_line_type(line), LineType::Variable);
+        assert_eq!(renderer.update(line).unwrap(), LineType::Variable);
+        assert_eq!(renderer.functions["wobble"].params, ["x", "k"]);
+        renderer.update("fn two() = 2").unwrap();
+        assert!(renderer.functions["two"].params.is_empty());
+
+        // A function cannot call itself, even through another one
+        renderer.update("fn a(x) = x + 1").unwrap();
+        renderer.update("fn b(x) = a(x) * 2").unwrap();
+        assert!(matches!(renderer.update("fn a(x) = b(x)"), Err(ParsingError::RecursiveFunction)));
+        assert!(renderer.functions.contains_key("a"));
+
+        let invalid = |line| LSystemRenderer::default().update(line).unwrap_err();
+        assert!(matches!(invalid("fn sin(x) = x"), ParsingError::ShadowedName));
+        assert!(matches!(invalid("fn f(DEPTH) = DEPTH"), ParsingError::ShadowedName));
+        assert!(matches!(invalid("fn f(x, x) = x"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("fn f x = x"), ParsingError::InvalidFormat));
+        assert!(matches!(invalid("fn f(x) = x +"), ParsingError::InvalidExpression));
+
+        // Variables can call them
+        assert_eq!(renderer.update("size = wobble(2, 1) + 1").unwrap(), LineType::Variable);
+
+        renderer.update("fnord = 1").unwrap();
+        assert_eq!(renderer.variables["fnord"], "1".parse().unwrap());
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| {
+            let operations = "repeat 2 { ".repeat(depth) + "forward 1" + &" }".repeat(depth);
+            parse_operations(&operations, 0)
+        };
+
+        assert!(nested(MAX_NESTING).is_ok());
+        assert!(matches!(nested(MAX_NESTING + 1), Err(ParsingError::NestingTooDeep)));
+    }
+}
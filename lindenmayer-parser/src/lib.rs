@@ -17,10 +17,26 @@ pub const INITIAL_ROT: &str = "initial_rot";
 pub const INITIAL_POS: &str = "initial_pos";
 pub const INITIAL_THICKNESS: &str = "initial_thickness";
 pub const INITIAL_COLOR: &str = "initial_color";
+pub const INITIAL_CAP: &str = "initial_cap";
+pub const INITIAL_JOIN: &str = "initial_join";
+pub const INITIAL_MITER_LIMIT: &str = "initial_miter_limit";
+pub const INITIAL_DASH: &str = "initial_dash";
 pub const BACKGROUND: &str = "background";
 pub const CANVAS: &str = "canvas";
 pub const SEED: &str = "seed";
 pub const INJECT: &str = "inject";
+pub const ANIMATE: &str = "animate";
+pub const FUNCTION: &str = "fn";
+pub const PALETTE: &str = "palette";
+pub const CAMERA: &str = "camera";
+pub const CAMERA_YAW: &str = "camera_yaw";
+pub const CAMERA_PITCH: &str = "camera_pitch";
+pub const CAMERA_DISTANCE: &str = "camera_distance";
+pub const AUTOFIT: &str = "autofit";
+pub const CURVES: &str = "curves";
+pub const TROPISM: &str = "tropism";
+pub const VIEW: &str = "view";
+pub const STRICT: &str = "strict";
 pub const COMMENT: &str = ";";
 
 // Symbols
@@ -28,14 +44,90 @@ pub const OP_DECLARATION: &str = ":";
 pub const RULE_DECLARATION: &str = "->";
 pub const VAR_DECLARATION: &str = "=";
 pub const TUPLE_SEPARATOR: &str = ",";
+pub const KEYFRAME_SEPARATOR: &str = ":";
+pub const OP_SEPARATOR: char = ';';
+pub const BLOCK_START: char = '{';
+pub const BLOCK_END: char = '}';
+pub const QUOTE: char = '"';
+pub const PLACEHOLDER_START: char = '{';
+pub const PLACEHOLDER_END: char = '}';
+pub const PRECISION_SEPARATOR: &str = ":.";
+
+// Maximum number of nested blocks in an operation list
+pub const MAX_NESTING: usize = 16;
+
+// Keyframe units
+pub const SECONDS: &str = "s";
+pub const MILLISECONDS: &str = "ms";
+pub const FRAMES: &str = "f";
+
+// Interpolations
+pub const LINEAR: &str = "linear";
+pub const STEP: &str = "step";
+pub const CUBIC: &str = "cubic";
+pub const EASE_IN: &str = "ease-in";
+pub const EASE_OUT: &str = "ease-out";
+pub const EASE_IN_OUT: &str = "ease-in-out";
+
+// Playback modes
+pub const ONCE: &str = "once";
+pub const LOOP: &str = "loop";
+pub const PING_PONG: &str = "ping-pong";
+
+// Projections
+pub const ORTHOGRAPHIC: &str = "orthographic";
+pub const PERSPECTIVE: &str = "perspective";
+
+// Smoothings of the paths
+pub const JOINED: &str = "joined";
+pub const CATMULL_ROM: &str = "catmull-rom";
+pub const BEZIER: &str = "bezier";
+
+// Line caps and joins
+pub const BUTT: &str = "butt";
+pub const ROUND: &str = "round";
+pub const SQUARE: &str = "square";
+pub const MITER: &str = "miter";
+pub const BEVEL: &str = "bevel";
+
+// Text options
+pub const SIZE: &str = "size";
+pub const ANCHOR: &str = "anchor";
+pub const HEADING: &str = "heading";
+pub const START: &str = "start";
+pub const MIDDLE: &str = "middle";
+pub const END: &str = "end";
+// Placeholder of the symbol being executed
+pub const SYMBOL: &str = "SYMBOL";
+pub const DEFAULT_TEXT_SIZE: &str = "12";
+pub const DEFAULT_DECIMALS: usize = 2;
+
+// Dash pattern of solid lines
+pub const SOLID: &str = "solid";
+
+// Line widths which are not scaled by autofit
+pub const SCREEN_UNITS: &str = "screen";
 
 // Operations
 pub const FORWARD: &str = "forward";
 pub const JUMP: &str = "jump";
 pub const DOT: &str = "dot";
 pub const ROTATE: &str = "rotate";
+pub const PITCH: &str = "pitch";
+pub const ROLL: &str = "roll";
 pub const THICKNESS: &str = "thickness";
+pub const CAP: &str = "cap";
+pub const JOIN: &str = "join";
+pub const MITER_LIMIT: &str = "miter_limit";
+pub const DASH: &str = "dash";
 pub const IGNORE: &str = "ignore";
 pub const PUSH: &str = "push";
 pub const POP: &str = "pop";
-pub const COLOR: &str = "color";
\ No newline at end of file
+pub const BEGIN_POLYGON: &str = "begin_polygon";
+pub const VERTEX: &str = "vertex";
+pub const END_POLYGON: &str = "end_polygon";
+pub const COLOR: &str = "color";
+pub const TEXT: &str = "text";
+pub const IF: &str = "if";
+pub const ELSE: &str = "else";
+pub const REPEAT: &str = "repeat";
\ No newline at end of file
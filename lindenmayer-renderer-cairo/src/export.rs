@@ -0,0 +1,143 @@
+use crate::CairoCanvas;
+use cairo::{Context, PdfSurface, PsSurface, Surface, SvgSurface};
+use lindenmayer_renderer::canvas::{Canvas, ExprContext, RenderError};
+use lindenmayer_renderer::LSystemRenderer;
+use std::path::Path;
+
+/// Vector formats of the exported files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Pdf,
+    Ps,
+    // Encapsulated PostScript, for including the drawing in other documents
+    Eps,
+    Svg,
+}
+
+/// Physical page the drawing is fitted to, keeping its proportions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    // Size in millimetres
+    pub width: f64,
+    pub height: f64,
+    // Resolution of the parts that cairo cannot keep as vectors, e.g. transparent ones
+    pub dpi: f64,
+}
+
+impl Page {
+    pub const A4: Page = Page {
+        width: 210.0,
+        height: 297.0,
+        dpi: 300.0,
+    };
+    pub const A3: Page = Page {
+        width: 297.0,
+        height: 420.0,
+        dpi: 300.0,
+    };
+    pub const LETTER: Page = Page {
+        width: 215.9,
+        height: 279.4,
+        dpi: 300.0,
+    };
+
+    // Size in points, the unit of the cairo vector surfaces
+    fn points(&self) -> (f64, f64) {
+        let points = |millimetres: f64| millimetres / 25.4 * 72.0;
+        (points(self.width), points(self.height))
+    }
+}
+
+/// Variables of a frame of the animation, played at the given frame rate.
+pub fn frame_variables<'a>(frame: u32, fps: f64) -> ExprContext<'a> {
+    let mut variables = ExprContext::new();
+    variables.var("FRAME", frame as f64);
+    variables.var("TIME", frame as f64 * 1000.0 / fps);
+    variables
+}
+
+/// Draws a frame of the fractal to a file, on a single page.
+pub fn export<P: AsRef<Path>>(
+    fractal: &LSystemRenderer,
+    variables: &mut ExprContext,
+    format: Format,
+    page: &Page,
+    path: P,
+) -> Result<(), RenderError> {
+    let size = page.points();
+    let surface = match format {
+        Format::Pdf => {
+            let surface = PdfSurface::new(size.0, size.1, path).map_err(cairo_error)?;
+            Surface::clone(&surface)
+        }
+        Format::Ps | Format::Eps => {
+            let surface = PsSurface::new(size.0, size.1, path).map_err(cairo_error)?;
+            surface.set_eps(format == Format::Eps);
+            Surface::clone(&surface)
+        }
+        Format::Svg => {
+            let surface = SvgSurface::new(size.0, size.1, Some(path)).map_err(cairo_error)?;
+            Surface::clone(&surface)
+        }
+    };
+    surface.set_fallback_resolution(page.dpi, page.dpi);
+
+    let cr = Context::new(&surface).map_err(cairo_error)?;
+    draw_page(&cr, fractal, variables, size)?;
+    cr.show_page().map_err(cairo_error)?;
+
+    surface.finish();
+    surface.status().map_err(cairo_error)
+}
+
+/// Writes a PDF with a page for each fractal and variables,
+/// e.g. the frames of an animation or variants of a grammar.
+pub fn export_pdf_pages<'a, 'b, P, I>(pages: I, page: &Page, path: P) -> Result<(), RenderError>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (&'a LSystemRenderer, ExprContext<'b>)>,
+{
+    let size = page.points();
+    let surface = PdfSurface::new(size.0, size.1, path).map_err(cairo_error)?;
+    surface.set_fallback_resolution(page.dpi, page.dpi);
+
+    let cr = Context::new(&surface).map_err(cairo_error)?;
+    for (fractal, mut variables) in pages {
+        draw_page(&cr, fractal, &mut variables, size)?;
+        cr.show_page().map_err(cairo_error)?;
+    }
+
+    surface.finish();
+    surface.status().map_err(cairo_error)
+}
+
+// Fits the canvas of the fractal to the page, centered
+fn draw_page(
+    cr: &Context,
+    fractal: &LSystemRenderer,
+    variables: &mut ExprContext,
+    page: (f64, f64),
+) -> Result<(), RenderError> {
+    let (width, height) = (fractal.canvas.0 as f64, fractal.canvas.1 as f64);
+    // The scale would not be finite, which cairo rejects
+    if width <= 0.0 || height <= 0.0 {
+        let error = format!("cannot fit a canvas of {width}x{height} to the page");
+        return Err(RenderError::Canvas(error.into()));
+    }
+    let scale = (page.0 / width).min(page.1 / height);
+
+    cr.save().map_err(cairo_error)?;
+    cr.translate((page.0 - width * scale) / 2.0, (page.1 - height * scale) / 2.0);
+    cr.scale(scale, scale);
+    cr.rectangle(0.0, 0.0, width, height);
+    cr.clip();
+
+    let canvas: &dyn Canvas = &CairoCanvas(cr.clone());
+    canvas.draw_fractal(fractal, variables)?;
+
+    cr.restore().map_err(cairo_error)
+}
+
+fn cairo_error(error: cairo::Error) -> RenderError {
+    RenderError::Canvas(Box::new(error))
+}
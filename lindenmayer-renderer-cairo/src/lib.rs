@@ -1,46 +1,173 @@
 use cairo::Context;
-use lindenmayer_renderer::canvas::Canvas;
+use lindenmayer_renderer::canvas::{Canvas, CanvasResult, LineCap, LineJoin, TextAnchor};
+use lindenmayer_renderer::display_list::Transform;
+
+pub mod export;
 
 pub struct CairoCanvas(pub Context);
 
+impl CairoCanvas {
+    // Operations which do not return a result leave the error in the context
+    fn status(&self) -> CanvasResult {
+        Ok(Context::status(&self.0)?)
+    }
+}
+
 impl Canvas for CairoCanvas {
-    fn move_to(&self, x: f64, y: f64) {
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult {
         Context::move_to(&self.0, x, y);
+        self.status()
     }
 
-    fn line_to(&self, x: f64, y: f64) {
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult {
         Context::line_to(&self.0, x, y);
+        self.status()
+    }
+
+    fn stroke(&self) -> CanvasResult {
+        Ok(Context::stroke(&self.0)?)
+    }
+
+    fn save(&self) -> CanvasResult {
+        Ok(Context::save(&self.0)?)
+    }
+
+    fn restore(&self) -> CanvasResult {
+        Ok(Context::restore(&self.0)?)
+    }
+
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult {
+        Context::translate(&self.0, dx, dy);
+        self.status()
     }
 
-    fn stroke(&self) {
-        let _ = Context::stroke(&self.0);
+    fn rotate(&self, angle: f64) -> CanvasResult {
+        Context::rotate(&self.0, angle);
+        self.status()
     }
 
-    fn save(&self) {
-        let _ = Context::save(&self.0);
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult {
+        Context::scale(&self.0, sx, sy);
+        self.status()
     }
 
-    fn restore(&self) {
-        let _ = Context::restore(&self.0);
+    fn set_transform(&self, transform: &Transform) -> CanvasResult {
+        let matrix = cairo::Matrix::new(
+            transform.xx,
+            transform.yx,
+            transform.xy,
+            transform.yy,
+            transform.x0,
+            transform.y0,
+        );
+        Context::set_matrix(&self.0, matrix);
+        self.status()
     }
 
-    fn set_line_width(&self, thickness: f64) {
+    fn clip_rect(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        Context::rectangle(&self.0, x, y, width, height);
+        Context::clip(&self.0);
+        self.status()
+    }
+
+    fn clip_path(&self) -> CanvasResult {
+        Context::clip(&self.0);
+        self.status()
+    }
+
+    fn set_line_width(&self, thickness: f64) -> CanvasResult {
         Context::set_line_width(&self.0, thickness);
+        self.status()
     }
 
-    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) {
+    fn set_line_cap(&self, cap: LineCap) -> CanvasResult {
+        let cap = match cap {
+            LineCap::Butt => cairo::LineCap::Butt,
+            LineCap::Round => cairo::LineCap::Round,
+            LineCap::Square => cairo::LineCap::Square,
+        };
+        Context::set_line_cap(&self.0, cap);
+        self.status()
+    }
+
+    fn set_line_join(&self, join: LineJoin) -> CanvasResult {
+        let join = match join {
+            LineJoin::Miter => cairo::LineJoin::Miter,
+            LineJoin::Round => cairo::LineJoin::Round,
+            LineJoin::Bevel => cairo::LineJoin::Bevel,
+        };
+        Context::set_line_join(&self.0, join);
+        self.status()
+    }
+
+    fn set_miter_limit(&self, limit: f64) -> CanvasResult {
+        Context::set_miter_limit(&self.0, limit);
+        self.status()
+    }
+
+    fn set_dash(&self, dashes: &[f64]) -> CanvasResult {
+        Context::set_dash(&self.0, dashes, 0.0);
+        self.status()
+    }
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
         Context::rectangle(&self.0, x, y, width, height);
+        self.status()
     }
 
-    fn set_color(&self, r: f64, g: f64, b: f64, a: f64) {
+    fn set_color(&self, r: f64, g: f64, b: f64, a: f64) -> CanvasResult {
         Context::set_source_rgba(&self.0, r, g, b, a);
+        self.status()
     }
 
-    fn arc(&self, x: f64, y: f64, r: f64) {
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult {
         Context::arc(&self.0, x, y, r, 0.0, 2.0 * std::f64::consts::PI);
+        self.status()
+    }
+
+    fn fill(&self) -> CanvasResult {
+        Ok(Context::fill(&self.0)?)
     }
-    
-    fn fill(&self) {
-        let _ = Context::fill(&self.0);
+
+    fn close_path(&self) -> CanvasResult {
+        Context::close_path(&self.0);
+        self.status()
+    }
+
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult {
+        Context::curve_to(&self.0, x1, y1, x2, y2, x3, y3);
+        self.status()
+    }
+
+    fn text(
+        &self,
+        x: f64,
+        y: f64,
+        text: &str,
+        size: f64,
+        angle: f64,
+        anchor: TextAnchor,
+    ) -> CanvasResult {
+        let cr = &self.0;
+
+        cr.save()?;
+        cr.set_font_size(size);
+        cr.translate(x, y);
+        cr.rotate(angle);
+
+        // The transformation is restored even if the text cannot be drawn
+        let result = (|| {
+            let width = cr.text_extents(text)?.x_advance();
+            let offset = match anchor {
+                TextAnchor::Start => 0.0,
+                TextAnchor::Middle => width / 2.0,
+                TextAnchor::End => width,
+            };
+            cr.move_to(-offset, 0.0);
+            cr.show_text(text)
+        })();
+
+        cr.restore()?;
+        Ok(result?)
     }
 }
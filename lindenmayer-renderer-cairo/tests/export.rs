@@ -0,0 +1,83 @@
+use lindenmayer_examples::variables;
+use lindenmayer_renderer::LSystemRenderer;
+use lindenmayer_renderer_cairo::export::{export, export_pdf_pages, frame_variables, Format, Page};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn stickman() -> LSystemRenderer {
+    lindenmayer_examples::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples/stickman.lsys"))
+}
+
+// Empty directory for the files of a test
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("lindenmayer-export-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+// Number in the text after the given key, e.g. the number of pages
+fn number_after(text: &str, key: &str) -> Option<usize> {
+    let start = text.find(key)? + key.len();
+    let digits = text[start..].trim_start().split(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
+}
+
+#[test]
+fn formats() {
+    let directory = directory("formats");
+    let fractal = stickman();
+
+    let formats = [
+        (Format::Pdf, "pdf", "%PDF-"),
+        (Format::Ps, "ps", "%!PS-Adobe-3.0\n"),
+        (Format::Eps, "eps", "%!PS-Adobe-3.0 EPSF-3.0"),
+        (Format::Svg, "svg", "<?xml"),
+    ];
+    for (format, extension, header) in formats {
+        let path = directory.join(format!("stickman.{extension}"));
+        export(&fractal, &mut variables(0.0, 0.0), format, &Page::A4, &path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with(header), "header of {format:?}");
+        match format {
+            Format::Pdf => assert_eq!(number_after(&text, "/Count"), Some(1)),
+            Format::Ps | Format::Eps => assert_eq!(number_after(&text, "%%Pages:"), Some(1)),
+            Format::Svg => assert!(text.contains("<svg")),
+        }
+    }
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn pages() {
+    let directory = directory("pages");
+    let fractal = stickman();
+
+    let path = directory.join("frames.pdf");
+    let pages = (0..3).map(|frame| (&fractal, frame_variables(frame, 60.0)));
+    export_pdf_pages(pages, &Page::LETTER, &path).unwrap();
+
+    let text = String::from_utf8_lossy(&fs::read(&path).unwrap()).to_string();
+    assert!(text.starts_with("%PDF-"));
+    assert_eq!(number_after(&text, "/Count"), Some(3));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn empty_canvas() {
+    let directory = directory("empty");
+    let fractal = LSystemRenderer {
+        canvas: (0, 0),
+        ..stickman()
+    };
+
+    let path = directory.join("empty.pdf");
+    let error = export(&fractal, &mut variables(0.0, 0.0), Format::Pdf, &Page::A4, &path).unwrap_err();
+    assert_eq!(error.to_string(), "canvas error: cannot fit a canvas of 0x0 to the page");
+
+    fs::remove_dir_all(directory).unwrap();
+}
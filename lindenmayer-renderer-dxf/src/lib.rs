@@ -0,0 +1,703 @@
+use lindenmayer_renderer::backend::{channel, num, GraphicState, Path};
+use lindenmayer_renderer::canvas::{
+    Canvas, CanvasResult, ExprContext, LineCap, LineJoin, RenderError,
+};
+use lindenmayer_renderer::display_list::{DisplayList, Point, Primitive, Transform};
+use lindenmayer_renderer::LSystemRenderer;
+use std::cell::RefCell;
+use std::fmt::{Display, Write};
+
+// Length of the lines which curves are made of, in pixels
+const TOLERANCE: f64 = 0.5;
+
+// Decimals of the numbers in the file
+const DECIMALS: usize = 6;
+
+// Lineweights known to CAD tools, in hundredths of a millimetre
+const LINEWEIGHTS: [i32; 24] = [
+    0, 5, 9, 13, 15, 18, 20, 25, 30, 35, 40, 50, 53, 60, 70, 80, 90, 100, 106, 120, 140, 158, 200,
+    211,
+];
+
+// Lineweight of the fills, the default one of the CAD tool
+const DEFAULT_LINEWEIGHT: i32 = -3;
+
+// Handle of the record of the model space, which owns the entities.
+// The other handles are numbered after it, in the order they are given
+const MODEL_SPACE: &str = "1";
+
+// Tables without records, which CAD tools expect anyway
+const EMPTY_TABLES: [&str; 3] = ["VPORT", "VIEW", "UCS"];
+
+/// Length unit of the drawing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Units {
+    Unitless,
+    Inches,
+    Feet,
+    Millimetres,
+    Centimetres,
+    Metres,
+}
+
+impl Units {
+    // Value of `$INSUNITS` in the header
+    fn code(self) -> i32 {
+        match self {
+            Units::Unitless => 0,
+            Units::Inches => 1,
+            Units::Feet => 2,
+            Units::Millimetres => 4,
+            Units::Centimetres => 5,
+            Units::Metres => 6,
+        }
+    }
+
+    // Length of a unit, for the lineweights. Units are millimetres if unknown
+    fn millimetres(self) -> f64 {
+        match self {
+            Units::Unitless | Units::Millimetres => 1.0,
+            Units::Inches => 25.4,
+            Units::Feet => 304.8,
+            Units::Centimetres => 10.0,
+            Units::Metres => 1000.0,
+        }
+    }
+}
+
+/// How the entities are split between layers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layers {
+    // Everything is on the layer "0"
+    Single,
+    // A layer for each depth of the stack of the turtle, e.g. "DEPTH_2"
+    ByDepth,
+    // A layer for each color, e.g. "COLOR_FF0000"
+    ByColor,
+}
+
+/// How the drawing is written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setup {
+    pub units: Units,
+    // Length of a pixel of the canvas, in units
+    pub scale: f64,
+    pub layers: Layers,
+    // Whether the background is written too, as a closed polyline
+    pub background: bool,
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self {
+            units: Units::Millimetres,
+            scale: 1.0,
+            layers: Layers::ByDepth,
+            background: false,
+        }
+    }
+}
+
+/// Canvas writing a DXF drawing, with `LINE`, `LWPOLYLINE` and `CIRCLE` entities.
+/// The file has the structure of AutoCAD 2000, with the version of AutoCAD 2004
+/// which is the first one with true colors.
+/// Strokes keep their color and width, fills and dots become their outline.
+/// The y axis goes up, with the origin at the bottom left corner of the canvas.
+/// Clips and text are not supported.
+pub struct DxfCanvas {
+    width: f64,
+    height: f64,
+    setup: Setup,
+    dxf: RefCell<Dxf>,
+}
+
+#[derive(Default)]
+struct Dxf {
+    entities: String,
+    // Names and colors of the layers, in the order of their first entity
+    layers: Vec<(String, [u8; 3])>,
+    state: GraphicState<Style>,
+    path: Path,
+    depth: usize,
+    // Number of handles given after the one of the model space
+    handles: u32,
+}
+
+// Style of the graphic state
+#[derive(Clone)]
+struct Style {
+    color: [u8; 3],
+    width: f64,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            color: [0, 0, 0],
+            width: 2.0,
+        }
+    }
+}
+
+impl Dxf {
+    // Next handle, in hexadecimal
+    fn handle(&mut self) -> String {
+        self.handles += 1;
+        format!("{:X}", self.handles + 1)
+    }
+}
+
+impl DxfCanvas {
+    /// Drawing of a canvas of the given size in pixels.
+    pub fn new(width: f64, height: f64, setup: Setup) -> Self {
+        Self {
+            width,
+            height,
+            setup,
+            dxf: RefCell::default(),
+        }
+    }
+
+    /// Number of states on the stack of the turtle when the next shapes were drawn,
+    /// which is their layer with [`Layers::ByDepth`].
+    pub fn set_depth(&self, depth: usize) {
+        self.dxf.borrow_mut().depth = depth;
+    }
+
+    /// The DXF file.
+    pub fn finish(self) -> String {
+        let mut dxf = self.dxf.into_inner();
+        let paper_space = dxf.handle();
+
+        let mut tables = String::new();
+        write_tables(&mut tables, &mut dxf, &paper_space);
+
+        // The blocks of the spaces are empty, the entities are in their own section
+        let mut blocks = String::new();
+        for (name, record) in [
+            ("*Model_Space", MODEL_SPACE),
+            ("*Paper_Space", &paper_space),
+        ] {
+            let out = &mut blocks;
+            pair(out, 0, "BLOCK");
+            pair(out, 5, dxf.handle());
+            pair(out, 330, record);
+            pair(out, 100, "AcDbEntity");
+            pair(out, 8, "0");
+            pair(out, 100, "AcDbBlockBegin");
+            pair(out, 2, name);
+            pair(out, 70, 0);
+            pair(out, 10, 0);
+            pair(out, 20, 0);
+            pair(out, 30, 0);
+            pair(out, 3, name);
+            pair(out, 1, "");
+            pair(out, 0, "ENDBLK");
+            pair(out, 5, dxf.handle());
+            pair(out, 330, record);
+            pair(out, 100, "AcDbEntity");
+            pair(out, 8, "0");
+            pair(out, 100, "AcDbBlockEnd");
+        }
+
+        // The root dictionary, with the one of the groups
+        let (root, groups) = (dxf.handle(), dxf.handle());
+        let mut objects = String::new();
+        let out = &mut objects;
+        pair(out, 0, "DICTIONARY");
+        pair(out, 5, &root);
+        pair(out, 330, 0);
+        pair(out, 100, "AcDbDictionary");
+        pair(out, 281, 1);
+        pair(out, 3, "ACAD_GROUP");
+        pair(out, 350, &groups);
+        pair(out, 0, "DICTIONARY");
+        pair(out, 5, &groups);
+        pair(out, 330, &root);
+        pair(out, 100, "AcDbDictionary");
+        pair(out, 281, 1);
+
+        let mut document = String::new();
+        let out = &mut document;
+        section(out, "HEADER");
+        pair(out, 9, "$ACADVER");
+        pair(out, 1, "AC1018");
+        // Handle after the last one given
+        pair(out, 9, "$HANDSEED");
+        pair(out, 5, dxf.handle());
+        pair(out, 9, "$INSUNITS");
+        pair(out, 70, self.setup.units.code());
+        pair(out, 9, "$EXTMIN");
+        pair(out, 10, 0);
+        pair(out, 20, 0);
+        pair(out, 30, 0);
+        pair(out, 9, "$EXTMAX");
+        pair(out, 10, num(self.width * self.setup.scale, DECIMALS));
+        pair(out, 20, num(self.height * self.setup.scale, DECIMALS));
+        pair(out, 30, 0);
+        pair(out, 0, "ENDSEC");
+
+        section(out, "CLASSES");
+        pair(out, 0, "ENDSEC");
+
+        let sections = [
+            ("TABLES", &tables),
+            ("BLOCKS", &blocks),
+            ("ENTITIES", &dxf.entities),
+            ("OBJECTS", &objects),
+        ];
+        for (name, content) in sections {
+            section(out, name);
+            out.push_str(content);
+            pair(out, 0, "ENDSEC");
+        }
+        pair(out, 0, "EOF");
+
+        document
+    }
+}
+
+/// Draws a frame of the fractal as a DXF drawing of the size of its canvas.
+pub fn render(
+    fractal: &LSystemRenderer,
+    variables: &mut ExprContext,
+    setup: &Setup,
+) -> Result<String, RenderError> {
+    let mut display_list = DisplayList::clone(&*fractal.display_list(variables)?);
+    if !setup.background {
+        display_list
+            .primitives
+            .retain(|primitive| !matches!(primitive, Primitive::Fill { .. }));
+    }
+
+    let canvas = DxfCanvas::new(
+        fractal.canvas.0 as f64,
+        fractal.canvas.1 as f64,
+        setup.clone(),
+    );
+    // Fills keep the layer of the previous shapes
+    display_list.replay_with(&canvas, |primitive| {
+        if let Some(depth) = primitive.depth() {
+            canvas.set_depth(depth);
+        }
+        Ok(())
+    })?;
+
+    Ok(canvas.finish())
+}
+
+impl DxfCanvas {
+    fn transform(&self, x: f64, y: f64) -> Point {
+        self.dxf.borrow().state.apply(x, y)
+    }
+
+    // Position in the drawing of a point in pixels
+    fn position(&self, (x, y): Point) -> (String, String) {
+        let scale = self.setup.scale;
+        (
+            num(x * scale, DECIMALS),
+            num((self.height - y) * scale, DECIMALS),
+        )
+    }
+
+    // Writes the start of an entity, on the layer of the current state
+    fn entity(&self, dxf: &mut Dxf, kind: &str, subclass: &str, lineweight: i32) {
+        let color = dxf.state.style.color;
+        let layer = match self.setup.layers {
+            Layers::Single => String::from("0"),
+            Layers::ByDepth => format!("DEPTH_{}", dxf.depth),
+            Layers::ByColor => format!("COLOR_{:02X}{:02X}{:02X}", color[0], color[1], color[2]),
+        };
+        if !dxf.layers.iter().any(|(name, _)| *name == layer) {
+            dxf.layers.push((layer.clone(), color));
+        }
+
+        let handle = dxf.handle();
+        let out = &mut dxf.entities;
+        pair(out, 0, kind);
+        pair(out, 5, handle);
+        pair(out, 330, MODEL_SPACE);
+        pair(out, 100, "AcDbEntity");
+        pair(out, 8, layer);
+        pair(out, 420, true_color(color));
+        pair(out, 370, lineweight);
+        pair(out, 100, subclass);
+    }
+
+    fn circle(&self, dxf: &mut Dxf, center: Point, radius: f64, lineweight: i32) {
+        if !finite(&[center, (radius, 0.0)]) {
+            return;
+        }
+        self.entity(dxf, "CIRCLE", "AcDbCircle", lineweight);
+
+        let (x, y) = self.position(center);
+        let out = &mut dxf.entities;
+        pair(out, 10, x);
+        pair(out, 20, y);
+        pair(out, 30, 0);
+        pair(out, 40, num(radius * self.setup.scale, DECIMALS));
+    }
+
+    fn polyline(&self, dxf: &mut Dxf, points: &[Point], closed: bool, lineweight: i32) {
+        if !finite(points) {
+            return;
+        }
+
+        // Two points are a line
+        if let ([from, to], false) = (points, closed) {
+            self.entity(dxf, "LINE", "AcDbLine", lineweight);
+
+            let ((x1, y1), (x2, y2)) = (self.position(*from), self.position(*to));
+            let out = &mut dxf.entities;
+            pair(out, 10, x1);
+            pair(out, 20, y1);
+            pair(out, 30, 0);
+            pair(out, 11, x2);
+            pair(out, 21, y2);
+            pair(out, 31, 0);
+            return;
+        }
+
+        // The last point of closed polylines is implied
+        let points = match points {
+            [first, rest @ .., last] if closed && first == last => &points[..rest.len() + 1],
+            points => points,
+        };
+
+        self.entity(dxf, "LWPOLYLINE", "AcDbPolyline", lineweight);
+        let out = &mut dxf.entities;
+        pair(out, 90, points.len());
+        pair(out, 70, if closed { 1 } else { 0 });
+        for point in points {
+            let (x, y) = self.position(*point);
+            pair(out, 10, x);
+            pair(out, 20, y);
+        }
+    }
+
+    // Standard lineweight closest to the width of the lines
+    fn lineweight(&self, state: &GraphicState<Style>) -> i32 {
+        let width = state.style.width * state.transform.scale_factor() * self.setup.scale;
+        let hundredths = width * self.setup.units.millimetres() * 100.0;
+
+        let distance = |weight: &i32| (*weight as f64 - hundredths).abs();
+        let nearest = LINEWEIGHTS
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+        *nearest.unwrap_or(&0)
+    }
+}
+
+impl Canvas for DxfCanvas {
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult {
+        let point = self.transform(x, y);
+        self.dxf.borrow_mut().path.move_to(point);
+        Ok(())
+    }
+
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult {
+        let point = self.transform(x, y);
+        self.dxf.borrow_mut().path.line_to(point);
+        Ok(())
+    }
+
+    fn stroke(&self) -> CanvasResult {
+        let mut dxf = self.dxf.borrow_mut();
+        let lineweight = self.lineweight(&dxf.state);
+
+        for subpath in dxf.path.take() {
+            match subpath.circle {
+                Some((center, radius)) => self.circle(&mut dxf, center, radius, lineweight),
+                None if subpath.points.len() > 1 => {
+                    self.polyline(&mut dxf, &subpath.points, subpath.closed, lineweight)
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> CanvasResult {
+        self.dxf.borrow_mut().state.save();
+        Ok(())
+    }
+
+    fn restore(&self) -> CanvasResult {
+        self.dxf.borrow_mut().state.restore()
+    }
+
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult {
+        self.dxf.borrow_mut().state.translate(dx, dy);
+        Ok(())
+    }
+
+    fn rotate(&self, angle: f64) -> CanvasResult {
+        self.dxf.borrow_mut().state.rotate(angle);
+        Ok(())
+    }
+
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult {
+        self.dxf.borrow_mut().state.scale(sx, sy);
+        Ok(())
+    }
+
+    fn set_transform(&self, transform: &Transform) -> CanvasResult {
+        self.dxf.borrow_mut().state.transform = *transform;
+        Ok(())
+    }
+
+    fn clip_rect(&self, _x: f64, _y: f64, _width: f64, _height: f64) -> CanvasResult {
+        Ok(())
+    }
+
+    fn clip_path(&self) -> CanvasResult {
+        self.dxf.borrow_mut().path.take();
+        Ok(())
+    }
+
+    fn set_line_width(&self, thickness: f64) -> CanvasResult {
+        self.dxf.borrow_mut().state.style.width = thickness;
+        Ok(())
+    }
+
+    // CAD tools draw lines with their own caps, joins and linetypes
+    fn set_line_cap(&self, _cap: LineCap) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_line_join(&self, _join: LineJoin) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_miter_limit(&self, _limit: f64) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_dash(&self, _dashes: &[f64]) -> CanvasResult {
+        Ok(())
+    }
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        let mut dxf = self.dxf.borrow_mut();
+        let transform = dxf.state.transform;
+        dxf.path.rectangle(&transform, x, y, width, height);
+        Ok(())
+    }
+
+    fn set_color(&self, r: f64, g: f64, b: f64, _a: f64) -> CanvasResult {
+        self.dxf.borrow_mut().state.style.color = [channel(r), channel(g), channel(b)];
+        Ok(())
+    }
+
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult {
+        let mut dxf = self.dxf.borrow_mut();
+        let transform = dxf.state.transform;
+        dxf.path.arc(&transform, x, y, r, TOLERANCE);
+        Ok(())
+    }
+
+    fn fill(&self) -> CanvasResult {
+        let mut dxf = self.dxf.borrow_mut();
+        for subpath in dxf.path.take() {
+            match subpath.circle {
+                Some((center, radius)) => self.circle(&mut dxf, center, radius, DEFAULT_LINEWEIGHT),
+                None if subpath.points.len() > 1 => {
+                    self.polyline(&mut dxf, &subpath.points, true, DEFAULT_LINEWEIGHT)
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn close_path(&self) -> CanvasResult {
+        self.dxf.borrow_mut().path.close();
+        Ok(())
+    }
+
+    // Flattened into lines, polylines have no curves
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult {
+        let controls = [
+            self.transform(x1, y1),
+            self.transform(x2, y2),
+            self.transform(x3, y3),
+        ];
+        self.dxf.borrow_mut().path.curve_to(controls, TOLERANCE);
+        Ok(())
+    }
+}
+
+// Writes a group code and its value, each on its own line
+fn pair(out: &mut String, code: i32, value: impl Display) {
+    writeln!(out, "{code}\n{value}").unwrap();
+}
+
+fn section(out: &mut String, name: &str) {
+    pair(out, 0, "SECTION");
+    pair(out, 2, name);
+}
+
+// Writes the tables, with the records that CAD tools expect and the layers of the entities
+fn write_tables(out: &mut String, dxf: &mut Dxf, paper_space: &str) {
+    for name in EMPTY_TABLES {
+        table(out, dxf, name, 0);
+        pair(out, 0, "ENDTAB");
+    }
+
+    let ltype = table(out, dxf, "LTYPE", 3);
+    for (name, description) in [
+        ("ByBlock", ""),
+        ("ByLayer", ""),
+        ("Continuous", "Solid line"),
+    ] {
+        record(out, dxf, "LTYPE", &ltype, "AcDbLinetypeTableRecord");
+        pair(out, 2, name);
+        pair(out, 70, 0);
+        pair(out, 3, description);
+        pair(out, 72, 65);
+        pair(out, 73, 0);
+        pair(out, 40, 0);
+    }
+    pair(out, 0, "ENDTAB");
+
+    // The layer "0" always exists, without a color of its own if no entity is on it
+    let mut layers = dxf
+        .layers
+        .iter()
+        .map(|(name, color)| (name.clone(), Some(*color)))
+        .collect::<Vec<_>>();
+    if !layers.iter().any(|(name, _)| name == "0") {
+        layers.insert(0, (String::from("0"), None));
+    }
+
+    let layer = table(out, dxf, "LAYER", layers.len());
+    for (name, color) in layers {
+        record(out, dxf, "LAYER", &layer, "AcDbLayerTableRecord");
+        pair(out, 2, name);
+        pair(out, 70, 0);
+        pair(out, 62, 7);
+        if let Some(color) = color {
+            pair(out, 420, true_color(color));
+        }
+        pair(out, 6, "Continuous");
+        pair(out, 370, DEFAULT_LINEWEIGHT);
+    }
+    pair(out, 0, "ENDTAB");
+
+    let style = table(out, dxf, "STYLE", 1);
+    record(out, dxf, "STYLE", &style, "AcDbTextStyleTableRecord");
+    pair(out, 2, "Standard");
+    pair(out, 70, 0);
+    pair(out, 40, 0);
+    pair(out, 41, 1);
+    pair(out, 50, 0);
+    pair(out, 71, 0);
+    pair(out, 42, 2.5);
+    pair(out, 3, "txt");
+    pair(out, 4, "");
+    pair(out, 0, "ENDTAB");
+
+    let appid = table(out, dxf, "APPID", 1);
+    record(out, dxf, "APPID", &appid, "AcDbRegAppTableRecord");
+    pair(out, 2, "ACAD");
+    pair(out, 70, 0);
+    pair(out, 0, "ENDTAB");
+
+    // Dimension styles have their handle in the group 105 instead of 5
+    let dimstyle = table(out, dxf, "DIMSTYLE", 1);
+    pair(out, 100, "AcDbDimStyleTable");
+    pair(out, 0, "DIMSTYLE");
+    pair(out, 105, dxf.handle());
+    pair(out, 330, &dimstyle);
+    pair(out, 100, "AcDbSymbolTableRecord");
+    pair(out, 100, "AcDbDimStyleTableRecord");
+    pair(out, 2, "Standard");
+    pair(out, 70, 0);
+    pair(out, 0, "ENDTAB");
+
+    let block_record = table(out, dxf, "BLOCK_RECORD", 2);
+    for (name, handle) in [("*Model_Space", MODEL_SPACE), ("*Paper_Space", paper_space)] {
+        pair(out, 0, "BLOCK_RECORD");
+        pair(out, 5, handle);
+        pair(out, 330, &block_record);
+        pair(out, 100, "AcDbSymbolTableRecord");
+        pair(out, 100, "AcDbBlockTableRecord");
+        pair(out, 2, name);
+    }
+    pair(out, 0, "ENDTAB");
+}
+
+// Writes the start of a table with the given number of records, returns its handle
+fn table(out: &mut String, dxf: &mut Dxf, name: &str, records: usize) -> String {
+    let handle = dxf.handle();
+    pair(out, 0, "TABLE");
+    pair(out, 2, name);
+    pair(out, 5, &handle);
+    pair(out, 330, 0);
+    pair(out, 100, "AcDbSymbolTable");
+    pair(out, 70, records);
+    handle
+}
+
+// Writes the start of a record of a table
+fn record(out: &mut String, dxf: &mut Dxf, kind: &str, table: &str, subclass: &str) {
+    pair(out, 0, kind);
+    pair(out, 5, dxf.handle());
+    pair(out, 330, table);
+    pair(out, 100, "AcDbSymbolTableRecord");
+    pair(out, 100, subclass);
+}
+
+fn true_color([r, g, b]: [u8; 3]) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+// CAD tools cannot read shapes with infinite or NaN coordinates, they are left out
+fn finite(points: &[Point]) -> bool {
+    points
+        .iter()
+        .all(|point| point.0.is_finite() && point.1.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities() {
+        let setup = Setup {
+            units: Units::Centimetres,
+            scale: 0.5,
+            ..Setup::default()
+        };
+        let dxf = DxfCanvas::new(100.0, 100.0, setup);
+        let canvas: &dyn Canvas = &dxf;
+
+        canvas.set_line_width(0.05).unwrap();
+        canvas.move_to(0.0, 0.0).unwrap();
+        canvas.line_to(10.0, 0.0).unwrap();
+        canvas.stroke().unwrap();
+
+        dxf.set_depth(1);
+        canvas.set_color(1.0, 0.0, 0.0, 1.0).unwrap();
+        canvas.arc(50.0, 50.0, 4.0).unwrap();
+        canvas.fill().unwrap();
+
+        let text = dxf.finish();
+        let entities = text
+            .split("ENTITIES\n")
+            .nth(1)
+            .unwrap()
+            .split("0\nENDSEC\n")
+            .next()
+            .unwrap();
+        assert_eq!(
+            entities,
+            "0\nLINE\n5\n2\n330\n1\n100\nAcDbEntity\n8\nDEPTH_0\n420\n0\n370\n25\n100\nAcDbLine\n\
+            10\n0\n20\n50\n30\n0\n11\n5\n21\n50\n31\n0\n\
+            0\nCIRCLE\n5\n3\n330\n1\n100\nAcDbEntity\n8\nDEPTH_1\n420\n16711680\n370\n-3\n100\nAcDbCircle\n\
+            10\n25\n20\n25\n30\n0\n40\n2\n"
+        );
+        assert!(text.contains("9\n$INSUNITS\n70\n5\n"));
+    }
+}
@@ -0,0 +1,241 @@
+use lindenmayer_examples::variables;
+use lindenmayer_renderer::display_list::{PathSegment, Point, Primitive, Transform};
+use lindenmayer_renderer_dxf::{Layers, Setup, Units};
+use std::collections::HashSet;
+
+// Precision of the numbers in the file
+const EPSILON: f64 = 1e-5;
+
+#[derive(Debug, Default)]
+struct Entity {
+    kind: String,
+    layer: String,
+    color: u32,
+    points: Vec<Point>,
+    radius: f64,
+    closed: bool,
+    subclasses: Vec<String>,
+}
+
+// Entities of the file, with the group codes of the points in pairs
+fn parse(dxf: &str) -> Vec<Entity> {
+    let lines = dxf.lines().collect::<Vec<_>>();
+    let pairs = lines
+        .chunks(2)
+        .map(|pair| (pair[0].trim().parse::<i32>().unwrap(), pair[1]));
+
+    let mut entities: Vec<Entity> = vec![];
+    let mut in_entities = false;
+    let mut x = 0.0;
+    for (code, value) in pairs {
+        match (code, value) {
+            (2, "ENTITIES") => in_entities = true,
+            (0, "ENDSEC") => in_entities = false,
+            (0, kind) if in_entities => entities.push(Entity {
+                kind: kind.to_string(),
+                ..Entity::default()
+            }),
+            _ if !in_entities => {}
+            (_, value) => {
+                let entity = entities.last_mut().unwrap();
+                match code {
+                    8 => entity.layer = value.to_string(),
+                    420 => entity.color = value.parse().unwrap(),
+                    10 | 11 => x = value.parse().unwrap(),
+                    20 | 21 => entity.points.push((x, value.parse().unwrap())),
+                    40 => entity.radius = value.parse().unwrap(),
+                    70 => entity.closed = value == "1",
+                    100 => entity.subclasses.push(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    entities
+}
+
+// Checks the sections of the file and that its handles are unique and refer to each other
+fn check_structure(dxf: &str, example: &str) {
+    let lines = dxf.lines().collect::<Vec<_>>();
+    let pairs = lines
+        .chunks(2)
+        .map(|pair| (pair[0].trim().parse::<i32>().unwrap(), pair[1]))
+        .collect::<Vec<_>>();
+
+    let sections = pairs
+        .windows(2)
+        .filter(|pairs| pairs[0] == (0, "SECTION"))
+        .map(|pairs| pairs[1].1)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sections,
+        ["HEADER", "CLASSES", "TABLES", "BLOCKS", "ENTITIES", "OBJECTS"],
+        "{example:?}"
+    );
+    assert_eq!(pairs.last(), Some(&(0, "EOF")));
+
+    // The seed follows its variable in the header, the other handles are in the groups 5 and 105
+    let seed = pairs
+        .windows(2)
+        .find(|pairs| pairs[0] == (9, "$HANDSEED"))
+        .unwrap()[1]
+        .1;
+    let seed = u32::from_str_radix(seed, 16).unwrap();
+    let mut handles = HashSet::new();
+    for pair in pairs
+        .windows(2)
+        .filter(|pairs| pairs[0].0 != 9)
+        .map(|pairs| pairs[1])
+    {
+        if let (5 | 105, handle) = pair {
+            let handle = u32::from_str_radix(handle, 16).unwrap();
+            assert!(
+                handles.insert(handle),
+                "handle {handle:X} of {example:?} is not unique"
+            );
+            assert!(
+                handle < seed,
+                "handle {handle:X} of {example:?} is after the seed"
+            );
+        }
+    }
+
+    for (_, owner) in pairs.iter().filter(|(code, _)| [330, 350].contains(code)) {
+        let owner = u32::from_str_radix(owner, 16).unwrap();
+        assert!(
+            owner == 0 || handles.contains(&owner),
+            "owner {owner:X} of {example:?} does not exist"
+        );
+    }
+}
+
+// Shapes with infinite or NaN coordinates are not written
+fn finite(primitive: &Primitive) -> bool {
+    let finite = |point: &Point| point.0.is_finite() && point.1.is_finite();
+    match primitive {
+        Primitive::Segment { from, to, .. } => finite(from) && finite(to),
+        Primitive::Path {
+            start, segments, ..
+        } => {
+            finite(start)
+                && segments.iter().all(|segment| match segment {
+                    PathSegment::Line(to) => finite(to),
+                    PathSegment::Cubic(points) => points.iter().all(finite),
+                })
+        }
+        Primitive::Dot { center, radius, .. } => finite(center) && radius.is_finite(),
+        Primitive::Polygon { points, .. } => points.iter().all(finite),
+        Primitive::Text { .. } | Primitive::Fill { .. } => true,
+    }
+}
+
+fn assert_close(actual: Point, expected: Point) {
+    let close = (actual.0 - expected.0).abs() < EPSILON && (actual.1 - expected.1).abs() < EPSILON;
+    assert!(close, "{actual:?} is not {expected:?}");
+}
+
+#[test]
+fn examples() {
+    let setup = Setup {
+        units: Units::Millimetres,
+        scale: 0.25,
+        layers: Layers::ByDepth,
+        background: false,
+    };
+    for (example, renderer) in lindenmayer_examples::all() {
+        let dxf =
+            lindenmayer_renderer_dxf::render(&renderer, &mut variables(0.0, 0.0), &setup).unwrap();
+        let display_list = renderer.display_list(&mut variables(0.0, 0.0)).unwrap();
+        let entities = parse(&dxf);
+        check_structure(&dxf, &example);
+
+        // Position in the drawing of a point of the display list
+        let view = display_list
+            .view
+            .map(|view| view.transform())
+            .unwrap_or_default();
+        let height = renderer.canvas.1 as f64;
+        let position = |point: &Point| {
+            let (x, y) = view.apply(*point);
+            (x * setup.scale, (height - y) * setup.scale)
+        };
+
+        let primitives = display_list
+            .primitives
+            .iter()
+            .filter(|primitive| {
+                !matches!(primitive, Primitive::Fill { .. } | Primitive::Text { .. })
+            })
+            .filter(|primitive| finite(primitive))
+            .collect::<Vec<_>>();
+        assert_eq!(entities.len(), primitives.len(), "entities of {example:?}");
+
+        for (entity, primitive) in entities.iter().zip(primitives) {
+            let color = match primitive {
+                Primitive::Segment {
+                    from, to, color, ..
+                } => {
+                    assert_eq!(entity.kind, "LINE");
+                    assert_close(entity.points[0], position(from));
+                    assert_close(entity.points[1], position(to));
+                    color
+                }
+                Primitive::Path {
+                    start,
+                    segments,
+                    color,
+                    ..
+                } => {
+                    let end = match segments.last() {
+                        Some(PathSegment::Line(end)) | Some(PathSegment::Cubic([_, _, end])) => end,
+                        None => start,
+                    };
+                    assert!(["LINE", "LWPOLYLINE"].contains(&entity.kind.as_str()));
+                    assert_close(entity.points[0], position(start));
+                    assert_close(*entity.points.last().unwrap(), position(end));
+                    color
+                }
+                Primitive::Dot {
+                    center,
+                    radius,
+                    color,
+                    ..
+                } => {
+                    assert_eq!(entity.kind, "CIRCLE");
+                    assert_close(entity.points[0], position(center));
+                    let radius = radius * Transform::scale_factor(&view) * setup.scale;
+                    assert!((entity.radius - radius).abs() < EPSILON);
+                    color
+                }
+                Primitive::Polygon { points, color, .. } => {
+                    assert_eq!(entity.kind, "LWPOLYLINE");
+                    assert!(entity.closed);
+                    assert_eq!(entity.points.len(), points.len());
+                    for (actual, expected) in entity.points.iter().zip(points) {
+                        assert_close(*actual, position(expected));
+                    }
+                    color
+                }
+                Primitive::Text { .. } | Primitive::Fill { .. } => unreachable!(),
+            };
+
+            let subclass = match entity.kind.as_str() {
+                "LINE" => "AcDbLine",
+                "CIRCLE" => "AcDbCircle",
+                _ => "AcDbPolyline",
+            };
+            assert_eq!(entity.subclasses, ["AcDbEntity", subclass]);
+
+            let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u32;
+            assert_eq!(
+                entity.color,
+                channel(color.0) << 16 | channel(color.1) << 8 | channel(color.2)
+            );
+            assert_eq!(
+                entity.layer,
+                format!("DEPTH_{}", primitive.depth().unwrap())
+            );
+        }
+    }
+}
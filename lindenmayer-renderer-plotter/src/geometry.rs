@@ -0,0 +1,143 @@
+use lindenmayer_renderer::display_list::Point;
+
+/// Whether the point is inside the polygons, with the even-odd rule.
+pub fn inside(point: Point, polygons: &[Vec<Point>]) -> bool {
+    let mut inside = false;
+    for polygon in polygons {
+        for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+            if (a.1 > point.1) != (b.1 > point.1) {
+                let x = a.0 + (point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+                if point.0 < x {
+                    inside = !inside;
+                }
+            }
+        }
+    }
+
+    inside
+}
+
+/// Parts of the polyline inside the polygons, with the even-odd rule.
+pub fn clip(polyline: &[Point], polygons: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let mut parts = vec![];
+    let mut current: Vec<Point> = vec![];
+
+    for pair in polyline.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+
+        // Where the segment crosses the edges of the polygons
+        let mut cuts = vec![0.0, 1.0];
+        for polygon in polygons {
+            for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+                cuts.extend(intersection((from, to), (*a, *b)));
+            }
+        }
+        cuts.sort_by(f64::total_cmp);
+
+        let at = |t: f64| (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+        for range in cuts.windows(2) {
+            if range[1] - range[0] < 1e-12 {
+                continue;
+            }
+
+            if inside(at((range[0] + range[1]) / 2.0), polygons) {
+                if current.is_empty() {
+                    current.push(at(range[0]));
+                }
+                current.push(at(range[1]));
+            } else if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if current.len() > 1 {
+        parts.push(current);
+    }
+
+    parts
+}
+
+// Position along the first segment where it crosses the second one
+fn intersection(first: (Point, Point), second: (Point, Point)) -> Option<f64> {
+    let d1 = (first.1 .0 - first.0 .0, first.1 .1 - first.0 .1);
+    let d2 = (second.1 .0 - second.0 .0, second.1 .1 - second.0 .1);
+    let denominator = d1.0 * d2.1 - d1.1 * d2.0;
+    if denominator.abs() < 1e-12 {
+        return None;
+    }
+
+    let offset = (second.0 .0 - first.0 .0, second.0 .1 - first.0 .1);
+    let t = (offset.0 * d2.1 - offset.1 * d2.0) / denominator;
+    let u = (offset.0 * d1.1 - offset.1 * d1.0) / denominator;
+
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Diagonal lines filling the polygons, `spacing` apart.
+/// Every other line goes the opposite way, so that the pen zig-zags.
+pub fn hatch(polygons: &[Vec<Point>], spacing: f64) -> Vec<Vec<Point>> {
+    let points = polygons.iter().flatten();
+    let (mut min, mut max) = (f64::MAX, f64::MIN);
+    let (mut low, mut high) = (f64::MAX, f64::MIN);
+    for point in points {
+        // Lines have a constant x + y, they are ordered along x - y
+        min = min.min(point.0 + point.1);
+        max = max.max(point.0 + point.1);
+        low = low.min(point.0 - point.1);
+        high = high.max(point.0 - point.1);
+    }
+    if spacing <= 0.0 || min > max {
+        return vec![];
+    }
+
+    let step = spacing * std::f64::consts::SQRT_2;
+    let mut lines = vec![];
+    let mut sum = min + step / 2.0;
+    let mut reversed = false;
+    while sum < max {
+        // The points where x - y is `low` and `high`
+        let from = ((sum + low) / 2.0, (sum - low) / 2.0);
+        let to = ((sum + high) / 2.0, (sum - high) / 2.0);
+
+        let line = if reversed { [to, from] } else { [from, to] };
+        lines.extend(clip(&line, polygons));
+        sum += step;
+        reversed = !reversed;
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lindenmayer_renderer::backend::distance;
+
+    #[test]
+    fn clipping() {
+        let square = vec![vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]];
+
+        // The line enters and leaves the square
+        let parts = clip(&[(-5.0, 5.0), (5.0, 5.0), (15.0, 5.0)], &square);
+        assert_eq!(parts, vec![vec![(0.0, 5.0), (5.0, 5.0), (10.0, 5.0)]]);
+        assert!(clip(&[(-5.0, -5.0), (-5.0, 15.0)], &square).is_empty());
+
+        // A hole is left out
+        let mut frame = square.clone();
+        frame.push(vec![(2.0, 2.0), (8.0, 2.0), (8.0, 8.0), (2.0, 8.0)]);
+        assert_eq!(clip(&[(-5.0, 5.0), (15.0, 5.0)], &frame).len(), 2);
+
+        // Lines a unit apart cover the square
+        let lines = hatch(&square, 1.0);
+        let length = lines
+            .iter()
+            .flat_map(|line| line.windows(2))
+            .map(|pair| distance(pair[0], pair[1]))
+            .sum::<f64>();
+        assert!((length - 100.0).abs() < 1.0);
+        assert!(lines
+            .iter()
+            .flatten()
+            .all(|point| (-1e-9..=10.0 + 1e-9).contains(&point.0)));
+    }
+}
@@ -0,0 +1,441 @@
+use crate::geometry::hatch;
+use lindenmayer_renderer::backend::{channel, circle, GraphicState, Path};
+use lindenmayer_renderer::canvas::{
+    Canvas, CanvasResult, ExprContext, LineCap, LineJoin, RenderError,
+};
+use lindenmayer_renderer::display_list::{DisplayList, Point, Primitive, Transform};
+use lindenmayer_renderer::LSystemRenderer;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub mod geometry;
+pub mod optimize;
+pub mod output;
+
+pub use output::{gcode, hpgl};
+
+// Length of the lines which curves and circles are made of, in pixels
+const TOLERANCE: f64 = 0.5;
+
+/// How the drawing is put on paper and plotted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setup {
+    // Size of the paper in millimetres, the drawing is fitted inside the margin
+    pub paper: (f64, f64),
+    pub margin: f64,
+    // Speeds in millimetres per minute, with the pen down and up
+    pub draw_feed: f64,
+    pub travel_feed: f64,
+    // Heights of the pen in G-code
+    pub pen_up: f64,
+    pub pen_down: f64,
+    // Distance in millimetres between the lines of fills and dots,
+    // only their outline is drawn if none
+    pub hatch: Option<f64>,
+    // Whether the background is filled too
+    pub background: bool,
+    // Pens the lines are drawn with, each in a layer
+    pub layers: Layers,
+    // Reorders the paths to travel less with the pen up
+    pub optimize: bool,
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self {
+            paper: (210.0, 297.0),
+            margin: 10.0,
+            draw_feed: 3000.0,
+            travel_feed: 6000.0,
+            pen_up: 5.0,
+            pen_down: 0.0,
+            hatch: Some(0.5),
+            background: false,
+            layers: Layers::ByColor,
+            optimize: true,
+        }
+    }
+}
+
+/// How the lines are split between pens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layers {
+    // Everything is drawn with a single pen
+    Single,
+    // A pen for each color
+    ByColor,
+    // Each color is drawn with the pen of the nearest color
+    Pens(Vec<[u8; 3]>),
+}
+
+/// Paths drawn with the same pen, in millimetres from the
+/// bottom left corner of the paper.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub color: [u8; 3],
+    pub paths: Vec<Vec<Point>>,
+}
+
+/// Canvas collecting the lines drawn by a pen plotter.
+/// Strokes are drawn with the pen, whatever their width and dashes,
+/// fills become hatch lines and filled circles concentric circles.
+/// Text is not supported.
+pub struct PlotterCanvas {
+    // Distance between the hatch lines, in pixels
+    hatch: Option<f64>,
+    plot: RefCell<Plot>,
+}
+
+#[derive(Default)]
+struct Plot {
+    // Polylines in pixels, with their color
+    lines: Vec<([u8; 3], Vec<Point>)>,
+    state: GraphicState<Style>,
+    path: Path,
+}
+
+// Style of the graphic state
+#[derive(Clone, Default)]
+struct Style {
+    color: [u8; 3],
+    // Areas the lines are clipped to, each as polygons
+    clips: Vec<Rc<Vec<Vec<Point>>>>,
+}
+
+impl PlotterCanvas {
+    /// Canvas with the given distance in pixels between the lines of fills,
+    /// only their outline is drawn if none.
+    pub fn new(hatch: Option<f64>) -> Self {
+        Self {
+            hatch,
+            plot: RefCell::default(),
+        }
+    }
+
+    /// The lines drawn, grouped by pen in the order of their first line.
+    /// With a single pen, its color is the one of the first line.
+    pub fn layers(self, mode: &Layers) -> Vec<Layer> {
+        let mut layers: Vec<Layer> = vec![];
+        for (color, line) in self.plot.into_inner().lines {
+            let color = match mode {
+                Layers::Pens(pens) => nearest(color, pens),
+                _ => color,
+            };
+
+            let single = *mode == Layers::Single;
+            let index = match layers
+                .iter()
+                .position(|layer| single || layer.color == color)
+            {
+                Some(index) => index,
+                None => {
+                    layers.push(Layer {
+                        color,
+                        paths: vec![],
+                    });
+                    layers.len() - 1
+                }
+            };
+            layers[index].paths.push(line);
+        }
+
+        layers
+    }
+}
+
+/// Draws a frame of the fractal on the paper of the setup.
+pub fn plot(
+    fractal: &LSystemRenderer,
+    variables: &mut ExprContext,
+    setup: &Setup,
+) -> Result<Vec<Layer>, RenderError> {
+    // The canvas is fitted inside the margin and centered
+    let canvas = (fractal.canvas.0 as f64, fractal.canvas.1 as f64);
+    let area = (
+        setup.paper.0 - 2.0 * setup.margin,
+        setup.paper.1 - 2.0 * setup.margin,
+    );
+    let scale = (area.0 / canvas.0).min(area.1 / canvas.1);
+    let offset = (
+        (setup.paper.0 - canvas.0 * scale) / 2.0,
+        (setup.paper.1 - canvas.1 * scale) / 2.0,
+    );
+
+    // The paper is the background
+    let mut display_list = DisplayList::clone(&*fractal.display_list(variables)?);
+    if !setup.background {
+        display_list
+            .primitives
+            .retain(|primitive| !matches!(primitive, Primitive::Fill { .. }));
+    }
+
+    let plotter = PlotterCanvas::new(setup.hatch.map(|spacing| spacing / scale));
+    let plotter_canvas: &dyn Canvas = &plotter;
+    plotter_canvas.clip_rect(0.0, 0.0, canvas.0, canvas.1)?;
+    display_list.replay(plotter_canvas)?;
+
+    // The y axis of plotters goes up
+    let paper = |point: &Point| {
+        (
+            offset.0 + point.0 * scale,
+            setup.paper.1 - offset.1 - point.1 * scale,
+        )
+    };
+    let mut layers = plotter.layers(&setup.layers);
+    for layer in &mut layers {
+        let paths = layer
+            .paths
+            .iter()
+            .map(|path| path.iter().map(paper).collect());
+        layer.paths = paths.collect();
+        if setup.optimize {
+            layer.paths = optimize::optimize(std::mem::take(&mut layer.paths), (0.0, 0.0));
+        }
+    }
+
+    Ok(layers)
+}
+
+// Color of the pen closest to the given one, the color itself without pens
+fn nearest(color: [u8; 3], pens: &[[u8; 3]]) -> [u8; 3] {
+    let distance = |pen: &&[u8; 3]| {
+        let squares = pen
+            .iter()
+            .zip(color)
+            .map(|(a, b)| (*a as i32 - b as i32).pow(2));
+        squares.sum::<i32>()
+    };
+    pens.iter().min_by_key(distance).copied().unwrap_or(color)
+}
+
+impl PlotterCanvas {
+    fn transform(&self, x: f64, y: f64) -> Point {
+        self.plot.borrow().state.apply(x, y)
+    }
+}
+
+impl Plot {
+    // Adds the parts of the line inside the clip
+    fn draw(&mut self, line: Vec<Point>) {
+        let mut parts = vec![line];
+        for clip in &self.state.style.clips {
+            parts = parts
+                .iter()
+                .flat_map(|part| geometry::clip(part, clip))
+                .collect();
+        }
+
+        let color = self.state.style.color;
+        self.lines.extend(
+            parts
+                .into_iter()
+                .filter(|part| part.len() > 1)
+                .map(|part| (color, part)),
+        );
+    }
+}
+
+impl Canvas for PlotterCanvas {
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult {
+        let point = self.transform(x, y);
+        self.plot.borrow_mut().path.move_to(point);
+        Ok(())
+    }
+
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult {
+        let point = self.transform(x, y);
+        self.plot.borrow_mut().path.line_to(point);
+        Ok(())
+    }
+
+    fn stroke(&self) -> CanvasResult {
+        let mut plot = self.plot.borrow_mut();
+        for subpath in plot.path.take() {
+            let mut points = subpath.points;
+            if subpath.closed && points.len() > 2 {
+                points.push(points[0]);
+            }
+            plot.draw(points);
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> CanvasResult {
+        self.plot.borrow_mut().state.save();
+        Ok(())
+    }
+
+    fn restore(&self) -> CanvasResult {
+        self.plot.borrow_mut().state.restore()
+    }
+
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult {
+        self.plot.borrow_mut().state.translate(dx, dy);
+        Ok(())
+    }
+
+    fn rotate(&self, angle: f64) -> CanvasResult {
+        self.plot.borrow_mut().state.rotate(angle);
+        Ok(())
+    }
+
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult {
+        self.plot.borrow_mut().state.scale(sx, sy);
+        Ok(())
+    }
+
+    fn set_transform(&self, transform: &Transform) -> CanvasResult {
+        self.plot.borrow_mut().state.transform = *transform;
+        Ok(())
+    }
+
+    fn clip_rect(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        self.rectangle(x, y, width, height)?;
+        self.clip_path()
+    }
+
+    fn clip_path(&self) -> CanvasResult {
+        let mut plot = self.plot.borrow_mut();
+        let polygons = plot.path.take_polygons();
+        plot.state.style.clips.push(Rc::new(polygons));
+        Ok(())
+    }
+
+    // Pens have their own width, cap, join and dashes
+    fn set_line_width(&self, _thickness: f64) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_line_cap(&self, _cap: LineCap) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_line_join(&self, _join: LineJoin) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_miter_limit(&self, _limit: f64) -> CanvasResult {
+        Ok(())
+    }
+
+    fn set_dash(&self, _dashes: &[f64]) -> CanvasResult {
+        Ok(())
+    }
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        let mut plot = self.plot.borrow_mut();
+        let transform = plot.state.transform;
+        plot.path.rectangle(&transform, x, y, width, height);
+        Ok(())
+    }
+
+    fn set_color(&self, r: f64, g: f64, b: f64, _a: f64) -> CanvasResult {
+        self.plot.borrow_mut().state.style.color = [channel(r), channel(g), channel(b)];
+        Ok(())
+    }
+
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult {
+        let mut plot = self.plot.borrow_mut();
+        let transform = plot.state.transform;
+        plot.path.arc(&transform, x, y, r, TOLERANCE);
+        Ok(())
+    }
+
+    fn fill(&self) -> CanvasResult {
+        let mut plot = self.plot.borrow_mut();
+        let subpaths = plot.path.take();
+
+        let lines = match self.hatch {
+            // Dots are filled from their outline inwards
+            Some(spacing) if subpaths.iter().all(|subpath| subpath.circle.is_some()) => subpaths
+                .iter()
+                .filter_map(|subpath| subpath.circle)
+                .flat_map(|(center, radius)| {
+                    let count = (radius / spacing).ceil().max(1.0) as usize;
+                    (0..count).map(move |i| circle(center, radius - i as f64 * spacing, TOLERANCE))
+                })
+                .collect::<Vec<_>>(),
+            Some(spacing) => {
+                let polygons = subpaths
+                    .into_iter()
+                    .map(|subpath| subpath.points)
+                    .collect::<Vec<_>>();
+                hatch(&polygons, spacing)
+            }
+            None => subpaths
+                .into_iter()
+                .map(|subpath| {
+                    let mut points = subpath.points;
+                    points.extend(points.first().copied());
+                    points
+                })
+                .collect(),
+        };
+
+        for line in lines {
+            plot.draw(line);
+        }
+        Ok(())
+    }
+
+    fn close_path(&self) -> CanvasResult {
+        self.plot.borrow_mut().path.close();
+        Ok(())
+    }
+
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult {
+        let controls = [
+            self.transform(x1, y1),
+            self.transform(x2, y2),
+            self.transform(x3, y3),
+        ];
+        self.plot.borrow_mut().path.curve_to(controls, TOLERANCE);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers() {
+        let plotter = PlotterCanvas::new(Some(1.0));
+        let canvas: &dyn Canvas = &plotter;
+
+        canvas.move_to(0.0, 0.0).unwrap();
+        canvas.line_to(10.0, 0.0).unwrap();
+        canvas.stroke().unwrap();
+
+        // A clipped square of another color is hatched
+        canvas.save().unwrap();
+        canvas.set_color(1.0, 0.0, 0.0, 1.0).unwrap();
+        canvas.clip_rect(0.0, 0.0, 5.0, 10.0).unwrap();
+        canvas.rectangle(0.0, 0.0, 10.0, 10.0).unwrap();
+        canvas.fill().unwrap();
+        canvas.restore().unwrap();
+
+        // A dot is made of circles, in the first color again
+        canvas.arc(20.0, 20.0, 2.0).unwrap();
+        canvas.fill().unwrap();
+
+        // Dark red is drawn with the black pen
+        canvas.set_color(0.3, 0.0, 0.0, 1.0).unwrap();
+        canvas.move_to(0.0, 0.0).unwrap();
+        canvas.line_to(0.0, 10.0).unwrap();
+        canvas.stroke().unwrap();
+
+        let layers = plotter.layers(&Layers::Pens(vec![[255, 0, 0], [0, 0, 0]]));
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].color, [0, 0, 0]);
+        assert_eq!(layers[0].paths.len(), 4);
+        assert_eq!(layers[0].paths[0], vec![(0.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(layers[1].color, [255, 0, 0]);
+        assert!(layers[1].paths.len() > 5);
+        assert!(layers[1]
+            .paths
+            .iter()
+            .flatten()
+            .all(|point| point.0 <= 5.0 + 1e-9));
+    }
+}
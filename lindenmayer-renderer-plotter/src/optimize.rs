@@ -0,0 +1,208 @@
+use lindenmayer_renderer::backend::distance;
+use lindenmayer_renderer::display_list::Point;
+
+// Ends closer than this are the same point
+const JOIN_DISTANCE: f64 = 1e-6;
+
+/// Reorders the paths, reversing some of them, so that the pen travels less while
+/// it is up: after each path the pen goes to the nearest end of the paths left.
+/// Paths starting where the previous one ends are joined.
+/// The paths are left in their order if it is already shorter.
+pub fn optimize(paths: Vec<Vec<Point>>, start: Point) -> Vec<Vec<Point>> {
+    let paths = paths
+        .into_iter()
+        .filter(|path| !path.is_empty())
+        .collect::<Vec<_>>();
+    let mut ends = Grid::new(&paths);
+    let mut used = vec![false; paths.len()];
+
+    let mut ordered = vec![];
+    let mut position = start;
+    for _ in 0..paths.len() {
+        let Some((index, reversed)) = ends.nearest(position, &used) else {
+            break;
+        };
+        used[index] = true;
+
+        let mut path = paths[index].clone();
+        if reversed {
+            path.reverse();
+        }
+        position = path[path.len() - 1];
+        ordered.push(path);
+    }
+
+    if travel(&ordered, start) > travel(&paths, start) {
+        ordered = paths;
+    }
+    join(ordered)
+}
+
+fn join(paths: Vec<Vec<Point>>) -> Vec<Vec<Point>> {
+    let mut joined: Vec<Vec<Point>> = vec![];
+    for path in paths {
+        match joined.last_mut() {
+            Some(last) if distance(last[last.len() - 1], path[0]) < JOIN_DISTANCE => {
+                last.extend(path.into_iter().skip(1));
+            }
+            _ => joined.push(path),
+        }
+    }
+
+    joined
+}
+
+/// Distance travelled with the pen up to draw the paths in order.
+pub fn travel(paths: &[Vec<Point>], start: Point) -> f64 {
+    let mut position = start;
+    let mut travel = 0.0;
+    for path in paths.iter().filter(|path| !path.is_empty()) {
+        travel += distance(position, path[0]);
+        position = path[path.len() - 1];
+    }
+
+    travel
+}
+
+// Index of a path, whether it starts from its last point, and that point
+type End = (usize, bool, Point);
+
+// Ends of the paths, in square cells so that the nearest one is found
+// by looking at the cells around a point
+struct Grid {
+    cell: f64,
+    origin: Point,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<End>>,
+}
+
+impl Grid {
+    fn new(paths: &[Vec<Point>]) -> Self {
+        let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+        for point in paths
+            .iter()
+            .flat_map(|path| [path[0], path[path.len() - 1]])
+        {
+            min = (min.0.min(point.0), min.1.min(point.1));
+            max = (max.0.max(point.0), max.1.max(point.1));
+        }
+
+        // About one path in each cell, with at most as many cells as paths
+        let count = paths.len().max(1) as f64;
+        let size = ((max.0 - min.0).max(0.0), (max.1 - min.1).max(0.0));
+        let cell = (size.0 * size.1 / count)
+            .sqrt()
+            .max(size.0.max(size.1) / count.sqrt())
+            .max(1e-6);
+        let columns = (size.0 / cell) as usize + 1;
+        let rows = (size.1 / cell) as usize + 1;
+
+        let mut grid = Self {
+            cell,
+            origin: min,
+            columns,
+            rows,
+            cells: vec![vec![]; columns * rows],
+        };
+        for (index, path) in paths.iter().enumerate() {
+            for (reversed, end) in [(false, path[0]), (true, path[path.len() - 1])] {
+                let (column, row) = grid.key(end);
+                grid.cells[row * columns + column].push((index, reversed, end));
+            }
+        }
+
+        grid
+    }
+
+    // Cell of the point, or the nearest one for points outside
+    fn key(&self, point: Point) -> (usize, usize) {
+        let index = |value: f64, origin: f64, count: usize| {
+            (((value - origin) / self.cell).max(0.0) as usize).min(count - 1)
+        };
+
+        (
+            index(point.0, self.origin.0, self.columns),
+            index(point.1, self.origin.1, self.rows),
+        )
+    }
+
+    // Path with the end nearest to the point, and whether it starts at the other end
+    fn nearest(&mut self, point: Point, used: &[bool]) -> Option<(usize, bool)> {
+        let (column, row) = self.key(point);
+        let mut best: Option<(f64, usize, bool)> = None;
+
+        // Rings of cells around the point, until the nearest end cannot be further out
+        for ring in 0..self.columns.max(self.rows) {
+            if best.is_some_and(|(best, ..)| best <= ring.saturating_sub(1) as f64 * self.cell) {
+                break;
+            }
+
+            for (column, row) in ring_cells((column, row), ring, (self.columns, self.rows)) {
+                let cell = &mut self.cells[row * self.columns + column];
+                cell.retain(|(index, ..)| !used[*index]);
+
+                for (index, reversed, end) in cell.iter() {
+                    let distance = distance(point, *end);
+                    if best.is_none_or(|(best, ..)| distance < best) {
+                        best = Some((distance, *index, *reversed));
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, index, reversed)| (index, reversed))
+    }
+}
+
+// Cells at the given distance from the center one, inside the grid
+fn ring_cells(center: (usize, usize), ring: usize, size: (usize, usize)) -> Vec<(usize, usize)> {
+    let (x, y) = (center.0 as i64, center.1 as i64);
+    let ring = ring as i64;
+
+    let mut cells = vec![];
+    for offset in -ring..=ring {
+        cells.push((x + offset, y - ring));
+        if ring > 0 {
+            cells.push((x + offset, y + ring));
+        }
+    }
+    for offset in -ring + 1..ring {
+        cells.push((x - ring, y + offset));
+        cells.push((x + ring, y + offset));
+    }
+
+    let inside =
+        |(x, y): &(i64, i64)| (0..size.0 as i64).contains(x) && (0..size.1 as i64).contains(y);
+    cells
+        .into_iter()
+        .filter(inside)
+        .map(|(x, y)| (x as usize, y as usize))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_ends() {
+        let paths = vec![
+            vec![(10.0, 0.0), (20.0, 0.0)],
+            vec![(0.0, 10.0), (0.0, 5.0)],
+            vec![(1.0, 0.0), (10.0, 0.0)],
+            vec![(0.0, 0.5), (0.0, 1.0)],
+        ];
+        let optimized = optimize(paths.clone(), (0.0, 0.0));
+
+        assert_eq!(
+            optimized,
+            vec![
+                vec![(0.0, 0.5), (0.0, 1.0)],
+                vec![(1.0, 0.0), (10.0, 0.0), (20.0, 0.0)],
+                vec![(0.0, 5.0), (0.0, 10.0)],
+            ]
+        );
+        assert!(travel(&optimized, (0.0, 0.0)) < travel(&paths, (0.0, 0.0)));
+    }
+}
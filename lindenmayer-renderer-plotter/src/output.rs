@@ -0,0 +1,104 @@
+use crate::{Layer, Setup};
+use lindenmayer_renderer::backend::num;
+use std::fmt::Write;
+
+// HPGL plotter units in a millimetre
+const HPGL_UNITS: f64 = 40.0;
+
+// Decimals of the numbers, in millimetres
+const DECIMALS: usize = 3;
+
+/// HPGL program drawing the layers, each with the pen of its position.
+/// A single layer can be passed to write it to a separate file.
+pub fn hpgl(layers: &[Layer], setup: &Setup) -> String {
+    let units = |value: f64| (value * HPGL_UNITS).round() as i64;
+
+    let mut program = String::from("IN;\n");
+    for (index, layer) in layers.iter().enumerate() {
+        // Speed in centimetres per second
+        writeln!(program, "SP{};", index + 1).unwrap();
+        writeln!(program, "VS{};", num(setup.draw_feed / 600.0, DECIMALS)).unwrap();
+
+        for path in layer.paths.iter().filter(|path| path.len() > 1) {
+            let (x, y) = path[0];
+            writeln!(program, "PU{},{};", units(x), units(y)).unwrap();
+
+            let points = path[1..]
+                .iter()
+                .map(|(x, y)| format!("{},{}", units(*x), units(*y)));
+            writeln!(program, "PD{};", points.collect::<Vec<_>>().join(",")).unwrap();
+        }
+    }
+    program.push_str("PU;\nSP0;\n");
+
+    program
+}
+
+/// G-code program drawing the layers, the program pauses
+/// before each layer after the first one to change pen.
+/// A single layer can be passed to write it to a separate file.
+pub fn gcode(layers: &[Layer], setup: &Setup) -> String {
+    let mut program = String::from("G21\nG90\n");
+    writeln!(program, "G0 Z{}", num(setup.pen_up, DECIMALS)).unwrap();
+
+    for (index, layer) in layers.iter().enumerate() {
+        let [r, g, b] = layer.color;
+        let color = format!("#{r:02x}{g:02x}{b:02x}");
+        if index > 0 {
+            writeln!(program, "M0 (change to the pen {color})").unwrap();
+        } else {
+            writeln!(program, "(pen {color})").unwrap();
+        }
+
+        for path in layer.paths.iter().filter(|path| path.len() > 1) {
+            let (x, y) = path[0];
+            let feed = num(setup.travel_feed, DECIMALS);
+            writeln!(
+                program,
+                "G1 X{} Y{} F{feed}",
+                num(x, DECIMALS),
+                num(y, DECIMALS)
+            )
+            .unwrap();
+            writeln!(program, "G0 Z{}", num(setup.pen_down, DECIMALS)).unwrap();
+
+            writeln!(program, "F{}", num(setup.draw_feed, DECIMALS)).unwrap();
+            for (x, y) in &path[1..] {
+                writeln!(program, "G1 X{} Y{}", num(*x, DECIMALS), num(*y, DECIMALS)).unwrap();
+            }
+            writeln!(program, "G0 Z{}", num(setup.pen_up, DECIMALS)).unwrap();
+        }
+    }
+    program.push_str("G0 X0 Y0\nM2\n");
+
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs() {
+        let layers = vec![
+            Layer {
+                color: [0, 0, 0],
+                paths: vec![vec![(10.0, 10.0), (20.0, 10.0), (20.0, 12.5)]],
+            },
+            Layer {
+                color: [255, 0, 0],
+                paths: vec![vec![(0.0, 0.0), (1.0, 1.0)], vec![(5.0, 5.0)]],
+            },
+        ];
+        let setup = Setup::default();
+
+        assert_eq!(
+            hpgl(&layers, &setup),
+            "IN;\nSP1;\nVS5;\nPU400,400;\nPD800,400,800,500;\nSP2;\nVS5;\nPU0,0;\nPD40,40;\nPU;\nSP0;\n"
+        );
+        assert_eq!(
+            gcode(&layers[1..], &setup),
+            "G21\nG90\nG0 Z5\n(pen #ff0000)\nG1 X0 Y0 F6000\nG0 Z0\nF3000\nG1 X1 Y1\nG0 Z5\nG0 X0 Y0\nM2\n"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use lindenmayer_examples::variables;
+use lindenmayer_parser::Updatable;
+use lindenmayer_renderer::LSystemRenderer;
+use lindenmayer_renderer_plotter::{plot, Layers, Setup};
+
+const FRACTAL: &str = "axiom F+F
+iter 0
+initial_pos 10,40
+initial_rot 0
+initial_thickness 1
+background rgb(255, 255, 255)
+initial_color #000000ff
+canvas 100,50
+F: forward 20
++: rotate 1.5707963267948966; color rgb(255, 0, 0)";
+
+fn fractal() -> LSystemRenderer {
+    let mut renderer = LSystemRenderer::default();
+    for line in FRACTAL.lines() {
+        renderer.update(line).unwrap();
+    }
+    renderer.update_expr();
+    renderer.update_program();
+    renderer.update_rng();
+
+    renderer
+}
+
+// Paper fitting the 100x50 canvas at 1.8 mm per pixel inside the margin, 15 mm above and below it
+fn setup() -> Setup {
+    Setup {
+        paper: (200.0, 120.0),
+        optimize: false,
+        ..Setup::default()
+    }
+}
+
+#[test]
+fn paper() {
+    let layers = plot(&fractal(), &mut variables(0.0, 0.0), &setup()).unwrap();
+
+    // Lines go up from the start, and left until they are clipped by the canvas
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].color, [0, 0, 0]);
+    assert_eq!(layers[0].paths, vec![vec![(28.0, 33.0), (28.0, 69.0)]]);
+    assert_eq!(layers[1].color, [255, 0, 0]);
+    assert_eq!(layers[1].paths, vec![vec![(28.0, 69.0), (10.0, 69.0)]]);
+}
+
+#[test]
+fn background() {
+    let setup = Setup {
+        background: true,
+        hatch: Some(5.0),
+        ..setup()
+    };
+    let layers = plot(&fractal(), &mut variables(0.0, 0.0), &setup).unwrap();
+
+    // The background is hatched over the whole canvas, before the lines
+    assert_eq!(layers.len(), 3);
+    assert_eq!(layers[0].color, [255, 255, 255]);
+    assert!(layers[0].paths.len() >= 18);
+    for &(x, y) in layers[0].paths.iter().flatten() {
+        assert!((10.0 - 1e-9..=190.0 + 1e-9).contains(&x), "{x}");
+        assert!((15.0 - 1e-9..=105.0 + 1e-9).contains(&y), "{y}");
+    }
+}
+
+#[test]
+fn single_pen() {
+    let setup = Setup {
+        layers: Layers::Single,
+        ..setup()
+    };
+    let layers = plot(&fractal(), &mut variables(0.0, 0.0), &setup).unwrap();
+
+    assert_eq!(layers.len(), 1);
+    assert_eq!(layers[0].color, [0, 0, 0]);
+    assert_eq!(layers[0].paths.len(), 2);
+}
@@ -0,0 +1,21 @@
+//! Prints the size and the canvas calls of the first frame of an example,
+//! which `tests/reference/cairo.py` draws into a reference image.
+
+use lindenmayer_examples::variables;
+use lindenmayer_renderer::canvas::Canvas;
+use lindenmayer_renderer::recording::RecordingCanvas;
+use std::path::Path;
+
+fn main() {
+    let name = std::env::args().nth(1).expect("name of an example");
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("../examples/{name}.lsys"));
+    let renderer = lindenmayer_examples::load(&path);
+
+    let canvas = RecordingCanvas::new();
+    (&canvas as &dyn Canvas)
+        .draw_fractal(&renderer, &mut variables(0.0, 0.0))
+        .unwrap();
+
+    println!("canvas {} {}", renderer.canvas.0, renderer.canvas.1);
+    print!("{}", canvas.log());
+}
@@ -0,0 +1,467 @@
+use crate::raster::Coverage;
+use crate::stroke::Pen;
+use lindenmayer_renderer::backend::{GraphicState, Path};
+use lindenmayer_renderer::canvas::{
+    Canvas, CanvasError, CanvasResult, ExprContext, LineCap, LineJoin, RenderError,
+};
+use lindenmayer_renderer::display_list::{Point, Transform};
+use lindenmayer_renderer::LSystemRenderer;
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+pub mod raster;
+pub mod stroke;
+
+// Length of the lines which curves and circles are made of, in pixels
+const TOLERANCE: f64 = 0.5;
+
+/// Canvas drawing into an RGBA buffer, without native dependencies.
+/// Lines and circles are anti-aliased from their exact coverage, like cairo does.
+/// Text is not supported.
+pub struct RasterCanvas {
+    width: usize,
+    height: usize,
+    image: RefCell<Image>,
+}
+
+struct Image {
+    // Premultiplied colors
+    pixels: Vec<[f32; 4]>,
+    state: GraphicState<Style>,
+    path: Path,
+}
+
+// Style of the graphic state
+#[derive(Clone)]
+struct Style {
+    color: (f64, f64, f64, f64),
+    width: f64,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f64,
+    dashes: Vec<f64>,
+    // Coverage of each pixel by the clip, everything is drawn if none
+    clip: Option<Rc<Vec<f32>>>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            color: (0.0, 0.0, 0.0, 1.0),
+            width: 2.0,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+            miter_limit: 10.0,
+            dashes: vec![],
+            clip: None,
+        }
+    }
+}
+
+impl RasterCanvas {
+    /// Transparent image of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            image: RefCell::new(Image {
+                pixels: vec![[0.0; 4]; width * height],
+                state: GraphicState::default(),
+                path: Path::default(),
+            }),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Color of a pixel, not premultiplied.
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        unpremultiply(self.image.borrow().pixels[y * self.width + x])
+    }
+
+    /// Rows of pixels from the top, 4 bytes each, not premultiplied.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let image = self.image.borrow();
+        image
+            .pixels
+            .iter()
+            .flat_map(|pixel| unpremultiply(*pixel))
+            .collect()
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), CanvasError> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba8())?;
+        Ok(writer.finish()?)
+    }
+}
+
+/// Draws a frame of the fractal on an image of the size of its canvas.
+pub fn render(
+    fractal: &LSystemRenderer,
+    variables: &mut ExprContext,
+) -> Result<RasterCanvas, RenderError> {
+    let canvas = RasterCanvas::new(
+        fractal.canvas.0.max(0) as usize,
+        fractal.canvas.1.max(0) as usize,
+    );
+    (&canvas as &dyn Canvas).draw_fractal(fractal, variables)?;
+
+    Ok(canvas)
+}
+
+impl RasterCanvas {
+    // Blends the current color where the polygons are
+    fn paint(&self, coverage: &Coverage) {
+        let mut image = self.image.borrow_mut();
+        let image = &mut *image;
+        let (r, g, b, a) = image.state.style.color;
+        let color = [r as f32, g as f32, b as f32, a.clamp(0.0, 1.0) as f32];
+
+        for y in 0..coverage.height {
+            for x in 0..coverage.width {
+                let index = (coverage.y + y) * self.width + coverage.x + x;
+                let clip = image
+                    .state
+                    .style
+                    .clip
+                    .as_ref()
+                    .map_or(1.0, |clip| clip[index]);
+                let alpha = coverage.get(x, y) * clip * color[3];
+                if alpha <= 0.0 {
+                    continue;
+                }
+
+                let pixel = &mut image.pixels[index];
+                for channel in 0..3 {
+                    pixel[channel] = color[channel] * alpha + pixel[channel] * (1.0 - alpha);
+                }
+                pixel[3] = alpha + pixel[3] * (1.0 - alpha);
+            }
+        }
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn transform(&self, x: f64, y: f64) -> Point {
+        self.image.borrow().state.apply(x, y)
+    }
+}
+
+impl Canvas for RasterCanvas {
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult {
+        let point = self.transform(x, y);
+        self.image.borrow_mut().path.move_to(point);
+        Ok(())
+    }
+
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult {
+        let point = self.transform(x, y);
+        self.image.borrow_mut().path.line_to(point);
+        Ok(())
+    }
+
+    fn stroke(&self) -> CanvasResult {
+        let polygons = {
+            let mut image = self.image.borrow_mut();
+            let subpaths = image.path.take();
+            let (style, transform) = (&image.state.style, &image.state.transform);
+
+            // Lengths are scaled along with the drawing
+            let scale = transform.scale_factor();
+            let dashes = style
+                .dashes
+                .iter()
+                .map(|dash| dash * scale)
+                .collect::<Vec<_>>();
+            let pen = Pen {
+                width: style.width * scale,
+                cap: style.cap,
+                join: style.join,
+                miter_limit: style.miter_limit,
+                dashes: &dashes,
+            };
+            stroke::stroke(&subpaths, &pen)
+        };
+
+        self.paint(&Coverage::union(&polygons, self.size()));
+        Ok(())
+    }
+
+    fn save(&self) -> CanvasResult {
+        self.image.borrow_mut().state.save();
+        Ok(())
+    }
+
+    fn restore(&self) -> CanvasResult {
+        self.image.borrow_mut().state.restore()
+    }
+
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult {
+        self.image.borrow_mut().state.translate(dx, dy);
+        Ok(())
+    }
+
+    fn rotate(&self, angle: f64) -> CanvasResult {
+        self.image.borrow_mut().state.rotate(angle);
+        Ok(())
+    }
+
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult {
+        self.image.borrow_mut().state.scale(sx, sy);
+        Ok(())
+    }
+
+    fn set_transform(&self, transform: &Transform) -> CanvasResult {
+        self.image.borrow_mut().state.transform = *transform;
+        Ok(())
+    }
+
+    fn clip_rect(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        self.rectangle(x, y, width, height)?;
+        self.clip_path()
+    }
+
+    fn clip_path(&self) -> CanvasResult {
+        let polygons = self.image.borrow_mut().path.take_polygons();
+        let coverage = Coverage::non_zero(&polygons, self.size());
+
+        // The new clip is the intersection with the current one
+        let mut clip = vec![0.0; self.width * self.height];
+        for y in 0..coverage.height {
+            for x in 0..coverage.width {
+                clip[(coverage.y + y) * self.width + coverage.x + x] = coverage.get(x, y);
+            }
+        }
+
+        let mut image = self.image.borrow_mut();
+        if let Some(current) = &image.state.style.clip {
+            for (value, current) in clip.iter_mut().zip(current.iter()) {
+                *value *= current;
+            }
+        }
+        image.state.style.clip = Some(Rc::new(clip));
+        Ok(())
+    }
+
+    fn set_line_width(&self, thickness: f64) -> CanvasResult {
+        self.image.borrow_mut().state.style.width = thickness;
+        Ok(())
+    }
+
+    fn set_line_cap(&self, cap: LineCap) -> CanvasResult {
+        self.image.borrow_mut().state.style.cap = cap;
+        Ok(())
+    }
+
+    fn set_line_join(&self, join: LineJoin) -> CanvasResult {
+        self.image.borrow_mut().state.style.join = join;
+        Ok(())
+    }
+
+    fn set_miter_limit(&self, limit: f64) -> CanvasResult {
+        self.image.borrow_mut().state.style.miter_limit = limit;
+        Ok(())
+    }
+
+    fn set_dash(&self, dashes: &[f64]) -> CanvasResult {
+        self.image.borrow_mut().state.style.dashes = dashes.to_vec();
+        Ok(())
+    }
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        let mut image = self.image.borrow_mut();
+        let transform = image.state.transform;
+        image.path.rectangle(&transform, x, y, width, height);
+        Ok(())
+    }
+
+    fn set_color(&self, r: f64, g: f64, b: f64, a: f64) -> CanvasResult {
+        self.image.borrow_mut().state.style.color = (r, g, b, a);
+        Ok(())
+    }
+
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult {
+        // Like cairo, a circle without a positive radius is only its center
+        if r <= 0.0 {
+            return self.line_to(x, y);
+        }
+
+        let mut image = self.image.borrow_mut();
+        let transform = image.state.transform;
+        image.path.arc(&transform, x, y, r, TOLERANCE);
+        Ok(())
+    }
+
+    fn fill(&self) -> CanvasResult {
+        let polygons = self.image.borrow_mut().path.take_polygons();
+        self.paint(&Coverage::non_zero(&polygons, self.size()));
+        Ok(())
+    }
+
+    fn close_path(&self) -> CanvasResult {
+        self.image.borrow_mut().path.close();
+        Ok(())
+    }
+
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult {
+        let controls = [
+            self.transform(x1, y1),
+            self.transform(x2, y2),
+            self.transform(x3, y3),
+        ];
+        self.image.borrow_mut().path.curve_to(controls, TOLERANCE);
+        Ok(())
+    }
+}
+
+fn unpremultiply(pixel: [f32; 4]) -> [u8; 4] {
+    let alpha = pixel[3];
+    let channel = |value: f32| {
+        let value = if alpha > 0.0 { value / alpha } else { 0.0 };
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+
+    [
+        channel(pixel[0]),
+        channel(pixel[1]),
+        channel(pixel[2]),
+        (alpha.clamp(0.0, 1.0) * 255.0).round() as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Difference allowed with the values expected from the coverage of each pixel,
+    // e.g. 128 for half of it, the comparison with cairo is in `tests/reference.rs`
+    const TOLERANCE: i32 = 2;
+
+    fn white(width: usize, height: usize) -> RasterCanvas {
+        let canvas = RasterCanvas::new(width, height);
+        (&canvas as &dyn Canvas)
+            .rectangle(0.0, 0.0, width as f64, height as f64)
+            .unwrap();
+        canvas.set_color(1.0, 1.0, 1.0, 1.0).unwrap();
+        canvas.fill().unwrap();
+        canvas
+    }
+
+    fn assert_close(pixel: [u8; 4], expected: [u8; 4]) {
+        let close = pixel
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| (*a as i32 - b as i32).abs() <= TOLERANCE);
+        assert!(close, "{pixel:?} is not close to {expected:?}");
+    }
+
+    #[test]
+    fn lines() {
+        let canvas = white(40, 20);
+        canvas.set_color(0.0, 0.0, 0.0, 1.0).unwrap();
+
+        // Lines on the pixel edges cover them entirely
+        canvas.set_line_width(2.0).unwrap();
+        canvas.move_to(10.0, 5.0).unwrap();
+        canvas.line_to(30.0, 5.0).unwrap();
+        canvas.stroke().unwrap();
+        assert_close(canvas.pixel(20, 4), [0, 0, 0, 255]);
+        assert_close(canvas.pixel(20, 5), [0, 0, 0, 255]);
+        assert_close(canvas.pixel(20, 3), [255, 255, 255, 255]);
+        assert_close(canvas.pixel(9, 5), [255, 255, 255, 255]);
+
+        // Thin lines between two rows cover half of each
+        canvas.set_line_width(1.0).unwrap();
+        canvas.move_to(10.0, 15.0).unwrap();
+        canvas.line_to(30.0, 15.0).unwrap();
+        canvas.stroke().unwrap();
+        assert_close(canvas.pixel(20, 14), [128, 128, 128, 255]);
+        assert_close(canvas.pixel(20, 15), [128, 128, 128, 255]);
+
+        // Round caps go beyond the ends
+        canvas.set_line_cap(LineCap::Round).unwrap();
+        canvas.set_line_width(4.0).unwrap();
+        canvas.move_to(35.0, 2.0).unwrap();
+        canvas.line_to(35.0, 10.0).unwrap();
+        canvas.stroke().unwrap();
+        assert_close(canvas.pixel(35, 10), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn circles() {
+        let canvas = white(40, 40);
+        canvas.set_color(0.0, 0.0, 0.0, 1.0).unwrap();
+        canvas.arc(20.0, 20.0, 10.0).unwrap();
+        canvas.fill().unwrap();
+
+        // The covered area is the one of the circle
+        let area: f64 = canvas
+            .to_rgba8()
+            .chunks(4)
+            .map(|pixel| 1.0 - pixel[0] as f64 / 255.0)
+            .sum();
+        let expected = std::f64::consts::PI * 100.0;
+        assert!((area - expected).abs() / expected < 0.01, "{area}");
+
+        assert_close(canvas.pixel(20, 20), [0, 0, 0, 255]);
+        assert_close(canvas.pixel(5, 20), [255, 255, 255, 255]);
+
+        // Nothing is drawn without a positive radius
+        let canvas = white(40, 40);
+        canvas.set_color(0.0, 0.0, 0.0, 1.0).unwrap();
+        canvas.arc(20.0, 20.0, -10.0).unwrap();
+        canvas.fill().unwrap();
+        assert!(canvas.to_rgba8().iter().all(|channel| *channel == 255));
+    }
+
+    #[test]
+    fn blending() {
+        let canvas = white(10, 10);
+        canvas.set_color(1.0, 0.0, 0.0, 0.5).unwrap();
+        canvas.rectangle(0.0, 0.0, 10.0, 10.0).unwrap();
+        canvas.fill().unwrap();
+        assert_close(canvas.pixel(5, 5), [255, 128, 128, 255]);
+
+        // Transparent images keep the color of what is drawn on them
+        let canvas = RasterCanvas::new(10, 10);
+        canvas.set_color(0.0, 0.0, 1.0, 0.5).unwrap();
+        canvas.save().unwrap();
+        canvas.clip_rect(0.0, 0.0, 5.0, 10.0).unwrap();
+        canvas.rectangle(0.0, 0.0, 10.0, 10.0).unwrap();
+        canvas.fill().unwrap();
+        canvas.restore().unwrap();
+        assert_close(canvas.pixel(2, 5), [0, 0, 255, 128]);
+        assert_close(canvas.pixel(7, 5), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn png() {
+        let canvas = white(8, 4);
+        canvas.set_color(0.0, 0.5, 0.0, 1.0).unwrap();
+        canvas.arc(4.0, 2.0, 1.5).unwrap();
+        canvas.fill().unwrap();
+
+        let mut bytes = vec![];
+        canvas.write_png(&mut bytes).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (8, 4));
+        assert_eq!(decoded, canvas.to_rgba8());
+    }
+}
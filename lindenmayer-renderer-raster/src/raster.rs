@@ -0,0 +1,199 @@
+use lindenmayer_renderer::display_list::Point;
+
+/// Anti-aliased coverage of polygons, computed from the exact area
+/// of each pixel which is inside them. Overlapping polygons with the same
+/// orientation are merged, as in a non-zero fill, except where the winding
+/// number goes beyond one in the same pixel.
+pub struct Coverage {
+    // Pixel bounds of the covered area, clipped to the image
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    // Signed area accumulated by the edges, two extra columns
+    // catch what is drawn beyond the right side
+    cells: Vec<f32>,
+}
+
+impl Coverage {
+    /// Coverage of the polygons with the non-zero rule,
+    /// only computed inside an image of the given size.
+    pub fn non_zero(polygons: &[Vec<Point>], image: (usize, usize)) -> Self {
+        Self::new(polygons, image, false)
+    }
+
+    /// Coverage of the union of the polygons, whatever their orientation.
+    pub fn union(polygons: &[Vec<Point>], image: (usize, usize)) -> Self {
+        Self::new(polygons, image, true)
+    }
+
+    fn new(polygons: &[Vec<Point>], image: (usize, usize), union: bool) -> Self {
+        let points = polygons.iter().flatten();
+        let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+        for point in points {
+            min = (min.0.min(point.0), min.1.min(point.1));
+            max = (max.0.max(point.0), max.1.max(point.1));
+        }
+
+        let clamp = |value: f64, size: usize| value.clamp(0.0, size as f64) as usize;
+        let (x, y) = (clamp(min.0.floor(), image.0), clamp(min.1.floor(), image.1));
+        let width = clamp(max.0.ceil(), image.0).saturating_sub(x);
+        let height = clamp(max.1.ceil(), image.1).saturating_sub(y);
+
+        let mut coverage = Self {
+            x,
+            y,
+            width,
+            height,
+            cells: vec![0.0; (width + 2) * height],
+        };
+        if width == 0 || height == 0 {
+            return coverage;
+        }
+
+        for polygon in polygons {
+            let origin = (x as f64, y as f64);
+            let local = |point: &Point| (point.0 - origin.0, point.1 - origin.1);
+            let reversed = union && signed_area(polygon) < 0.0;
+            for (from, to) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+                if reversed {
+                    coverage.edge(local(to), local(from));
+                } else {
+                    coverage.edge(local(from), local(to));
+                }
+            }
+        }
+
+        // The coverage of a pixel is the sum of the areas on its left
+        for row in coverage.cells.chunks_mut(width + 2) {
+            let mut sum = 0.0;
+            for cell in row {
+                sum += *cell;
+                *cell = sum.abs().min(1.0);
+            }
+        }
+
+        coverage
+    }
+
+    /// Covered fraction of a pixel, relative to the bounds.
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.cells[y * (self.width + 2) + x]
+    }
+
+    // Splits the edge so that no part of it is on the left or on the right of the bounds.
+    // The parts outside are moved onto the side, where they have the same effect
+    fn edge(&mut self, from: Point, to: Point) {
+        let right = self.width as f64;
+        let mut points = vec![from];
+        for side in [0.0, right] {
+            if (from.0 - side) * (to.0 - side) < 0.0 {
+                let t = (side - from.0) / (to.0 - from.0);
+                points.push((side, from.1 + (to.1 - from.1) * t));
+            }
+        }
+        // The crossings are sorted along the edge
+        if points.len() == 3 && (points[1].0 - from.0).abs() > (points[2].0 - from.0).abs() {
+            points.swap(1, 2);
+        }
+        points.push(to);
+
+        for pair in points.windows(2) {
+            let clamp = |point: Point| (point.0.clamp(0.0, right), point.1);
+            self.line(clamp(pair[0]), clamp(pair[1]));
+        }
+    }
+
+    // Accumulates the signed area between the line and the right side
+    fn line(&mut self, from: Point, to: Point) {
+        if from.1 == to.1 {
+            return;
+        }
+
+        let (direction, from, to) = if from.1 < to.1 {
+            (1.0, from, to)
+        } else {
+            (-1.0, to, from)
+        };
+        let stride = self.width + 2;
+        let dxdy = (to.0 - from.0) / (to.1 - from.1);
+
+        let start = from.1.max(0.0);
+        let end = to.1.min(self.height as f64);
+        let mut x = from.0 + (start - from.1) * dxdy;
+
+        let mut row = start.floor() as usize;
+        while (row as f64) < end {
+            let top = (row as f64).max(start);
+            let bottom = ((row + 1) as f64).min(end);
+            let dy = bottom - top;
+            let next = x + dxdy * dy;
+            let d = (dy * direction) as f32;
+            let line = row * stride;
+
+            let (x0, x1) = if x < next { (x, next) } else { (next, x) };
+            let x0_floor = x0.floor();
+            let x0_index = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1_index = x1_ceil as usize;
+
+            if x1_index <= x0_index + 1 {
+                // Inside a single pixel, the area on its right goes to the next one
+                let middle = (0.5 * (x + next) - x0_floor) as f32;
+                self.cells[line + x0_index] += d - d * middle;
+                self.cells[line + x0_index + 1] += d * middle;
+            } else {
+                let s = (1.0 / (x1 - x0)) as f32;
+                let x0_fraction = (x0 - x0_floor) as f32;
+                let a0 = 0.5 * s * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = (x1 - x1_ceil + 1.0) as f32;
+                let am = 0.5 * s * x1_fraction * x1_fraction;
+
+                self.cells[line + x0_index] += d * a0;
+                if x1_index == x0_index + 2 {
+                    self.cells[line + x0_index + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0_fraction);
+                    self.cells[line + x0_index + 1] += d * (a1 - a0);
+                    for index in x0_index + 2..x1_index - 1 {
+                        self.cells[line + index] += d * s;
+                    }
+                    let a2 = a1 + (x1_index - x0_index - 3) as f32 * s;
+                    self.cells[line + x1_index - 1] += d * (1.0 - a2 - am);
+                }
+                self.cells[line + x1_index] += d * am;
+            }
+
+            x = next;
+            row += 1;
+        }
+    }
+}
+
+fn signed_area(polygon: &[Point]) -> f64 {
+    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+    edges.map(|(a, b)| a.0 * b.1 - b.0 * a.1).sum::<f64>() / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area() {
+        // Half of the square covers the pixels on the diagonal by half
+        let triangle = vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)];
+        let coverage = Coverage::non_zero(&[triangle], (10, 10));
+        assert_eq!((coverage.width, coverage.height), (4, 4));
+        assert!((coverage.get(1, 1) - 0.5).abs() < 1e-6);
+        assert!((coverage.get(2, 1) - 1.0).abs() < 1e-6);
+        assert!(coverage.get(1, 2).abs() < 1e-6);
+
+        // Parts outside the image do not change the inside
+        let square = vec![(-5.0, 2.5), (20.0, 2.5), (20.0, 4.0), (-5.0, 4.0)];
+        let coverage = Coverage::union(&[square], (10, 10));
+        assert_eq!((coverage.x, coverage.y, coverage.width), (0, 2, 10));
+        assert!((coverage.get(0, 0) - 0.5).abs() < 1e-6);
+        assert!((coverage.get(9, 1) - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,221 @@
+use lindenmayer_renderer::canvas::{LineCap, LineJoin};
+use lindenmayer_renderer::display_list::Point;
+use std::f64::consts::PI;
+
+pub use lindenmayer_renderer::backend::Subpath;
+
+/// Style of a stroke, with the lengths in pixels.
+pub struct Pen<'a> {
+    pub width: f64,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub miter_limit: f64,
+    pub dashes: &'a [f64],
+}
+
+/// Points of a circle, with enough sides to look round.
+pub fn circle(center: Point, radius: f64) -> Vec<Point> {
+    let sides = ((radius * PI).ceil() as usize).clamp(8, 256);
+    (0..sides)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f64 / sides as f64;
+            (
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+/// Polygons covering the stroke of the subpaths.
+pub fn stroke(subpaths: &[Subpath], pen: &Pen) -> Vec<Vec<Point>> {
+    let mut polygons = vec![];
+    let half = pen.width / 2.0;
+    if half <= 0.0 {
+        return polygons;
+    }
+
+    for subpath in subpaths {
+        for dash in dash(subpath, pen.dashes) {
+            stroke_subpath(&dash, pen, half, &mut polygons);
+        }
+    }
+
+    polygons
+}
+
+fn stroke_subpath(subpath: &Subpath, pen: &Pen, half: f64, polygons: &mut Vec<Vec<Point>>) {
+    let mut points = subpath.points.clone();
+    points.dedup();
+    if subpath.closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+
+    // A single point only has caps
+    if points.len() == 1 {
+        if !subpath.closed {
+            match pen.cap {
+                LineCap::Butt => {}
+                LineCap::Round => polygons.push(circle(points[0], half)),
+                LineCap::Square => {
+                    let (x, y) = points[0];
+                    polygons.push(vec![
+                        (x - half, y - half),
+                        (x + half, y - half),
+                        (x + half, y + half),
+                        (x - half, y + half),
+                    ]);
+                }
+            }
+        }
+        return;
+    }
+
+    let mut segments = points
+        .windows(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect::<Vec<_>>();
+    if subpath.closed && points.len() > 2 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+
+    let count = segments.len();
+    for (i, &(mut from, mut to)) in segments.iter().enumerate() {
+        let direction = unit(from, to);
+
+        if !subpath.closed && pen.cap == LineCap::Square {
+            if i == 0 {
+                from = (from.0 - direction.0 * half, from.1 - direction.1 * half);
+            }
+            if i == count - 1 {
+                to = (to.0 + direction.0 * half, to.1 + direction.1 * half);
+            }
+        }
+
+        let normal = (-direction.1 * half, direction.0 * half);
+        polygons.push(vec![
+            (from.0 + normal.0, from.1 + normal.1),
+            (to.0 + normal.0, to.1 + normal.1),
+            (to.0 - normal.0, to.1 - normal.1),
+            (from.0 - normal.0, from.1 - normal.1),
+        ]);
+    }
+
+    // Joins between consecutive segments
+    let joins = if subpath.closed { count } else { count - 1 };
+    for i in 0..joins {
+        let (previous, next) = (segments[i], segments[(i + 1) % count]);
+        join(previous, next, pen, half, polygons);
+    }
+
+    if !subpath.closed && pen.cap == LineCap::Round {
+        polygons.push(circle(points[0], half));
+        polygons.push(circle(points[points.len() - 1], half));
+    }
+}
+
+fn join(
+    previous: (Point, Point),
+    next: (Point, Point),
+    pen: &Pen,
+    half: f64,
+    polygons: &mut Vec<Vec<Point>>,
+) {
+    let point = previous.1;
+    if pen.join == LineJoin::Round {
+        polygons.push(circle(point, half));
+        return;
+    }
+
+    let (d1, d2) = (unit(previous.0, previous.1), unit(next.0, next.1));
+    let cross = d1.0 * d2.1 - d1.1 * d2.0;
+    if cross.abs() < 1e-12 {
+        return;
+    }
+
+    // The gap is on the outside of the turn
+    let side = if cross > 0.0 { -half } else { half };
+    let n1 = (-d1.1 * side, d1.0 * side);
+    let n2 = (-d2.1 * side, d2.0 * side);
+    let a = (point.0 + n1.0, point.1 + n1.1);
+    let b = (point.0 + n2.0, point.1 + n2.1);
+
+    // Ratio between the length of the miter and the width, as in cairo
+    let cos = (d1.0 * d2.0 + d1.1 * d2.1).clamp(-1.0, 1.0);
+    let ratio = 1.0 / ((1.0 - cos) / 2.0).sqrt().max(1e-12);
+
+    if pen.join == LineJoin::Miter && ratio <= pen.miter_limit {
+        let bisector = unit((0.0, 0.0), (n1.0 + n2.0, n1.1 + n2.1));
+        let length = half * ratio;
+        let tip = (point.0 + bisector.0 * length, point.1 + bisector.1 * length);
+        polygons.push(vec![point, a, tip, b]);
+    } else {
+        polygons.push(vec![point, a, b]);
+    }
+}
+
+// Splits the subpath into the dashes of the pattern
+fn dash(subpath: &Subpath, dashes: &[f64]) -> Vec<Subpath> {
+    let total: f64 = dashes.iter().sum();
+    if dashes.is_empty() || total <= 0.0 {
+        return vec![subpath.clone()];
+    }
+
+    let mut points = subpath.points.clone();
+    if subpath.closed && points.len() > 1 {
+        points.push(points[0]);
+    }
+
+    let mut result = vec![];
+    let mut current = Subpath::default();
+    let (mut index, mut left) = (0, dashes[0]);
+    let mut on = true;
+
+    if let Some(first) = points.first() {
+        current.points.push(*first);
+    }
+    for pair in points.windows(2) {
+        let (mut from, to) = (pair[0], pair[1]);
+        let mut length = distance(from, to);
+
+        // The segment can end several dashes
+        while length > left {
+            let t = left / length;
+            let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            if on {
+                current.points.push(point);
+                result.push(std::mem::take(&mut current));
+            } else {
+                current.points = vec![point];
+            }
+
+            length -= left;
+            from = point;
+            on = !on;
+            index = (index + 1) % dashes.len();
+            left = dashes[index];
+        }
+
+        left -= length;
+        if on {
+            current.points.push(to);
+        }
+    }
+    if on && current.points.len() > 1 {
+        result.push(current);
+    }
+
+    result
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+fn unit(from: Point, to: Point) -> Point {
+    let length = distance(from, to);
+    if length == 0.0 {
+        return (0.0, 0.0);
+    }
+    ((to.0 - from.0) / length, (to.1 - from.1) / length)
+}
@@ -0,0 +1,126 @@
+use lindenmayer_examples::variables;
+use lindenmayer_renderer::LSystemRenderer;
+use std::fs::File;
+use std::path::Path;
+
+// Every example is compared with `tests/reference/{name}.png`. With the `cairo` feature,
+// the references are drawn by `CairoCanvas` instead, and written there when UPDATE_REFERENCE
+// is set. Without it, they are drawn by cairo from the logged calls, e.g.
+// `cargo run --example calls tree3d | python3 tests/reference/cairo.py tests/reference/tree3d.png`
+#[cfg(feature = "cairo")]
+const UPDATE_ENV: &str = "UPDATE_REFERENCE";
+
+// Difference allowed in each channel of most pixels
+const TOLERANCE: u8 = 2;
+
+// Share of the pixels which may differ more, on the edges of the shapes and where thin lines
+// overlap, as the coverage is not computed like cairo does, and how much
+const EDGES: f64 = 0.02;
+const EDGE_TOLERANCE: u8 = 32;
+
+// Rows of RGBA pixels, with the alpha that cairo leaves out of opaque images
+#[cfg(not(feature = "cairo"))]
+fn reference(name: &str, _renderer: &LSystemRenderer) -> (usize, usize, Vec<u8>) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/reference/{name}.png"));
+    let mut reader = png::Decoder::new(File::open(path).unwrap())
+        .read_info()
+        .unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => pixels,
+        png::ColorType::Rgb => pixels
+            .chunks(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        color_type => panic!("unexpected colors {color_type:?}"),
+    };
+
+    (info.width as usize, info.height as usize, pixels)
+}
+
+// Rows of RGBA pixels drawn by `CairoCanvas`, unpremultiplied like the PNGs of cairo
+#[cfg(feature = "cairo")]
+fn reference(name: &str, renderer: &LSystemRenderer) -> (usize, usize, Vec<u8>) {
+    use cairo::{Context, Format, ImageSurface};
+    use lindenmayer_renderer::canvas::Canvas;
+    use lindenmayer_renderer_cairo::CairoCanvas;
+
+    let (width, height) = renderer.canvas;
+    let mut surface = ImageSurface::create(Format::ARgb32, width, height).unwrap();
+    let canvas = CairoCanvas(Context::new(&surface).unwrap());
+    (&canvas as &dyn Canvas)
+        .draw_fractal(renderer, &mut variables(0.0, 0.0))
+        .unwrap();
+    drop(canvas);
+    surface.flush();
+
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/reference/{name}.png"));
+        surface
+            .write_to_png(&mut File::create(path).unwrap())
+            .unwrap();
+    }
+
+    // Native endian ARGB, with the colors multiplied by the alpha
+    let stride = surface.stride() as usize;
+    let data = surface.data().unwrap();
+    let pixels = (0..height as usize)
+        .flat_map(|y| data[y * stride..][..width as usize * 4].chunks(4))
+        .flat_map(|pixel| {
+            let [b, g, r, a] = u32::from_ne_bytes(pixel.try_into().unwrap()).to_le_bytes();
+            let channel = |c: u8| {
+                if a == 0 {
+                    0
+                } else {
+                    ((c as u32 * 255 + a as u32 / 2) / a as u32) as u8
+                }
+            };
+            [channel(r), channel(g), channel(b), a]
+        })
+        .collect();
+
+    (width as usize, height as usize, pixels)
+}
+
+#[test]
+fn same_as_cairo() {
+    for (name, renderer) in lindenmayer_examples::all() {
+        let canvas =
+            lindenmayer_renderer_raster::render(&renderer, &mut variables(0.0, 0.0)).unwrap();
+
+        let (width, height, expected) = reference(&name, &renderer);
+        assert_eq!(
+            (canvas.width(), canvas.height()),
+            (width, height),
+            "size of {name}"
+        );
+
+        // Largest difference of the channels of each pixel
+        let differences = canvas
+            .to_rgba8()
+            .chunks(4)
+            .zip(expected.chunks(4))
+            .map(|(pixel, expected)| {
+                pixel
+                    .iter()
+                    .zip(expected)
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .max()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let edges = differences
+            .iter()
+            .filter(|difference| **difference > TOLERANCE)
+            .count();
+        let max = differences.iter().max().copied().unwrap_or_default();
+        assert!(
+            edges as f64 <= EDGES * (width * height) as f64 && max <= EDGE_TOLERANCE,
+            "{name} differs from cairo: {edges} pixels beyond {TOLERANCE}, up to {max}"
+        );
+    }
+}
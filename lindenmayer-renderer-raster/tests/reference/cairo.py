@@ -0,0 +1,75 @@
+#!/usr/bin/env python3
+"""Draws the calls logged by a RecordingCanvas with cairo, into a PNG.
+
+    cargo run --example calls stickman | python3 tests/reference/cairo.py tests/reference/stickman.png
+
+The first line of the input is the size of the image, e.g. `canvas 350 650`.
+Only the system libcairo is needed, through ctypes.
+"""
+
+import ctypes
+import ctypes.util
+import math
+import sys
+
+cairo = ctypes.CDLL(ctypes.util.find_library("cairo") or "libcairo.so.2")
+cairo.cairo_image_surface_create.restype = ctypes.c_void_p
+cairo.cairo_create.restype = ctypes.c_void_p
+cairo.cairo_create.argtypes = [ctypes.c_void_p]
+cairo.cairo_surface_write_to_png.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+
+FORMAT_ARGB32 = 0
+CAPS = {"Butt": 0, "Round": 1, "Square": 2}
+JOINS = {"Miter": 0, "Round": 1, "Bevel": 2}
+
+
+class Matrix(ctypes.Structure):
+    _fields_ = [(name, ctypes.c_double) for name in ("xx", "yx", "xy", "yy", "x0", "y0")]
+
+
+def call(name, cr, *args):
+    function = getattr(cairo, "cairo_" + name)
+    function.argtypes = [ctypes.c_void_p] + [ctypes.c_double] * len(args)
+    function(cr, *args)
+
+
+def draw(lines, path):
+    _, width, height = lines[0].split()
+    surface = ctypes.c_void_p(cairo.cairo_image_surface_create(FORMAT_ARGB32, int(width), int(height)))
+    cr = ctypes.c_void_p(cairo.cairo_create(surface))
+
+    for line in lines[1:]:
+        name, *args = line.split()
+        if name in ("text", "set_depth"):
+            # Text depends on the fonts of the system, the depth is not drawn
+            continue
+        elif name == "set_color":
+            call("set_source_rgba", cr, *map(float, args))
+        elif name == "set_line_cap":
+            cairo.cairo_set_line_cap(cr, CAPS[args[0]])
+        elif name == "set_line_join":
+            cairo.cairo_set_line_join(cr, JOINS[args[0]])
+        elif name == "set_dash":
+            dashes = (ctypes.c_double * len(args))(*map(float, args))
+            cairo.cairo_set_dash.argtypes = [ctypes.c_void_p, ctypes.c_void_p, ctypes.c_int, ctypes.c_double]
+            cairo.cairo_set_dash(cr, dashes, len(args), 0.0)
+        elif name == "set_transform":
+            matrix = Matrix(*map(float, args))
+            cairo.cairo_set_matrix.argtypes = [ctypes.c_void_p, ctypes.POINTER(Matrix)]
+            cairo.cairo_set_matrix(cr, ctypes.byref(matrix))
+        elif name == "clip_rect":
+            call("rectangle", cr, *map(float, args))
+            call("clip", cr)
+        elif name == "clip_path":
+            call("clip", cr)
+        elif name == "arc":
+            call("arc", cr, *map(float, args), 0.0, 2.0 * math.pi)
+        else:
+            call(name, cr, *map(float, args))
+
+    assert cairo.cairo_status(cr) == 0, "cairo error {}".format(cairo.cairo_status(cr))
+    assert cairo.cairo_surface_write_to_png(surface, path.encode()) == 0
+
+
+if __name__ == "__main__":
+    draw(sys.stdin.read().splitlines(), sys.argv[1])
@@ -0,0 +1,578 @@
+use lindenmayer_renderer::backend;
+use lindenmayer_renderer::canvas::{
+    Canvas, CanvasResult, ExprContext, LineCap, LineJoin, RenderError, TextAnchor,
+};
+use lindenmayer_renderer::display_list::{Point, Transform};
+use lindenmayer_renderer::LSystemRenderer;
+use std::cell::RefCell;
+use std::fmt::Write;
+
+/// Canvas writing an SVG document.
+/// `save` and `restore` open and close groups, clips are groups too.
+/// Consecutive strokes with the same style are merged into a single path.
+pub struct SvgCanvas {
+    width: f64,
+    height: f64,
+    svg: RefCell<Svg>,
+}
+
+#[derive(Default)]
+struct Svg {
+    body: String,
+    // Clip paths, referenced by the groups of the body
+    defs: String,
+    state: State,
+    // States saved by `save`, with the number of groups open at their level
+    saved: Vec<(State, usize)>,
+    // Groups open since the last `save`
+    groups: usize,
+    path: Vec<Command>,
+    current: Option<Point>,
+    // Start of the current sub-path, where `close_path` goes back to
+    start: Option<Point>,
+    // Stroke not written yet, later strokes with the same attributes are appended to it
+    pending: Option<(String, String)>,
+    clips: usize,
+}
+
+// Graphic state restored by `restore`, the path is not part of it
+#[derive(Clone)]
+struct State {
+    color: (f64, f64, f64, f64),
+    width: f64,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f64,
+    dashes: Vec<f64>,
+    transform: Transform,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            color: (0.0, 0.0, 0.0, 1.0),
+            width: 2.0,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+            miter_limit: 10.0,
+            dashes: vec![],
+            transform: Transform::default(),
+        }
+    }
+}
+
+enum Command {
+    Move(Point),
+    Line(Point),
+    Curve(Point, Point, Point),
+    Close,
+    Rect(Point, f64, f64),
+    // Full circle, joined to the current point if any
+    Arc(Point, f64, bool),
+}
+
+impl SvgCanvas {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            svg: RefCell::default(),
+        }
+    }
+
+    /// The SVG document, groups which have not been restored are closed.
+    pub fn finish(self) -> String {
+        let mut svg = self.svg.into_inner();
+        svg.flush();
+
+        let groups = svg.groups + svg.saved.iter().map(|(_, groups)| groups).sum::<usize>();
+        for _ in 0..groups {
+            svg.body.push_str("</g>\n");
+        }
+
+        let (width, height) = (num(self.width), num(self.height));
+        let mut document = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+        );
+        if !svg.defs.is_empty() {
+            document.push_str("<defs>\n");
+            document.push_str(&svg.defs);
+            document.push_str("</defs>\n");
+        }
+        document.push_str(&svg.body);
+        document.push_str("</svg>\n");
+
+        document
+    }
+}
+
+/// Draws a frame of the fractal as an SVG document of the size of its canvas.
+pub fn render(
+    fractal: &LSystemRenderer,
+    variables: &mut ExprContext,
+) -> Result<String, RenderError> {
+    let canvas = SvgCanvas::new(fractal.canvas.0 as f64, fractal.canvas.1 as f64);
+    (&canvas as &dyn Canvas).draw_fractal(fractal, variables)?;
+
+    Ok(canvas.finish())
+}
+
+impl Svg {
+    fn push(&mut self, command: Command) {
+        match command {
+            Command::Move(point) => {
+                self.start = Some(point);
+                self.current = Some(point);
+            }
+            Command::Line(point) | Command::Curve(_, _, point) => {
+                self.start = self.start.or(Some(point));
+                self.current = Some(point);
+            }
+            Command::Close => self.current = self.start,
+            Command::Rect(point, _, _) => {
+                self.start = Some(point);
+                self.current = Some(point);
+            }
+            Command::Arc((x, y), r, _) => {
+                let point = (x + r, y);
+                self.start = self.start.or(Some(point));
+                self.current = Some(point);
+            }
+        }
+        self.path.push(command);
+    }
+
+    fn take_path(&mut self) -> String {
+        self.current = None;
+        self.start = None;
+
+        let mut data = String::new();
+        for command in self.path.drain(..) {
+            if !data.is_empty() {
+                data.push(' ');
+            }
+
+            match command {
+                Command::Move((x, y)) => write!(data, "M{} {}", num(x), num(y)),
+                Command::Line((x, y)) => write!(data, "L{} {}", num(x), num(y)),
+                Command::Curve((x1, y1), (x2, y2), (x3, y3)) => write!(
+                    data,
+                    "C{} {} {} {} {} {}",
+                    num(x1),
+                    num(y1),
+                    num(x2),
+                    num(y2),
+                    num(x3),
+                    num(y3)
+                ),
+                Command::Close => write!(data, "Z"),
+                Command::Rect((x, y), width, height) => write!(
+                    data,
+                    "M{} {} h{} v{} h{} Z",
+                    num(x),
+                    num(y),
+                    num(width),
+                    num(height),
+                    num(-width)
+                ),
+                Command::Arc((x, y), r, joined) => {
+                    // Two half circles, clockwise from the right as in cairo
+                    let (r, right, left, y) = (num(r), num(x + r), num(x - r), num(y));
+                    let start = if joined { 'L' } else { 'M' };
+                    write!(
+                        data,
+                        "{start}{right} {y} A{r} {r} 0 0 1 {left} {y} A{r} {r} 0 0 1 {right} {y}"
+                    )
+                }
+            }
+            .unwrap();
+        }
+
+        data
+    }
+
+    // Writes the stroke which is being merged
+    fn flush(&mut self) {
+        if let Some((attributes, data)) = self.pending.take() {
+            writeln!(self.body, "<path d=\"{data}\" {attributes}/>").unwrap();
+        }
+    }
+
+    fn open_group(&mut self, attributes: &str) {
+        self.flush();
+        writeln!(self.body, "<g {attributes}>").unwrap();
+        self.groups += 1;
+    }
+
+    fn clip(&mut self, shape: String) {
+        let id = format!("clip{}", self.clips);
+        self.clips += 1;
+
+        writeln!(self.defs, "<clipPath id=\"{id}\">{shape}</clipPath>").unwrap();
+        self.open_group(&format!("clip-path=\"url(#{id})\""));
+    }
+
+    fn stroke_attributes(&self) -> String {
+        let state = &self.state;
+        let mut attributes = format!(
+            "fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"",
+            color(state.color),
+            num(state.width)
+        );
+
+        if state.color.3 < 1.0 {
+            write!(attributes, " stroke-opacity=\"{}\"", num(state.color.3)).unwrap();
+        }
+        match state.cap {
+            LineCap::Butt => {}
+            LineCap::Round => attributes.push_str(" stroke-linecap=\"round\""),
+            LineCap::Square => attributes.push_str(" stroke-linecap=\"square\""),
+        }
+        match state.join {
+            // SVG has a different default limit
+            LineJoin::Miter => write!(
+                attributes,
+                " stroke-miterlimit=\"{}\"",
+                num(state.miter_limit)
+            )
+            .unwrap(),
+            LineJoin::Round => attributes.push_str(" stroke-linejoin=\"round\""),
+            LineJoin::Bevel => attributes.push_str(" stroke-linejoin=\"bevel\""),
+        }
+        if !state.dashes.is_empty() {
+            let dashes = state
+                .dashes
+                .iter()
+                .map(|dash| num(*dash))
+                .collect::<Vec<_>>();
+            write!(attributes, " stroke-dasharray=\"{}\"", dashes.join(" ")).unwrap();
+        }
+        attributes.push_str(&self.transform_attribute(None));
+
+        attributes
+    }
+
+    fn fill_attributes(&self) -> String {
+        let (r, g, b, a) = self.state.color;
+        let mut attributes = format!("fill=\"{}\"", color((r, g, b, a)));
+        if a < 1.0 {
+            write!(attributes, " fill-opacity=\"{}\"", num(a)).unwrap();
+        }
+
+        attributes
+    }
+
+    // Current transformation, followed by another one
+    fn transform_attribute(&self, then: Option<String>) -> String {
+        let t = &self.state.transform;
+        let mut transforms = vec![];
+        if *t != Transform::default() {
+            transforms.push(format!(
+                "matrix({} {} {} {} {} {})",
+                num(t.xx),
+                num(t.yx),
+                num(t.xy),
+                num(t.yy),
+                num(t.x0),
+                num(t.y0)
+            ));
+        }
+        transforms.extend(then);
+
+        if transforms.is_empty() {
+            String::new()
+        } else {
+            format!(" transform=\"{}\"", transforms.join(" "))
+        }
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult {
+        self.svg.borrow_mut().push(Command::Move((x, y)));
+        Ok(())
+    }
+
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        // Without a current point the line starts where it ends
+        let command = match svg.current {
+            Some(_) => Command::Line((x, y)),
+            None => Command::Move((x, y)),
+        };
+        svg.push(command);
+        Ok(())
+    }
+
+    fn stroke(&self) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        let data = svg.take_path();
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let attributes = svg.stroke_attributes();
+        match &mut svg.pending {
+            Some((pending, pending_data)) if *pending == attributes => {
+                pending_data.push(' ');
+                pending_data.push_str(&data);
+            }
+            _ => {
+                svg.flush();
+                svg.pending = Some((attributes, data));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save(&self) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        svg.flush();
+        svg.body.push_str("<g>\n");
+
+        let saved = (svg.state.clone(), svg.groups);
+        svg.saved.push(saved);
+        svg.groups = 1;
+        Ok(())
+    }
+
+    fn restore(&self) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        let (state, groups) = svg.saved.pop().ok_or("restore without a matching save")?;
+
+        svg.flush();
+        for _ in 0..svg.groups {
+            svg.body.push_str("</g>\n");
+        }
+        svg.state = state;
+        svg.groups = groups;
+        Ok(())
+    }
+
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        svg.state.transform = Transform::translate(dx, dy).then(&svg.state.transform);
+        Ok(())
+    }
+
+    fn rotate(&self, angle: f64) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        svg.state.transform = Transform::rotate(angle).then(&svg.state.transform);
+        Ok(())
+    }
+
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult {
+        let scale = Transform {
+            xx: sx,
+            yy: sy,
+            ..Transform::default()
+        };
+
+        let mut svg = self.svg.borrow_mut();
+        svg.state.transform = scale.then(&svg.state.transform);
+        Ok(())
+    }
+
+    fn set_transform(&self, transform: &Transform) -> CanvasResult {
+        self.svg.borrow_mut().state.transform = *transform;
+        Ok(())
+    }
+
+    fn clip_rect(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        let shape = format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}/>",
+            num(x),
+            num(y),
+            num(width),
+            num(height),
+            svg.transform_attribute(None)
+        );
+        svg.clip(shape);
+        Ok(())
+    }
+
+    fn clip_path(&self) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        let data = svg.take_path();
+        let shape = format!("<path d=\"{data}\"{}/>", svg.transform_attribute(None));
+        svg.clip(shape);
+        Ok(())
+    }
+
+    fn set_line_width(&self, thickness: f64) -> CanvasResult {
+        self.svg.borrow_mut().state.width = thickness;
+        Ok(())
+    }
+
+    fn set_line_cap(&self, cap: LineCap) -> CanvasResult {
+        self.svg.borrow_mut().state.cap = cap;
+        Ok(())
+    }
+
+    fn set_line_join(&self, join: LineJoin) -> CanvasResult {
+        self.svg.borrow_mut().state.join = join;
+        Ok(())
+    }
+
+    fn set_miter_limit(&self, limit: f64) -> CanvasResult {
+        self.svg.borrow_mut().state.miter_limit = limit;
+        Ok(())
+    }
+
+    fn set_dash(&self, dashes: &[f64]) -> CanvasResult {
+        self.svg.borrow_mut().state.dashes = dashes.to_vec();
+        Ok(())
+    }
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        self.svg
+            .borrow_mut()
+            .push(Command::Rect((x, y), width, height));
+        Ok(())
+    }
+
+    fn set_color(&self, r: f64, g: f64, b: f64, a: f64) -> CanvasResult {
+        self.svg.borrow_mut().state.color = (r, g, b, a);
+        Ok(())
+    }
+
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        let joined = svg.current.is_some();
+        svg.push(Command::Arc((x, y), r, joined));
+        Ok(())
+    }
+
+    fn fill(&self) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        svg.flush();
+
+        // Single shapes are written as such
+        let attributes = svg.fill_attributes() + &svg.transform_attribute(None);
+        let element = match svg.path.as_slice() {
+            [Command::Arc((x, y), r, _)] => format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {attributes}/>",
+                num(*x),
+                num(*y),
+                num(*r)
+            ),
+            [Command::Rect((x, y), width, height)] => format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {attributes}/>",
+                num(*x),
+                num(*y),
+                num(*width),
+                num(*height)
+            ),
+            [] => return Ok(()),
+            _ => format!("<path d=\"{}\" {attributes}/>", svg.take_path()),
+        };
+        svg.take_path();
+
+        svg.body.push_str(&element);
+        svg.body.push('\n');
+        Ok(())
+    }
+
+    fn close_path(&self) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        if svg.current.is_some() {
+            svg.push(Command::Close);
+        }
+        Ok(())
+    }
+
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        if svg.current.is_none() {
+            svg.push(Command::Move((x1, y1)));
+        }
+        svg.push(Command::Curve((x1, y1), (x2, y2), (x3, y3)));
+        Ok(())
+    }
+
+    fn text(
+        &self,
+        x: f64,
+        y: f64,
+        text: &str,
+        size: f64,
+        angle: f64,
+        anchor: TextAnchor,
+    ) -> CanvasResult {
+        let mut svg = self.svg.borrow_mut();
+        svg.flush();
+
+        let anchor = match anchor {
+            TextAnchor::Start => "",
+            TextAnchor::Middle => " text-anchor=\"middle\"",
+            TextAnchor::End => " text-anchor=\"end\"",
+        };
+        let rotation = (angle != 0.0)
+            .then(|| format!("rotate({} {} {})", num(angle.to_degrees()), num(x), num(y)));
+        let attributes = svg.fill_attributes() + &svg.transform_attribute(rotation);
+
+        writeln!(
+            svg.body,
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\"{anchor} {attributes}>{}</text>",
+            num(x),
+            num(y),
+            num(size),
+            escape(text)
+        )
+        .unwrap();
+        Ok(())
+    }
+}
+
+// Numbers with at most 3 decimals, without trailing zeros
+fn num(value: f64) -> String {
+    backend::num(value, 3)
+}
+
+fn color((r, g, b, _): (f64, f64, f64, f64)) -> String {
+    let [r, g, b] = [r, g, b].map(backend::channel);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_strokes() {
+        let svg = SvgCanvas::new(10.0, 10.0);
+        let canvas: &dyn Canvas = &svg;
+
+        for (from, to) in [((0.0, 0.0), (5.0, 0.0)), ((5.0, 0.0), (5.0, 5.0))] {
+            canvas.move_to(from.0, from.1).unwrap();
+            canvas.line_to(to.0, to.1).unwrap();
+            canvas.stroke().unwrap();
+        }
+        canvas.set_color(1.0, 0.0, 0.0, 0.5).unwrap();
+        canvas.save().unwrap();
+        canvas.translate(1.0, 2.0).unwrap();
+        canvas.arc(5.0, 5.0, 1.5).unwrap();
+        canvas.fill().unwrap();
+        canvas.restore().unwrap();
+        assert!(canvas.restore().is_err());
+
+        assert_eq!(
+            svg.finish(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\" viewBox=\"0 0 10 10\">\n\
+            <path d=\"M0 0 L5 0 M5 0 L5 5\" fill=\"none\" stroke=\"#000000\" stroke-width=\"2\" stroke-miterlimit=\"10\"/>\n\
+            <g>\n\
+            <circle cx=\"5\" cy=\"5\" r=\"1.5\" fill=\"#ff0000\" fill-opacity=\"0.5\" transform=\"matrix(1 0 0 1 1 2)\"/>\n\
+            </g>\n\
+            </svg>\n"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use lindenmayer_examples::{hash, variables};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// Set to write the current output as the expected one
+const UPDATE_ENV: &str = "UPDATE_GOLDEN";
+
+// Bytes of the document written in full in the golden file, the rest is only in the hash
+const LOGGED_BYTES: usize = 2048;
+
+// Size and hash of the document, followed by its beginning
+fn golden(svg: &str) -> String {
+    let mut golden = String::new();
+    writeln!(golden, "bytes {} hash {:016x}", svg.len(), hash(svg)).unwrap();
+
+    let mut end = svg.len().min(LOGGED_BYTES);
+    while !svg.is_char_boundary(end) {
+        end -= 1;
+    }
+    golden.push_str(&svg[..end]);
+    if end < svg.len() {
+        golden.push_str("\n...\n");
+    }
+
+    golden
+}
+
+#[test]
+fn examples() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os(UPDATE_ENV).is_some();
+
+    let mut mismatches = vec![];
+    for (name, renderer) in lindenmayer_examples::all() {
+        let svg = lindenmayer_renderer_svg::render(&renderer, &mut variables(0.0, 0.0)).unwrap();
+        let golden = golden(&svg);
+
+        let expected = directory.join(format!("{name}.golden"));
+        if update {
+            fs::write(&expected, &golden).unwrap();
+        } else if fs::read_to_string(&expected).ok().as_deref() != Some(golden.as_str()) {
+            mismatches.push(name);
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "output differs from the golden files of {mismatches:?}, run with {UPDATE_ENV}=1 to update them"
+    );
+}
@@ -0,0 +1,185 @@
+/// Interpolation used between two consecutive keyframes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    Cubic,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+/// What happens once the time goes past the last keyframe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Playback {
+    Once,
+    Loop,
+    PingPong,
+}
+
+/// Time domain of the keyframes of a track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeUnit {
+    Frames,
+    // Milliseconds, like the TIME variable
+    Millis,
+}
+
+/// Keyframe track of a variable.
+/// The keyframes are (time, value) pairs sorted by time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub keyframes: Vec<(f64, f64)>,
+    pub unit: TimeUnit,
+    pub interpolation: Interpolation,
+    pub playback: Playback,
+}
+
+impl Track {
+    pub fn new(
+        mut keyframes: Vec<(f64, f64)>,
+        unit: TimeUnit,
+        interpolation: Interpolation,
+        playback: Playback,
+    ) -> Self {
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Self {
+            keyframes,
+            unit,
+            interpolation,
+            playback,
+        }
+    }
+
+    /// Value of the track given the current FRAME and TIME.
+    pub fn value_at(&self, frame: f64, time: f64) -> f64 {
+        match self.unit {
+            TimeUnit::Frames => self.sample(frame),
+            TimeUnit::Millis => self.sample(time),
+        }
+    }
+
+    /// Value of the track at time `t`, expressed in the unit of the track.
+    pub fn sample(&self, t: f64) -> f64 {
+        let (first, last) = match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 0.0,
+        };
+
+        let duration = last.0 - first.0;
+        if duration <= 0.0 {
+            return first.1;
+        }
+
+        // Map t into [first, last] according to the playback mode
+        let local = t - first.0;
+        let t = match self.playback {
+            Playback::Once => t.clamp(first.0, last.0),
+            Playback::Loop => first.0 + local.rem_euclid(duration),
+            Playback::PingPong => {
+                let phase = local.rem_euclid(2.0 * duration);
+                if phase > duration {
+                    first.0 + 2.0 * duration - phase
+                } else {
+                    first.0 + phase
+                }
+            }
+        };
+
+        // Index of the segment [i, i + 1] containing t
+        let i = self
+            .keyframes
+            .windows(2)
+            .position(|w| t < w[1].0)
+            .unwrap_or(self.keyframes.len() - 2);
+
+        let (t0, v0) = self.keyframes[i];
+        let (t1, v1) = self.keyframes[i + 1];
+        let x = ((t - t0) / (t1 - t0)).clamp(0.0, 1.0);
+
+        match self.interpolation {
+            Interpolation::Step => {
+                if x >= 1.0 {
+                    v1
+                } else {
+                    v0
+                }
+            }
+            Interpolation::Linear => lerp(v0, v1, x),
+            Interpolation::EaseIn => lerp(v0, v1, x * x),
+            Interpolation::EaseOut => lerp(v0, v1, 1.0 - (1.0 - x) * (1.0 - x)),
+            Interpolation::EaseInOut => lerp(v0, v1, x * x * (3.0 - 2.0 * x)),
+            Interpolation::Cubic => {
+                // Catmull-Rom spline through the neighbouring keyframes
+                let before = if i > 0 { self.keyframes[i - 1].1 } else { v0 };
+                let after = self.keyframes.get(i + 2).map(|k| k.1).unwrap_or(v1);
+
+                catmull_rom(before, v0, v1, after, x)
+            }
+        }
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn catmull_rom(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(interpolation: Interpolation, playback: Playback) -> Track {
+        let keyframes = vec![(0.0, 0.0), (1000.0, 1.0), (2000.0, 3.0)];
+        Track::new(keyframes, TimeUnit::Millis, interpolation, playback)
+    }
+
+    #[test]
+    fn interpolation() {
+        let linear = track(Interpolation::Linear, Playback::Once);
+        assert_eq!(linear.sample(500.0), 0.5);
+        assert_eq!(linear.sample(1500.0), 2.0);
+        assert_eq!(linear.sample(-10.0), 0.0);
+        assert_eq!(linear.sample(5000.0), 3.0);
+
+        let step = track(Interpolation::Step, Playback::Once);
+        assert_eq!(step.sample(999.0), 0.0);
+        assert_eq!(step.sample(1000.0), 1.0);
+
+        let cubic = track(Interpolation::Cubic, Playback::Once);
+        assert_eq!(cubic.sample(1000.0), 1.0);
+        assert_eq!(cubic.sample(2000.0), 3.0);
+
+        let ease = track(Interpolation::EaseInOut, Playback::Once);
+        assert_eq!(ease.sample(500.0), 0.5);
+        assert!(ease.sample(250.0) < 0.25);
+    }
+
+    #[test]
+    fn playback() {
+        let looping = track(Interpolation::Linear, Playback::Loop);
+        assert_eq!(looping.sample(2500.0), 0.5);
+
+        let ping_pong = track(Interpolation::Linear, Playback::PingPong);
+        assert_eq!(ping_pong.sample(2500.0), 2.0);
+        assert_eq!(ping_pong.sample(4500.0), 0.5);
+
+        let frames = Track::new(
+            vec![(0.0, 1.0), (10.0, 2.0)],
+            TimeUnit::Frames,
+            Interpolation::Linear,
+            Playback::Once,
+        );
+        assert_eq!(frames.value_at(5.0, 99999.0), 1.5);
+    }
+}
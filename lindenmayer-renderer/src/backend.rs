@@ -0,0 +1,258 @@
+//! Parts shared by the canvas backends which do not draw themselves but build
+//! their own geometry, e.g. images, plotter programs or CAD drawings:
+//! the graphic state, the current path and the numbers of text formats.
+
+use crate::canvas::CanvasResult;
+use crate::display_list::{Point, Transform};
+use std::f64::consts::PI;
+
+/// Transformation of the following drawing and the other state `S` of a backend,
+/// saved and restored together. The path is not part of it.
+#[derive(Debug, Clone, Default)]
+pub struct GraphicState<S> {
+    pub transform: Transform,
+    pub style: S,
+    saved: Vec<(Transform, S)>,
+}
+
+impl<S: Clone> GraphicState<S> {
+    pub fn save(&mut self) {
+        self.saved.push((self.transform, self.style.clone()));
+    }
+
+    pub fn restore(&mut self) -> CanvasResult {
+        (self.transform, self.style) = self.saved.pop().ok_or("restore without a matching save")?;
+        Ok(())
+    }
+
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        self.transform = Transform::translate(dx, dy).then(&self.transform);
+    }
+
+    pub fn rotate(&mut self, angle: f64) {
+        self.transform = Transform::rotate(angle).then(&self.transform);
+    }
+
+    pub fn scale(&mut self, sx: f64, sy: f64) {
+        let scale = Transform {
+            xx: sx,
+            yy: sy,
+            ..Transform::default()
+        };
+        self.transform = scale.then(&self.transform);
+    }
+
+    /// Position of a point once transformed.
+    pub fn apply(&self, x: f64, y: f64) -> Point {
+        self.transform.apply((x, y))
+    }
+}
+
+/// Polyline of a path, in pixels.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subpath {
+    pub points: Vec<Point>,
+    pub closed: bool,
+    // Center and radius if the subpath is just a circle
+    pub circle: Option<(Point, f64)>,
+}
+
+/// Current path of a canvas, with its points already transformed.
+/// Arcs and curves are made of lines about `tolerance` long.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    pub subpaths: Vec<Subpath>,
+}
+
+impl Path {
+    /// Subpath which the next points are added to, if not closed.
+    pub fn current(&mut self) -> Option<&mut Subpath> {
+        self.subpaths.last_mut().filter(|subpath| !subpath.closed)
+    }
+
+    pub fn move_to(&mut self, point: Point) {
+        self.subpaths.push(Subpath {
+            points: vec![point],
+            ..Subpath::default()
+        });
+    }
+
+    pub fn line_to(&mut self, point: Point) {
+        match self.current() {
+            Some(subpath) => {
+                subpath.points.push(point);
+                subpath.circle = None;
+            }
+            None => self.move_to(point),
+        }
+    }
+
+    pub fn rectangle(&mut self, transform: &Transform, x: f64, y: f64, width: f64, height: f64) {
+        let points = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+        self.subpaths.push(Subpath {
+            points: points.iter().map(|point| transform.apply(*point)).collect(),
+            closed: true,
+            circle: None,
+        });
+    }
+
+    /// Full circle, joined to the current point if any.
+    pub fn arc(&mut self, transform: &Transform, x: f64, y: f64, r: f64, tolerance: f64) {
+        let center = transform.apply((x, y));
+        let radius = r * transform.scale_factor();
+
+        let sides = sides(radius, tolerance);
+        let points = (0..=sides)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f64 / sides as f64;
+                transform.apply((x + r * angle.cos(), y + r * angle.sin()))
+            })
+            .collect::<Vec<_>>();
+
+        match self.current() {
+            Some(subpath) => {
+                subpath.points.extend(points);
+                subpath.circle = None;
+            }
+            None => self.subpaths.push(Subpath {
+                points,
+                closed: false,
+                circle: Some((center, radius)),
+            }),
+        }
+    }
+
+    /// Cubic Bezier curve from the current point, with the transformed controls.
+    pub fn curve_to(&mut self, controls: [Point; 3], tolerance: f64) {
+        let start = match self.current().and_then(|subpath| subpath.points.last().copied()) {
+            Some(start) => start,
+            None => {
+                self.move_to(controls[0]);
+                controls[0]
+            }
+        };
+
+        for point in flatten(start, controls, tolerance) {
+            self.line_to(point);
+        }
+    }
+
+    pub fn close(&mut self) {
+        if let Some(subpath) = self.current() {
+            subpath.closed = true;
+        }
+    }
+
+    /// The subpaths, leaving the path empty.
+    pub fn take(&mut self) -> Vec<Subpath> {
+        std::mem::take(&mut self.subpaths)
+    }
+
+    /// The points of the subpaths, e.g. for fills and clips.
+    pub fn take_polygons(&mut self) -> Vec<Vec<Point>> {
+        self.take().into_iter().map(|subpath| subpath.points).collect()
+    }
+}
+
+/// Closed polyline of a circle, with sides about `tolerance` long.
+pub fn circle(center: Point, radius: f64, tolerance: f64) -> Vec<Point> {
+    let sides = sides(radius, tolerance);
+    (0..=sides)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f64 / sides as f64;
+            (center.0 + radius * angle.cos(), center.1 + radius * angle.sin())
+        })
+        .collect()
+}
+
+// Number of sides of the polyline of a circle
+fn sides(radius: f64, tolerance: f64) -> usize {
+    ((2.0 * PI * radius / tolerance).ceil() as usize).clamp(8, 512)
+}
+
+/// Points of a cubic Bezier curve after `start`, in lines about `tolerance` long.
+pub fn flatten(start: Point, controls: [Point; 3], tolerance: f64) -> Vec<Point> {
+    let [c1, c2, end] = controls;
+    let length = distance(start, c1) + distance(c1, c2) + distance(c2, end);
+    let steps = ((length / tolerance).ceil() as usize).clamp(1, 256);
+
+    (1..=steps)
+        .map(|i| {
+            let t = i as f64 / steps as f64;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            (
+                a * start.0 + b * c1.0 + c * c2.0 + d * end.0,
+                a * start.1 + b * c1.1 + c * c2.1 + d * end.1,
+            )
+        })
+        .collect()
+}
+
+pub fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// Color channel in `0..=1` as a byte.
+pub fn channel(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Number rounded to the given decimals, without trailing zeros and with `0` for `-0`.
+pub fn num(value: f64, decimals: usize) -> String {
+    let text = format!("{value:.decimals$}");
+    let text = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &text
+    };
+
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(num(100.0, 0), "100");
+        assert_eq!(num(1.0 / 3.0, 6), "0.333333");
+        assert_eq!(num(-0.4, 0), "0");
+    }
+
+    #[test]
+    fn paths() {
+        let mut state = GraphicState::<()>::default();
+        state.save();
+        state.translate(10.0, 0.0);
+        state.scale(2.0, 2.0);
+        assert_eq!(state.apply(1.0, 1.0), (12.0, 2.0));
+        state.restore().unwrap();
+        assert_eq!(state.apply(1.0, 1.0), (1.0, 1.0));
+        assert!(state.restore().is_err());
+
+        // A circle on its own is kept as one
+        let mut path = Path::default();
+        path.arc(&state.transform, 5.0, 5.0, 2.0, 0.5);
+        assert_eq!(path.subpaths[0].circle, Some(((5.0, 5.0), 2.0)));
+
+        // Lines and curves are joined to the current point
+        path.move_to((0.0, 0.0));
+        path.line_to((1.0, 0.0));
+        path.curve_to([(2.0, 0.0), (3.0, 0.0), (4.0, 0.0)], 0.5);
+        path.close();
+        path.line_to((5.0, 0.0));
+
+        let subpaths = path.take();
+        assert!(path.subpaths.is_empty());
+        assert_eq!(subpaths.len(), 3);
+        assert!(subpaths[1].closed && subpaths[1].circle.is_none());
+        assert_eq!(subpaths[1].points.len(), 8);
+        assert_eq!(*subpaths[1].points.last().unwrap(), (4.0, 0.0));
+        assert_eq!(subpaths[2].points, vec![(5.0, 0.0)]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Number of symbols or operations between two checks of the deadline and of the cancellation.
+const CHECK_INTERVAL: usize = 1024;
+
+/// Limits of a render. When one is reached the turtle stops
+/// and the primitives drawn so far are kept.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    // Operations executed, counting every iteration of the blocks
+    pub max_operations: Option<usize>,
+    pub max_primitives: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+}
+
+/// Flag shared with another thread to stop a render.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Allows the token to be used again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Whether the whole expression has been drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderStatus {
+    Complete,
+    // Stopped early, only the first symbols have been drawn
+    Partial(StopReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Operations,
+    Primitives,
+    Deadline,
+    Cancelled,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            StopReason::Operations => "too many operations",
+            StopReason::Primitives => "too many primitives",
+            StopReason::Deadline => "time limit reached",
+            StopReason::Cancelled => "cancelled",
+        };
+
+        write!(f, "{reason}")
+    }
+}
+
+impl Budget {
+    /// Reason to stop before the given step, i.e. the index of a symbol or the number
+    /// of operations executed, if any. The clock and the token are only read every few steps.
+    pub(crate) fn check(&self, step: usize, operations: usize, primitives: usize) -> Option<StopReason> {
+        if self.max_operations.is_some_and(|max| operations >= max) {
+            return Some(StopReason::Operations);
+        }
+        if self.max_primitives.is_some_and(|max| primitives >= max) {
+            return Some(StopReason::Primitives);
+        }
+
+        if step % CHECK_INTERVAL != 0 {
+            return None;
+        }
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Some(StopReason::Cancelled);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Some(StopReason::Deadline);
+        }
+
+        None
+    }
+}
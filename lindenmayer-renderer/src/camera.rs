@@ -0,0 +1,160 @@
+use crate::display_list::Point;
+use meval::Expr;
+
+pub type Vector = [f64; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Orthographic,
+    Perspective,
+}
+
+/// Camera of the 3D turtle, orbiting around the center of the canvas.
+/// The parameters are expressions so that they can be animated.
+#[derive(Debug)]
+pub struct Camera<E = Expr> {
+    // The turtle is 2D when there is no projection
+    pub projection: Option<Projection>,
+    // Orbit angles in radians
+    pub yaw: E,
+    pub pitch: E,
+    // Distance of the eye from the center, used by the perspective
+    pub distance: E,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        let constant = |v: &str| v.parse().unwrap();
+
+        Self {
+            projection: None,
+            yaw: constant("0"),
+            pitch: constant("0"),
+            distance: constant("1000"),
+        }
+    }
+}
+
+/// Camera with its parameters evaluated for the current frame.
+pub(crate) struct View {
+    projection: Projection,
+    center: Vector,
+    // Rows of the rotation matrix
+    rotation: [Vector; 3],
+    distance: f64,
+}
+
+impl View {
+    pub fn new(projection: Projection, center: Vector, yaw: f64, pitch: f64, distance: f64) -> Self {
+        let (sin_y, cos_y) = yaw.sin_cos();
+        let (sin_p, cos_p) = pitch.sin_cos();
+
+        // Rotation around the vertical axis, then around the horizontal one
+        let rotation = [
+            [cos_y, 0.0, sin_y],
+            [sin_p * sin_y, cos_p, -sin_p * cos_y],
+            [-cos_p * sin_y, sin_p, cos_p * cos_y],
+        ];
+
+        Self {
+            projection,
+            center,
+            rotation,
+            distance,
+        }
+    }
+
+    /// Position of a point on the canvas and the scale of the lengths around it,
+    /// `None` if the point is behind the eye.
+    pub fn project(&self, point: Vector) -> Option<(Point, f64)> {
+        let d = sub(point, self.center);
+        let [x, y, z] = self.rotation.map(|row| dot(row, d));
+
+        // The z axis points away from the viewer
+        let scale = match self.projection {
+            Projection::Orthographic => 1.0,
+            Projection::Perspective => {
+                let depth = self.distance + z;
+                if depth <= f64::EPSILON {
+                    return None;
+                }
+                self.distance / depth
+            }
+        };
+
+        Some(((self.center[0] + x * scale, self.center[1] + y * scale), scale))
+    }
+}
+
+pub(crate) fn add(a: Vector, b: Vector) -> Vector {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn sub(a: Vector, b: Vector) -> Vector {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(a: Vector, k: f64) -> Vector {
+    [a[0] * k, a[1] * k, a[2] * k]
+}
+
+pub(crate) fn dot(a: Vector, b: Vector) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: Vector, b: Vector) -> Vector {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Rotates `v` by `angle` around the unit vector `axis`, counterclockwise
+/// when the axis points towards the viewer.
+pub(crate) fn rotate_around(v: Vector, axis: Vector, angle: f64) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+
+    add(
+        add(scale(v, cos), scale(cross(axis, v), sin)),
+        scale(axis, dot(axis, v) * (1.0 - cos)),
+    )
+}
+
+/// Rotates the pair of orthogonal vectors (a, b) by `angle`, from `a` towards `b`.
+pub(crate) fn rotate(a: Vector, b: Vector, angle: f64) -> (Vector, Vector) {
+    let (sin, cos) = angle.sin_cos();
+
+    (
+        add(scale(a, cos), scale(b, sin)),
+        sub(scale(b, cos), scale(a, sin)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point, b: Point) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn projections() {
+        let center = [100.0, 100.0, 0.0];
+
+        // Without rotation the orthographic camera is the identity on the canvas
+        let front = View::new(Projection::Orthographic, center, 0.0, 0.0, 1000.0);
+        assert_eq!(front.project([30.0, 40.0, 25.0]), Some(((30.0, 40.0), 1.0)));
+
+        // A quarter orbit shows the depth horizontally
+        let side = View::new(Projection::Orthographic, center, std::f64::consts::FRAC_PI_2, 0.0, 0.0);
+        let (point, _) = side.project([100.0, 50.0, 20.0]).unwrap();
+        assert!(close(point, (120.0, 50.0)));
+
+        // Farther points are closer to the center
+        let perspective = View::new(Projection::Perspective, center, 0.0, 0.0, 100.0);
+        assert_eq!(perspective.project([200.0, 100.0, 100.0]), Some(((150.0, 100.0), 0.5)));
+        assert_eq!(perspective.project([200.0, 100.0, -100.0]), None);
+    }
+}
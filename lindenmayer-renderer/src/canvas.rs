@@ -1,192 +1,271 @@
-use crate::{LSystemRenderer, Operation};
-use meval::ContextProvider;
-use std::collections::HashMap;
+use crate::budget::{Budget, RenderStatus};
+use crate::camera::Vector;
+use crate::display_list::Transform;
+use crate::program::EvalError;
+use crate::LSystemRenderer;
+use std::error::Error;
+use std::fmt;
 
-use crate::expressions;
+/// Failure of a canvas backend.
+pub type CanvasError = Box<dyn Error + Send + Sync>;
 
+pub type CanvasResult = Result<(), CanvasError>;
+
+/// Error while drawing a frame.
+#[derive(Debug)]
+pub enum RenderError {
+    // An expression of the configuration could not be evaluated
+    Expression(EvalError),
+    // An operation of a symbol failed
+    Operation(Box<OperationError>),
+    Canvas(CanvasError),
+}
+
+/// Failure of an operation, with the place where it happened.
+#[derive(Debug)]
+pub struct OperationError {
+    pub error: ExecutionError,
+    pub symbol: char,
+    // Index of the symbol in the expanded string
+    pub index: usize,
+    // Position of the operation in the list of the symbol
+    pub operation: usize,
+    // Positions inside of the blocks of `if` and `repeat`, down to the failing operation.
+    // The else branch follows the then branch.
+    pub path: Vec<usize>,
+    // Source text of the failing operation, empty if unknown
+    pub source: String,
+    // State of the turtle before the operation
+    pub position: Vector,
+    pub depth: i32,
+}
+
+#[derive(Debug)]
+pub enum ExecutionError {
+    Expression(EvalError),
+    // Pop without a matching push, only reported in strict mode
+    StackUnderflow,
+    // Push beyond the maximum depth of the strict mode
+    StackOverflow(usize),
+    // More `repeat` iterations than allowed for a single symbol
+    TooManyIterations(u32),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Expression(error) => write!(f, "{error}"),
+            RenderError::Operation(error) => write!(f, "{error}"),
+            RenderError::Canvas(error) => write!(f, "canvas error: {error}"),
+        }
+    }
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Operations are numbered from 1
+        write!(f, "symbol '{}' (op {}", self.symbol, self.operation + 1)?;
+        for position in &self.path {
+            write!(f, ".{}", position + 1)?;
+        }
+        if !self.source.is_empty() {
+            write!(f, ": {}", self.source)?;
+        }
+        write!(f, ") at index {}: {}", self.index, self.error)?;
+
+        let [x, y, z] = self.position;
+        write!(f, " [turtle at ({x:.2}, {y:.2}")?;
+        if z != 0.0 {
+            write!(f, ", {z:.2}")?;
+        }
+        write!(f, "), depth {}]", self.depth)
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Expression(error) => write!(f, "{error}"),
+            ExecutionError::StackUnderflow => write!(f, "pop without a matching push"),
+            ExecutionError::StackOverflow(max_depth) => {
+                write!(f, "push beyond the maximum depth of {max_depth}")
+            }
+            ExecutionError::TooManyIterations(max) => {
+                write!(f, "more than {max} repeat iterations in a symbol")
+            }
+        }
+    }
+}
+
+impl Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExecutionError::Expression(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<meval::Error> for ExecutionError {
+    fn from(error: meval::Error) -> Self {
+        ExecutionError::Expression(error.into())
+    }
+}
+
+impl From<EvalError> for ExecutionError {
+    fn from(error: EvalError) -> Self {
+        ExecutionError::Expression(error)
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Expression(error) => Some(error),
+            RenderError::Operation(error) => Some(&error.error),
+            RenderError::Canvas(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+impl From<meval::Error> for RenderError {
+    fn from(error: meval::Error) -> Self {
+        RenderError::Expression(error.into())
+    }
+}
+
+impl From<EvalError> for RenderError {
+    fn from(error: EvalError) -> Self {
+        RenderError::Expression(error)
+    }
+}
+
+impl From<CanvasError> for RenderError {
+    fn from(error: CanvasError) -> Self {
+        RenderError::Canvas(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Which point of the text is placed at the given position.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TextAnchor {
+    #[default]
+    Start,
+    Middle,
+    End,
+}
+
+/// Drawing backend. The methods fail when the backend cannot draw,
+/// e.g. when its surface is in an error state.
 pub trait Canvas {
-    fn move_to(&self, x: f64, y: f64);
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult;
+
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult;
+
+    fn stroke(&self) -> CanvasResult;
+
+    fn save(&self) -> CanvasResult;
+
+    fn restore(&self) -> CanvasResult;
+
+    // Transformations of the following drawing, restored by `restore`
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult;
 
-    fn line_to(&self, x: f64, y: f64);
+    fn rotate(&self, angle: f64) -> CanvasResult;
 
-    fn stroke(&self);
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult;
 
-    fn save(&self);
+    /// Replaces the current transformation.
+    fn set_transform(&self, transform: &Transform) -> CanvasResult;
 
-    fn restore(&self);
+    /// Restricts the drawing to a rectangle, until `restore`.
+    fn clip_rect(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult;
 
-    fn set_line_width(&self, thickness: f64);
+    /// Restricts the drawing to the inside of the current path, until `restore`.
+    /// The path is consumed.
+    fn clip_path(&self) -> CanvasResult;
 
-    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64);
+    fn set_line_width(&self, thickness: f64) -> CanvasResult;
 
-    fn set_color(&self, r: f64, g: f64, b: f64, a: f64);
+    fn set_line_cap(&self, cap: LineCap) -> CanvasResult;
 
-    fn arc(&self, x: f64, y: f64, r: f64);
+    fn set_line_join(&self, join: LineJoin) -> CanvasResult;
 
-    fn fill(&self);
+    fn set_miter_limit(&self, limit: f64) -> CanvasResult;
 
+    /// Lengths of the alternating dashes and gaps, solid lines if empty.
+    fn set_dash(&self, dashes: &[f64]) -> CanvasResult;
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult;
+
+    fn set_color(&self, r: f64, g: f64, b: f64, a: f64) -> CanvasResult;
+
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult;
+
+    fn fill(&self) -> CanvasResult;
+
+    fn close_path(&self) -> CanvasResult;
+
+    /// Cubic Bezier curve from the current point to (x3, y3),
+    /// with (x1, y1) and (x2, y2) as control points.
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult;
+
+    /// Text of the given font size with its baseline going through (x, y),
+    /// rotated by `angle` radians around it. Backends without text support skip it.
+    fn text(
+        &self,
+        _x: f64,
+        _y: f64,
+        _text: &str,
+        _size: f64,
+        _angle: f64,
+        _anchor: TextAnchor,
+    ) -> CanvasResult {
+        Ok(())
+    }
 }
 
 pub type ExprContext<'a> = meval::Context<'a>;
 
 impl dyn Canvas {
-    pub fn draw_fractal<'a>(
+    pub fn draw_fractal(
         &self,
         fractal: &LSystemRenderer,
-        variables: &'a mut ExprContext,
-    ) -> Result<(), meval::Error> {
-        // Stack of variables
-        let mut stack1: HashMap<String, Vec<f64>> = HashMap::new();
-        let mut stack2 = vec![]; // (pos, rot, thickness)
-
-        // Current variables
-        let mut pos = fractal.initial_pos;
-        let mut rot = fractal.initial_rot;
-        let mut thickness = fractal.initial_thickness;
-        let mut color = fractal.initial_color;
-
-        let mut depth = 0;
-
-        // Set length variable
-        variables.var("LENGTH", fractal.expression.len() as f64);
-        // Set depth variable
-        variables.var("DEPTH", 0f64);
-        // Set pos variables
-        variables.var("POSX", pos.0 as f64);
-        variables.var("POSY", pos.1 as f64);
-
-        // Add random function to context
-        let rng = fractal.rng.clone();
-        variables.func2("rand", move |x, y| {
-            let mut rng = (*rng).borrow_mut();
-            expressions::rand(&mut rng, x, y)
-        });
-
-        // Add rgb function
-        variables.func3("rgb", move |r, g, b| {
-            expressions::rgb(r, g, b)
-        });
-
-        // Add initial values and keys
-        for (key, value) in fractal.variables.iter() {
-            variables.var(key, *value);
-
-            stack1.insert(key.to_string(), vec![*value]);
-        }
-        stack2.push((pos, rot, thickness, color));
-
-        let mut ignore_counter = 0;
-
-        // Fill background
-        let bg_col = fractal.background_color;
-        self.set_color(bg_col.0, bg_col.1, bg_col.2, bg_col.3);
-        self.rectangle(0.0, 0.0, fractal.canvas.0 as f64, fractal.canvas.1 as f64);
-        self.fill();
-
-        // Execute each operation
-        for (index, c) in fractal.expression.chars().enumerate() {
-            // Skip chars if an ignore action has been called
-            if ignore_counter > 0 {
-                ignore_counter -= 1;
-                continue;
-            }
-
-            // Update variables
-            variables.var("INDEX", index as f64);
-
-            let operations = fractal.operations.get(&c);
-
-            if let Some(operations) = operations {
-                for op in operations {
-                    match op {
-                        Operation::Forward(expr) => {
-                            let length = expr.eval_with_context(&variables)?;
-                            let start_pos = pos;
-                            pos = (pos.0 - length * rot.sin(), pos.1 - length * rot.cos());
-    
-                            self.set_line_width(thickness);
-                            self.set_color(color.0, color.1, color.2, color.3);
-                            self.move_to(start_pos.0, start_pos.1);
-                            self.line_to(pos.0, pos.1);
-                            self.stroke();
-
-                            // Update POS variables
-                            variables.var("POSX", pos.0 as f64);
-                            variables.var("POSY", pos.1 as f64);
-                        }
-                        Operation::Jump(expr) => {
-                            let length = expr.eval_with_context(&variables)?;
-                            pos = (pos.0 - length * rot.sin(), pos.1 - length * rot.cos());
-
-                            // Update POS variables
-                            variables.var("POSX", pos.0 as f64);
-                            variables.var("POSY", pos.1 as f64);
-                        }
-                        Operation::Dot(expr) => {
-                            let radius = expr.eval_with_context(&variables)?;
-    
-                            self.set_color(color.0, color.1, color.2, color.3);
-                            self.arc(pos.0, pos.1, radius);
-                            self.fill();
-                        }
-                        Operation::Rotate(expr) => {
-                            rot += expr.eval_with_context(&variables)?;
-                        }
-                        Operation::Thickness(expr) => {
-                            thickness = expr.eval_with_context(&variables)?;
-                        }
-                        Operation::Ignore(expr) => {
-                            let v = expr.eval_with_context(&variables)?;
-                            ignore_counter = v as u32;
-                        }
-                        Operation::PushStack => {
-                            for (key, value) in &mut stack1 {
-                                if let Some(v) = variables.get_var(key) {
-                                    value.push(v);
-                                }
-                            }
-                            stack2.push((pos, rot, thickness, color));
-    
-                            // Update depth value
-                            depth += 1;
-                            variables.var("DEPTH", depth as f64);
-    
-                            self.save();
-                        }
-                        Operation::PopStack => {
-                            if let Some((old_pos, old_rot, old_thickness, old_color)) = stack2.pop() {
-                                // restore variables
-                                pos = old_pos;
-                                rot = old_rot;
-                                thickness = old_thickness;
-                                color = old_color;
-                                for (key, value) in &mut stack1 {
-                                    if let Some(v) = value.pop() {
-                                        variables.var(key, v);
-                                    }
-                                }
-    
-                                // Update depth variable
-                                depth -= 1;
-                                variables.var("DEPTH", depth as f64);
-    
-                                self.restore();
-                            }
-                        }
-                        Operation::SetColor(color_value) => {
-                            color = color_value.get_color(&variables)?;
-                        }
-    
-                        Operation::SetVar(name, expr) => {
-                            let v = expr.eval_with_context(&variables)?;
-                            variables.var(name, v);
-                        }
-                    }
-                }
-            }
-        }
+        variables: &mut ExprContext,
+    ) -> Result<(), RenderError> {
+        let display_list = fractal.display_list(variables)?;
+        display_list.replay(self)?;
 
         Ok(())
     }
 
+    /// Draws the fractal until the budget runs out,
+    /// the primitives of the symbols executed so far are drawn anyway.
+    pub fn draw_fractal_within(
+        &self,
+        fractal: &LSystemRenderer,
+        variables: &mut ExprContext,
+        budget: &Budget,
+    ) -> Result<RenderStatus, RenderError> {
+        let (display_list, status) = fractal.display_list_within(variables, budget)?;
+        display_list.replay(self)?;
+
+        Ok(status)
+    }
 }
@@ -0,0 +1,131 @@
+use crate::display_list::{PathSegment, Point};
+
+/// How the points of a path are joined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    // Straight lines
+    Joined,
+    // Curve through the points
+    CatmullRom,
+    // Curve through the midpoints, the points are its control points
+    Bezier,
+}
+
+/// Consecutive forward moves of a branch are drawn as a single path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curves {
+    pub smoothing: Smoothing,
+    // Roundness of the curves, 0 gives straight lines.
+    // Catmull-Rom splines are the standard ones with 0.5
+    pub tension: f64,
+}
+
+impl Default for Curves {
+    fn default() -> Self {
+        Self {
+            smoothing: Smoothing::Joined,
+            tension: 0.5,
+        }
+    }
+}
+
+impl Curves {
+    /// Segments of the path through the points, starting from the first one.
+    pub fn segments(&self, points: &[Point]) -> Vec<PathSegment> {
+        match self.smoothing {
+            Smoothing::Joined => joined(points),
+            Smoothing::CatmullRom => self.catmull_rom(points),
+            Smoothing::Bezier => self.bezier(points),
+        }
+    }
+
+    fn catmull_rom(&self, points: &[Point]) -> Vec<PathSegment> {
+        let k = self.tension / 3.0;
+        let last = points.len().saturating_sub(1);
+
+        (0..last)
+            .map(|i| {
+                // The ends are repeated to get the tangents
+                let p0 = points[i.saturating_sub(1)];
+                let p1 = points[i];
+                let p2 = points[i + 1];
+                let p3 = points[(i + 2).min(last)];
+
+                PathSegment::Cubic([
+                    (p1.0 + (p2.0 - p0.0) * k, p1.1 + (p2.1 - p0.1) * k),
+                    (p2.0 - (p3.0 - p1.0) * k, p2.1 - (p3.1 - p1.1) * k),
+                    p2,
+                ])
+            })
+            .collect()
+    }
+
+    fn bezier(&self, points: &[Point]) -> Vec<PathSegment> {
+        if points.len() < 3 {
+            return joined(points);
+        }
+
+        // A tension of 0.5 gives the quadratic curve of each corner
+        let k = self.tension * 4.0 / 3.0;
+        let mid = |a: Point, b: Point| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+        let towards = |a: Point, b: Point| (a.0 + (b.0 - a.0) * k, a.1 + (b.1 - a.1) * k);
+
+        let mut segments = vec![PathSegment::Line(mid(points[0], points[1]))];
+        for corner in points.windows(3) {
+            let start = mid(corner[0], corner[1]);
+            let end = mid(corner[1], corner[2]);
+            segments.push(PathSegment::Cubic([
+                towards(start, corner[1]),
+                towards(end, corner[1]),
+                end,
+            ]));
+        }
+        segments.push(PathSegment::Line(points[points.len() - 1]));
+
+        segments
+    }
+}
+
+fn joined(points: &[Point]) -> Vec<PathSegment> {
+    points.iter().skip(1).map(|point| PathSegment::Line(*point)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoothing() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+
+        let joined = Curves::default();
+        assert_eq!(
+            joined.segments(&points),
+            vec![PathSegment::Line((10.0, 0.0)), PathSegment::Line((10.0, 10.0))]
+        );
+
+        // The curve goes through every point
+        let catmull_rom = Curves {
+            smoothing: Smoothing::CatmullRom,
+            tension: 0.5,
+        };
+        let segments = catmull_rom.segments(&points);
+        assert_eq!(segments.len(), 2);
+        assert!(matches!(segments[0], PathSegment::Cubic([_, _, end]) if end == points[1]));
+        assert!(matches!(segments[1], PathSegment::Cubic([_, _, end]) if end == points[2]));
+
+        // Without tension the controls are on the points
+        let bezier = Curves {
+            smoothing: Smoothing::Bezier,
+            tension: 0.0,
+        };
+        assert_eq!(
+            bezier.segments(&points),
+            vec![
+                PathSegment::Line((5.0, 0.0)),
+                PathSegment::Cubic([(5.0, 0.0), (10.0, 5.0), (10.0, 5.0)]),
+                PathSegment::Line((10.0, 10.0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,673 @@
+use crate::canvas::{Canvas, CanvasResult, LineCap, LineJoin, TextAnchor};
+use crate::expressions::color::Rgba;
+use meval::Expr;
+use std::rc::Rc;
+
+pub type Point = (f64, f64);
+
+/// Piece of a path, from the end of the previous one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    Line(Point),
+    // Cubic Bezier curve through two control points, then to the end
+    Cubic([Point; 3]),
+}
+
+impl PathSegment {
+    fn points_mut(&mut self) -> &mut [Point] {
+        match self {
+            PathSegment::Line(a) => std::slice::from_mut(a),
+            PathSegment::Cubic(points) => points,
+        }
+    }
+
+    fn points(&self) -> &[Point] {
+        match self {
+            PathSegment::Line(a) => std::slice::from_ref(a),
+            PathSegment::Cubic(points) => points,
+        }
+    }
+}
+
+/// How the lines are stroked, besides their width and color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub miter_limit: f64,
+    // Lengths of the dashes and of the gaps between them, solid if empty
+    pub dashes: Rc<[f64]>,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 10.0,
+            dashes: Rc::new([]),
+        }
+    }
+}
+
+/// Shape produced by the turtle.
+/// `index` is the position of the symbol which produced it,
+/// `depth` the number of states on the stack at that point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Segment {
+        from: Point,
+        to: Point,
+        width: f64,
+        color: Rgba,
+        stroke: StrokeStyle,
+        index: usize,
+        depth: usize,
+    },
+    // Consecutive segments drawn as a single stroke
+    Path {
+        start: Point,
+        segments: Vec<PathSegment>,
+        width: f64,
+        color: Rgba,
+        stroke: StrokeStyle,
+        index: usize,
+        depth: usize,
+    },
+    Dot {
+        center: Point,
+        radius: f64,
+        color: Rgba,
+        index: usize,
+        depth: usize,
+    },
+    Polygon {
+        points: Vec<Point>,
+        color: Rgba,
+        index: usize,
+        depth: usize,
+    },
+    // Text with its baseline through the position, rotated by the angle
+    Text {
+        position: Point,
+        text: String,
+        size: f64,
+        angle: f64,
+        anchor: TextAnchor,
+        color: Rgba,
+        index: usize,
+        depth: usize,
+    },
+    // Filled rectangle, e.g. the background
+    Fill {
+        origin: Point,
+        size: (f64, f64),
+        color: Rgba,
+    },
+}
+
+/// Geometry of a frame, in drawing order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayList {
+    pub primitives: Vec<Primitive>,
+    // Transformation of the whole drawing, applied by the canvas
+    pub view: Option<ViewTransform>,
+}
+
+/// Zoom and rotation of the whole drawing around a point,
+/// which is shown at the center of the canvas.
+#[derive(Debug)]
+pub struct Viewport<E = Expr> {
+    pub zoom: E,
+    pub center: (E, E),
+    // Rotation in radians
+    pub angle: E,
+}
+
+/// Viewport with its parameters evaluated for the current frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewTransform {
+    pub zoom: f64,
+    pub center: Point,
+    pub angle: f64,
+    // Size of the canvas, the drawing is clipped to it
+    pub canvas: (f64, f64),
+}
+
+impl ViewTransform {
+    pub fn transform(&self) -> Transform {
+        Transform::translate(-self.center.0, -self.center.1)
+            .then(&Transform::rotate(self.angle))
+            .then(&Transform::scale(self.zoom))
+            .then(&Transform::translate(self.canvas.0 / 2.0, self.canvas.1 / 2.0))
+    }
+
+    // Applies the transformation on top of the one of the canvas
+    fn apply(&self, canvas: &dyn Canvas) -> CanvasResult {
+        canvas.clip_rect(0.0, 0.0, self.canvas.0, self.canvas.1)?;
+        canvas.translate(self.canvas.0 / 2.0, self.canvas.1 / 2.0)?;
+        canvas.scale(self.zoom, self.zoom)?;
+        canvas.rotate(self.angle)?;
+        canvas.translate(-self.center.0, -self.center.1)
+    }
+}
+
+/// Scales and centers the drawing so that it fills the canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Autofit {
+    // Space left around the drawing, in pixels
+    pub margin: f64,
+    // Whether line widths and dot radii are scaled with the drawing,
+    // otherwise they stay in screen units
+    pub scale_widths: bool,
+}
+
+impl Default for Autofit {
+    fn default() -> Self {
+        Self {
+            margin: 20.0,
+            scale_widths: true,
+        }
+    }
+}
+
+/// Affine transformation, with the same layout as a cairo matrix:
+/// `x' = xx * x + xy * y + x0` and `y' = yx * x + yy * y + y0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub xx: f64,
+    pub yx: f64,
+    pub xy: f64,
+    pub yy: f64,
+    pub x0: f64,
+    pub y0: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::scale(1.0)
+    }
+}
+
+impl Transform {
+    pub fn translate(dx: f64, dy: f64) -> Self {
+        Self {
+            x0: dx,
+            y0: dy,
+            ..Self::scale(1.0)
+        }
+    }
+
+    pub fn scale(factor: f64) -> Self {
+        Self {
+            xx: factor,
+            yx: 0.0,
+            xy: 0.0,
+            yy: factor,
+            x0: 0.0,
+            y0: 0.0,
+        }
+    }
+
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+
+        Self {
+            xx: cos,
+            yx: sin,
+            xy: -sin,
+            yy: cos,
+            x0: 0.0,
+            y0: 0.0,
+        }
+    }
+
+    /// Transformation applying `self` first and then `other`.
+    pub fn then(&self, other: &Transform) -> Self {
+        Self {
+            xx: other.xx * self.xx + other.xy * self.yx,
+            yx: other.yx * self.xx + other.yy * self.yx,
+            xy: other.xx * self.xy + other.xy * self.yy,
+            yy: other.yx * self.xy + other.yy * self.yy,
+            x0: other.xx * self.x0 + other.xy * self.y0 + other.x0,
+            y0: other.yx * self.x0 + other.yy * self.y0 + other.y0,
+        }
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        (
+            self.xx * point.0 + self.xy * point.1 + self.x0,
+            self.yx * point.0 + self.yy * point.1 + self.y0,
+        )
+    }
+
+    /// How much lengths are scaled, on average.
+    pub fn scale_factor(&self) -> f64 {
+        (self.xx * self.yy - self.xy * self.yx).abs().sqrt()
+    }
+}
+
+fn set_stroke<'a>(
+    canvas: &dyn Canvas,
+    current: &mut Option<&'a StrokeStyle>,
+    stroke: &'a StrokeStyle,
+) -> CanvasResult {
+    if *current != Some(stroke) {
+        stroke.set(canvas)?;
+        *current = Some(stroke);
+    }
+
+    Ok(())
+}
+
+impl StrokeStyle {
+    fn set(&self, canvas: &dyn Canvas) -> CanvasResult {
+        canvas.set_line_cap(self.cap)?;
+        canvas.set_line_join(self.join)?;
+        canvas.set_miter_limit(self.miter_limit)?;
+        canvas.set_dash(&self.dashes)
+    }
+
+    // Dashes are lengths, scaled with the widths
+    fn scale(&mut self, factor: f64) {
+        if factor != 1.0 && !self.dashes.is_empty() {
+            self.dashes = self.dashes.iter().map(|dash| dash * factor).collect();
+        }
+    }
+}
+
+impl Primitive {
+    /// Depth of the stack when the primitive was drawn, none for fills.
+    pub fn depth(&self) -> Option<usize> {
+        match self {
+            Primitive::Segment { depth, .. }
+            | Primitive::Path { depth, .. }
+            | Primitive::Dot { depth, .. }
+            | Primitive::Polygon { depth, .. }
+            | Primitive::Text { depth, .. } => Some(*depth),
+            Primitive::Fill { .. } => None,
+        }
+    }
+}
+
+impl DisplayList {
+    pub fn push(&mut self, primitive: Primitive) {
+        self.primitives.push(primitive);
+    }
+
+    /// Primitives produced by the symbol at `index`.
+    pub fn at_index(&self, index: usize) -> impl Iterator<Item = &Primitive> {
+        self.primitives.iter().filter(move |primitive| match primitive {
+            Primitive::Segment { index: i, .. }
+            | Primitive::Path { index: i, .. }
+            | Primitive::Dot { index: i, .. }
+            | Primitive::Polygon { index: i, .. }
+            | Primitive::Text { index: i, .. } => *i == index,
+            Primitive::Fill { .. } => false,
+        })
+    }
+
+    /// Bounding box (min, max) of the segments, paths, dots and polygons, including their width.
+    /// The control points of the curves are included, so the box may not be tight.
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        self.bounds_with(true)
+    }
+
+    fn bounds_with(&self, widths: bool) -> Option<(Point, Point)> {
+        let mut bounds: Option<(Point, Point)> = None;
+
+        let mut extend = |(x, y): Point, margin: f64| {
+            let margin = if widths { margin } else { 0.0 };
+            let (min, max) = bounds.get_or_insert(((x, y), (x, y)));
+            *min = (min.0.min(x - margin), min.1.min(y - margin));
+            *max = (max.0.max(x + margin), max.1.max(y + margin));
+        };
+
+        for primitive in &self.primitives {
+            match primitive {
+                Primitive::Segment { from, to, width, .. } => {
+                    extend(*from, width / 2.0);
+                    extend(*to, width / 2.0);
+                }
+                Primitive::Path {
+                    start,
+                    segments,
+                    width,
+                    ..
+                } => {
+                    extend(*start, width / 2.0);
+                    for point in segments.iter().flat_map(PathSegment::points) {
+                        extend(*point, width / 2.0);
+                    }
+                }
+                Primitive::Dot { center, radius, .. } => extend(*center, *radius),
+                Primitive::Polygon { points, .. } => {
+                    points.iter().for_each(|point| extend(*point, 0.0));
+                }
+                // The extent of the text depends on the font of the backend
+                Primitive::Text { position, .. } => extend(*position, 0.0),
+                Primitive::Fill { .. } => {}
+            }
+        }
+
+        bounds
+    }
+
+    /// Transforms every primitive. Widths and radii are scaled as well,
+    /// rectangles are transformed through their corners.
+    pub fn transform(&mut self, transform: &Transform) {
+        self.apply(transform, true, true);
+    }
+
+    /// Fits the segments, paths, dots and polygons into a canvas of the given size,
+    /// the fills (e.g. the background) are left as they are.
+    pub fn fit(&mut self, canvas: (f64, f64), autofit: &Autofit) {
+        let Some((min, max)) = self.bounds_with(autofit.scale_widths) else {
+            return;
+        };
+
+        // Uniform scale, a single point is only centered
+        let available = (canvas.0 - 2.0 * autofit.margin, canvas.1 - 2.0 * autofit.margin);
+        let size = (max.0 - min.0, max.1 - min.1);
+        let scale = (available.0 / size.0).min(available.1 / size.1);
+        let scale = if scale.is_finite() && scale > 0.0 { scale } else { 1.0 };
+
+        let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+        let transform = Transform::translate(-center.0, -center.1)
+            .then(&Transform::scale(scale))
+            .then(&Transform::translate(canvas.0 / 2.0, canvas.1 / 2.0));
+
+        self.apply(&transform, autofit.scale_widths, false);
+    }
+
+    fn apply(&mut self, transform: &Transform, widths: bool, fills: bool) {
+        let scale = if widths { transform.scale_factor() } else { 1.0 };
+
+        for primitive in &mut self.primitives {
+            match primitive {
+                Primitive::Segment {
+                    from,
+                    to,
+                    width,
+                    stroke,
+                    ..
+                } => {
+                    *from = transform.apply(*from);
+                    *to = transform.apply(*to);
+                    *width *= scale;
+                    stroke.scale(scale);
+                }
+                Primitive::Path {
+                    start,
+                    segments,
+                    width,
+                    stroke,
+                    ..
+                } => {
+                    *start = transform.apply(*start);
+                    for point in segments.iter_mut().flat_map(PathSegment::points_mut) {
+                        *point = transform.apply(*point);
+                    }
+                    *width *= scale;
+                    stroke.scale(scale);
+                }
+                Primitive::Dot { center, radius, .. } => {
+                    *center = transform.apply(*center);
+                    *radius *= scale;
+                }
+                Primitive::Polygon { points, .. } => {
+                    for point in points {
+                        *point = transform.apply(*point);
+                    }
+                }
+                Primitive::Text {
+                    position,
+                    size,
+                    angle,
+                    ..
+                } => {
+                    *position = transform.apply(*position);
+                    *size *= scale;
+                    *angle += transform.yx.atan2(transform.xx);
+                }
+                Primitive::Fill { .. } if !fills => {}
+                Primitive::Fill { origin, size, .. } => {
+                    let a = transform.apply(*origin);
+                    let b = transform.apply((origin.0 + size.0, origin.1 + size.1));
+                    *origin = (a.0.min(b.0), a.1.min(b.1));
+                    *size = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+                }
+            }
+        }
+    }
+
+    /// Draws the primitives onto a canvas.
+    pub fn replay(&self, canvas: &dyn Canvas) -> CanvasResult {
+        self.replay_with(canvas, |_| Ok(()))
+    }
+
+    /// Draws the primitives onto a canvas, calling `before` with each one before drawing it,
+    /// e.g. for backends which group the shapes by the depth of their branch.
+    pub fn replay_with(
+        &self,
+        canvas: &dyn Canvas,
+        mut before: impl FnMut(&Primitive) -> CanvasResult,
+    ) -> CanvasResult {
+        // The stroke style is only set when it changes
+        let mut current: Option<&StrokeStyle> = None;
+        let mut in_view = false;
+
+        for primitive in &self.primitives {
+            // Fills, e.g. the background, cover the canvas whatever the view
+            let view = self.view.filter(|_| !matches!(primitive, Primitive::Fill { .. }));
+            if view.is_some() != in_view {
+                match view {
+                    Some(view) => {
+                        canvas.save()?;
+                        view.apply(canvas)?;
+                    }
+                    None => canvas.restore()?,
+                }
+                in_view = view.is_some();
+                current = None;
+            }
+            before(primitive)?;
+
+            match primitive {
+                Primitive::Segment {
+                    from,
+                    to,
+                    width,
+                    color,
+                    stroke,
+                    ..
+                } => {
+                    set_stroke(canvas, &mut current, stroke)?;
+                    canvas.set_line_width(*width)?;
+                    canvas.set_color(color.0, color.1, color.2, color.3)?;
+                    canvas.move_to(from.0, from.1)?;
+                    canvas.line_to(to.0, to.1)?;
+                    canvas.stroke()?;
+                }
+                Primitive::Path {
+                    start,
+                    segments,
+                    width,
+                    color,
+                    stroke,
+                    ..
+                } => {
+                    set_stroke(canvas, &mut current, stroke)?;
+                    canvas.set_line_width(*width)?;
+                    canvas.set_color(color.0, color.1, color.2, color.3)?;
+                    canvas.move_to(start.0, start.1)?;
+                    for segment in segments {
+                        match segment {
+                            PathSegment::Line(to) => canvas.line_to(to.0, to.1)?,
+                            PathSegment::Cubic([c1, c2, to]) => {
+                                canvas.curve_to(c1.0, c1.1, c2.0, c2.1, to.0, to.1)?
+                            }
+                        }
+                    }
+                    canvas.stroke()?;
+                }
+                Primitive::Dot {
+                    center,
+                    radius,
+                    color,
+                    ..
+                } => {
+                    canvas.set_color(color.0, color.1, color.2, color.3)?;
+                    canvas.arc(center.0, center.1, *radius)?;
+                    canvas.fill()?;
+                }
+                Primitive::Polygon { points, color, .. } => {
+                    let Some((first, rest)) = points.split_first() else {
+                        continue;
+                    };
+
+                    canvas.set_color(color.0, color.1, color.2, color.3)?;
+                    canvas.move_to(first.0, first.1)?;
+                    for point in rest {
+                        canvas.line_to(point.0, point.1)?;
+                    }
+                    canvas.close_path()?;
+                    canvas.fill()?;
+                }
+                Primitive::Text {
+                    position,
+                    text,
+                    size,
+                    angle,
+                    anchor,
+                    color,
+                    ..
+                } => {
+                    canvas.set_color(color.0, color.1, color.2, color.3)?;
+                    canvas.text(position.0, position.1, text, *size, *angle, *anchor)?;
+                }
+                Primitive::Fill {
+                    origin,
+                    size,
+                    color,
+                } => {
+                    canvas.set_color(color.0, color.1, color.2, color.3)?;
+                    canvas.rectangle(origin.0, origin.1, size.0, size.1)?;
+                    canvas.fill()?;
+                }
+            }
+        }
+
+        if in_view {
+            canvas.restore()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(from: Point, to: Point, index: usize) -> Primitive {
+        Primitive::Segment {
+            from,
+            to,
+            width: 2.0,
+            color: (0.0, 0.0, 0.0, 1.0),
+            stroke: StrokeStyle::default(),
+            index,
+            depth: 0,
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let list = DisplayList {
+            primitives: vec![
+                segment((0.0, 0.0), (10.0, 0.0), 0),
+                segment((10.0, 0.0), (10.0, -5.0), 2),
+                Primitive::Dot {
+                    center: (20.0, 3.0),
+                    radius: 1.0,
+                    color: (1.0, 0.0, 0.0, 1.0),
+                    index: 2,
+                    depth: 0,
+                },
+            ],
+            view: None,
+        };
+
+        assert_eq!(list.at_index(2).count(), 2);
+        assert_eq!(list.at_index(1).count(), 0);
+        assert_eq!(list.bounds(), Some(((-1.0, -6.0), (21.0, 4.0))));
+    }
+
+    #[test]
+    fn fitting() {
+        let mut list = DisplayList {
+            primitives: vec![
+                Primitive::Fill {
+                    origin: (0.0, 0.0),
+                    size: (100.0, 100.0),
+                    color: (1.0, 1.0, 1.0, 1.0),
+                },
+                segment((0.0, 0.0), (20.0, 10.0), 0),
+            ],
+            view: None,
+        };
+
+        let autofit = Autofit {
+            margin: 10.0,
+            scale_widths: false,
+        };
+        list.fit((100.0, 100.0), &autofit);
+
+        // The background does not move
+        assert!(matches!(list.primitives[0], Primitive::Fill { origin, .. } if origin == (0.0, 0.0)));
+        assert_eq!(list.bounds(), Some(((9.0, 29.0), (91.0, 71.0))));
+    }
+
+    #[test]
+    fn transforms() {
+        let transform =
+            Transform::rotate(std::f64::consts::FRAC_PI_2).then(&Transform::translate(5.0, 0.0));
+        let (x, y) = transform.apply((1.0, 0.0));
+        assert!((x - 5.0).abs() < 1e-12 && (y - 1.0).abs() < 1e-12);
+
+        let mut list = DisplayList {
+            primitives: vec![segment((1.0, 1.0), (2.0, 3.0), 0)],
+            view: None,
+        };
+        list.transform(&Transform::scale(2.0).then(&Transform::translate(1.0, 0.0)));
+        assert_eq!(
+            list.primitives[0],
+            Primitive::Segment {
+                from: (3.0, 2.0),
+                to: (5.0, 6.0),
+                width: 4.0,
+                color: (0.0, 0.0, 0.0, 1.0),
+                stroke: StrokeStyle::default(),
+                index: 0,
+                depth: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn views() {
+        let view = ViewTransform {
+            zoom: 2.0,
+            center: (10.0, 10.0),
+            angle: std::f64::consts::FRAC_PI_2,
+            canvas: (100.0, 50.0),
+        };
+        let transform = view.transform();
+
+        // The center goes to the middle of the canvas, the rest is rotated and zoomed around it
+        assert_eq!(transform.apply((10.0, 10.0)), (50.0, 25.0));
+        let (x, y) = transform.apply((11.0, 10.0));
+        assert!((x - 50.0).abs() < 1e-12 && (y - 27.0).abs() < 1e-12);
+    }
+}
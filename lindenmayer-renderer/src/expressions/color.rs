@@ -0,0 +1,171 @@
+pub type Rgba = (f64, f64, f64, f64);
+
+/// Red, green and blue in [0, 255], opaque
+pub fn rgb(r: f64, g: f64, b: f64) -> Rgba {
+    rgba(r, g, b, 1.0)
+}
+
+/// Red, green and blue in [0, 255], alpha in [0, 1]
+pub fn rgba(r: f64, g: f64, b: f64, a: f64) -> Rgba {
+    let channel = |v: f64| (v / 255.0).clamp(0.0, 1.0);
+
+    (channel(r), channel(g), channel(b), a.clamp(0.0, 1.0))
+}
+
+/// Hue in degrees, saturation and lightness in [0, 1]
+pub fn hsl(h: f64, s: f64, l: f64) -> Rgba {
+    let s = s.clamp(0.0, 1.0);
+    let l = l.clamp(0.0, 1.0);
+
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let (r, g, b) = hue_to_rgb(h, c);
+    let m = l - c / 2.0;
+
+    (r + m, g + m, b + m, 1.0)
+}
+
+/// Hue in degrees, saturation and value in [0, 1]
+pub fn hsv(h: f64, s: f64, v: f64) -> Rgba {
+    let s = s.clamp(0.0, 1.0);
+    let v = v.clamp(0.0, 1.0);
+
+    let c = v * s;
+    let (r, g, b) = hue_to_rgb(h, c);
+    let m = v - c;
+
+    (r + m, g + m, b + m, 1.0)
+}
+
+// RGB components of a hue with the given chroma, before adding the lightness
+fn hue_to_rgb(h: f64, c: f64) -> (f64, f64, f64) {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+
+    match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    }
+}
+
+/// Interpolates two colors in the OKLab color space.
+pub fn mix(c1: Rgba, c2: Rgba, t: f64) -> Rgba {
+    let t = t.clamp(0.0, 1.0);
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+    let (l1, a1, b1) = to_oklab(c1);
+    let (l2, a2, b2) = to_oklab(c2);
+    let (r, g, b) = from_oklab((lerp(l1, l2), lerp(a1, a2), lerp(b1, b2)));
+
+    (r, g, b, lerp(c1.3, c2.3))
+}
+
+/// Color of a palette, the index wraps around.
+pub fn palette(colors: &[Rgba], index: f64) -> Rgba {
+    if colors.is_empty() || !index.is_finite() {
+        return (0.0, 0.0, 0.0, 1.0);
+    }
+
+    let index = (index.floor() as i64).rem_euclid(colors.len() as i64);
+    colors[index as usize]
+}
+
+/// Color of the gradient through the colors of a palette, `t` goes from 0 to 1.
+pub fn gradient(colors: &[Rgba], t: f64) -> Rgba {
+    if colors.is_empty() || t.is_nan() {
+        return (0.0, 0.0, 0.0, 1.0);
+    }
+
+    let position = t.clamp(0.0, 1.0) * (colors.len() - 1) as f64;
+    let i = (position.floor() as usize).min(colors.len() - 1);
+    let next = (i + 1).min(colors.len() - 1);
+
+    mix(colors[i], colors[next], position - i as f64)
+}
+
+fn to_linear(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn to_oklab(color: Rgba) -> (f64, f64, f64) {
+    let r = to_linear(color.0);
+    let g = to_linear(color.1);
+    let b = to_linear(color.2);
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    (
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    )
+}
+
+fn from_oklab(lab: (f64, f64, f64)) -> (f64, f64, f64) {
+    let (l, a, b) = lab;
+
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    let r = 4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_;
+    let g = -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_;
+    let b = -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076219785 * s_;
+
+    (
+        from_linear(r).clamp(0.0, 1.0),
+        from_linear(g).clamp(0.0, 1.0),
+        from_linear(b).clamp(0.0, 1.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // OKLab conversions are not exact
+    fn assert_close(left: Rgba, right: Rgba) {
+        let channels = [left.0 - right.0, left.1 - right.1, left.2 - right.2, left.3 - right.3];
+        assert!(channels.iter().all(|d| d.abs() < 1e-6), "{left:?} != {right:?}");
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(rgba(10.0, 20.0, 30.0, 1.0), rgb(10.0, 20.0, 30.0));
+        assert_eq!(rgba(255.0, 0.0, 51.0, 0.2), (1.0, 0.0, 0.2, 0.2));
+        // Not rounded to 8 bits, but clamped
+        assert_eq!(rgba(127.5, -10.0, 300.0, 2.0), (0.5, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(hsl(120.0, 1.0, 0.5), (0.0, 1.0, 0.0, 1.0));
+        assert_eq!(hsv(240.0, 1.0, 1.0), (0.0, 0.0, 1.0, 1.0));
+
+        let black = rgb(0.0, 0.0, 0.0);
+        let white = rgb(255.0, 255.0, 255.0);
+        assert_close(mix(black, white, 0.0), black);
+        assert_close(mix(black, white, 1.0), white);
+
+        let colors = [(1.0, 0.0, 0.0, 1.0), (0.0, 0.0, 1.0, 1.0)];
+        assert_eq!(palette(&colors, 3.0), colors[1]);
+        assert_close(gradient(&colors, 1.0), colors[1]);
+    }
+}
@@ -0,0 +1,71 @@
+use meval::Expr;
+use std::collections::HashMap;
+
+/// Functions available in every expression.
+pub const BUILTIN_FUNCTIONS: &[&str] = &[
+    "sqrt", "exp", "ln", "abs", "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh",
+    "tanh", "asinh", "acosh", "atanh", "floor", "ceil", "round", "signum", "atan2", "max", "min",
+    "rand", "rgb", "rgba", "hsl", "hsv", "mix", "palette", "gradient",
+];
+
+/// Constants available in every expression.
+pub const BUILTIN_CONSTANTS: &[&str] = &["pi", "e"];
+
+/// Variables set by the renderer while drawing.
+pub const TURTLE_VARIABLES: &[&str] = &[
+    "INDEX", "LENGTH", "DEPTH", "POSX", "POSY", "POSZ", "FRAME", "TIME",
+];
+
+/// Maximum number of nested user function calls.
+pub const MAX_CALL_DEPTH: usize = 32;
+
+/// A function declared in the configuration, e.g.
+/// `fn wobble(x, k) = sin(x*k)/k`
+#[derive(Debug)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+/// Whether a name is already taken by a built-in or a turtle variable.
+pub fn is_reserved(name: &str) -> bool {
+    BUILTIN_FUNCTIONS.contains(&name)
+        || BUILTIN_CONSTANTS.contains(&name)
+        || TURTLE_VARIABLES.contains(&name)
+}
+
+/// Whether the function `name` can end up calling itself.
+/// Expressions are evaluated eagerly, so such a call would never terminate.
+pub fn is_recursive(functions: &HashMap<String, Function>, name: &str) -> bool {
+    let mut visited = vec![];
+    let mut to_visit = called_functions(functions, name);
+
+    while let Some(callee) = to_visit.pop() {
+        if callee == name {
+            return true;
+        }
+
+        if !visited.contains(&callee) {
+            to_visit.extend(called_functions(functions, callee));
+            visited.push(callee);
+        }
+    }
+
+    false
+}
+
+fn called_functions<'a>(functions: &'a HashMap<String, Function>, name: &str) -> Vec<&'a str> {
+    let Some(function) = functions.get(name) else {
+        return vec![];
+    };
+
+    function
+        .body
+        .iter()
+        .filter_map(|token| match token {
+            meval::tokenizer::Token::Func(callee, _) => functions.get_key_value(callee.as_str()),
+            _ => None,
+        })
+        .map(|(callee, _)| callee.as_str())
+        .collect()
+}
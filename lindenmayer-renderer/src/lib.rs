@@ -1,4 +1,11 @@
-use crate::canvas::ExprContext;
+use crate::animation::Track;
+use crate::budget::{Budget, RenderStatus};
+use crate::camera::{Camera, Vector};
+use crate::canvas::{ExprContext, LineCap, LineJoin, RenderError, TextAnchor};
+use crate::curves::Curves;
+use crate::display_list::{Autofit, DisplayList, StrokeStyle, Viewport};
+use crate::functions::Function;
+use crate::program::Program;
 pub use lindenmayer_engine::*;
 pub use meval;
 pub use rand_pcg::Pcg64;
@@ -6,47 +13,120 @@ use meval::*;
 use std::{rc::Rc, cell::RefCell};
 use std::collections::HashMap;
 
+pub mod animation;
+pub mod backend;
+pub mod budget;
+pub mod camera;
 pub mod canvas;
+pub mod curves;
+pub mod display_list;
+pub mod functions;
+pub mod program;
+pub mod recording;
+pub mod validation;
 pub(crate) mod expressions;
+pub(crate) mod turtle;
 
+// The expressions are parsed as `Expr` and then compiled
+// by the `Program`, which also replaces variable names with slots.
 #[derive(Debug)]
-pub enum Operation {
-    Forward(Expr),
-    Jump(Expr),
-    Dot(Expr),
-    Rotate(Expr),
-    Thickness(Expr),
-    Ignore(Expr),
+pub enum Operation<E = Expr, V = String> {
+    Forward(E),
+    Jump(E),
+    Dot(E),
+    Rotate(E),
+    // Rotations of the 3D turtle around its left and heading vectors
+    Pitch(E),
+    Roll(E),
+    Thickness(E),
+    // Susceptibility to the tropism of the current branch
+    Tropism(E),
+    // Stroke style of the following lines, restored by pop
+    LineCap(LineCap),
+    LineJoin(LineJoin),
+    MiterLimit(E),
+    Dash(Vec<E>),
+    Ignore(E),
     PushStack,
     PopStack,
-    SetColor(Color),
-    SetVar(String, Expr),
+    // Polygon filled with the current color, through the recorded vertices
+    BeginPolygon,
+    Vertex,
+    EndPolygon,
+    SetColor(Color<E>),
+    // Text drawn at the position of the turtle
+    Text(Label<E>),
+    SetVar(V, E),
+    // Executes the first list if the condition is not zero,
+    // the second one otherwise
+    If(E, Vec<Operation<E, V>>, Vec<Operation<E, V>>),
+    Repeat(E, Vec<Operation<E, V>>),
 }
 
+/// Bends the heading towards `direction` after every forward move,
+/// by an angle proportional to the susceptibility, as in ABOP.
 #[derive(Debug)]
-pub enum Color {
+pub struct Tropism<E = Expr> {
+    pub direction: Vector,
+    // Initial susceptibility, evaluated at every frame
+    pub susceptibility: E,
+}
+
+/// Stack errors stop the rendering instead of being ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Strict {
+    // Maximum number of states saved by push, unlimited if none
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Label<E = Expr> {
+    pub parts: Vec<LabelPart<E>>,
+    pub size: E,
+    pub anchor: TextAnchor,
+    // Whether the text is rotated along the heading of the turtle
+    pub follow_heading: bool,
+}
+
+#[derive(Debug)]
+pub enum LabelPart<E = Expr> {
+    Literal(String),
+    // The symbol being executed
+    Symbol,
+    // Value of an expression, with the given number of decimals
+    Value(E, usize),
+}
+
+#[derive(Debug)]
+pub enum Color<E = Expr> {
     Static((f64, f64, f64, f64)),
-    Dynamic(Expr),
+    Dynamic(E),
 }
 
-impl Color {
-    pub fn get_color<'a>(&self, variables: &'a ExprContext) -> Result<(f64, f64, f64, f64), meval::Error> {
-        match self {
-            Color::Static(v) => Ok(*v),
-            Color::Dynamic(v) => {
-                let color = v.eval_with_context(&variables)?;
-                let color = color as u64;
-                
-                let r = (color & 0xFF) as f64;
-                let g = ((color >> 8) & 0xFF) as f64;
-                let b = ((color >> 16) & 0xFF) as f64;
-
-                Ok((r / 255.0, g / 255.0, b / 255.0, 1.0))
-            },
-        }
-    }
+/// Named list of colors, usable in expressions
+/// through `palette(name, index)` and `gradient(name, t)`.
+pub type Palette = Vec<(f64, f64, f64, f64)>;
+
+// Fields read while drawing instead of being compiled by the program,
+// they can be changed without calling any `update_*` method
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    initial_pos: (f64, f64),
+    initial_rot: f64,
+    initial_thickness: f64,
+    background_color: (f64, f64, f64, f64),
+    initial_color: (f64, f64, f64, f64),
+    initial_stroke: StrokeStyle,
+    canvas: (i32, i32),
+    autofit: Option<Autofit>,
+    curves: Option<Curves>,
+    strict: Option<Strict>,
 }
 
+// Display list of a frame, with the settings
+// and the inputs of the program it was drawn with
+type CachedFrame = (Settings, Vec<Option<f64>>, Rc<DisplayList>);
+
 #[derive(Debug)]
 pub struct LSystemRenderer {
     pub lsystem: LSystem,
@@ -56,14 +136,33 @@ pub struct LSystemRenderer {
     pub initial_thickness: f64,
     pub background_color: (f64, f64, f64, f64),
     pub initial_color: (f64, f64, f64, f64),
+    pub initial_stroke: StrokeStyle,
     pub canvas: (i32, i32),
     pub seed: String,
     pub injections: Vec<(u32, String)>,
-    pub variables: HashMap<String, f64>,
+    /// Initial values, which can use the functions and the other variables.
+    ///
+    /// They used to be plain `f64`: a constant is now written as a parsed
+    /// expression, e.g. `"3".parse::<Expr>()`, and evaluated at every frame.
+    pub variables: HashMap<String, Expr>,
+    pub animations: HashMap<String, Track>,
+    pub functions: HashMap<String, Function>,
+    pub palettes: HashMap<String, Palette>,
     pub operations: HashMap<char, Vec<Operation>>,
+    // Source text of the operations, each one followed by the ones of its blocks,
+    // shown in the errors
+    pub sources: HashMap<char, Vec<String>>,
+    pub camera: Camera,
+    pub tropism: Option<Tropism>,
+    pub viewport: Option<Viewport>,
+    pub autofit: Option<Autofit>,
+    pub curves: Option<Curves>,
+    pub strict: Option<Strict>,
     // Cache for re-use
     pub expression: String,
+    pub program: Program,
     pub rng: Rc<RefCell<Pcg64>>,
+    cached_frame: RefCell<Option<CachedFrame>>,
 }
 
 impl Default for LSystemRenderer {
@@ -77,13 +176,25 @@ impl Default for LSystemRenderer {
         let initial_thickness = 1f64;
         let background_color = (1f64, 1f64, 1f64, 1f64);
         let initial_color = (0f64, 0f64, 0f64, 1f64);
+        let initial_stroke = StrokeStyle::default();
         let canvas = (750, 750);
         let seed = "Default Seed".to_string();
         let injections = vec![];
         let variables = HashMap::new();
+        let animations = HashMap::new();
+        let functions = HashMap::new();
+        let palettes = HashMap::new();
         let operations = HashMap::new();
+        let sources = HashMap::new();
+        let camera = Camera::default();
+        let tropism = None;
+        let viewport = None;
+        let autofit = None;
+        let curves = None;
+        let strict = None;
 
         let expression = String::from(""); // Nothing to render
+        let program = Program::default();
         let rng = expressions::get_rng(&seed);
         let rng = Rc::new(RefCell::new(rng));
 
@@ -95,37 +206,149 @@ impl Default for LSystemRenderer {
             initial_thickness,
             background_color,
             initial_color,
+            initial_stroke,
             canvas,
             seed,
             injections,
             variables,
+            animations,
+            functions,
+            palettes,
             operations,
+            sources,
+            camera,
+            tropism,
+            viewport,
+            autofit,
+            curves,
+            strict,
             expression,
+            program,
             rng,
+            cached_frame: RefCell::default(),
+        }
+    }
+}
+
+/// Inputs of [`LSystemRenderer::new`], which expands the L-system and compiles the operations.
+/// Fields left out with `..Default::default()` keep the values of [`LSystemRenderer::default`].
+#[derive(Debug)]
+pub struct RendererConfig {
+    pub lsystem: LSystem,
+    pub iter: usize,
+    pub initial_pos: (f64, f64),
+    pub initial_rot: f64,
+    pub initial_thickness: f64,
+    pub background_color: (f64, f64, f64, f64),
+    pub initial_color: (f64, f64, f64, f64),
+    pub initial_stroke: StrokeStyle,
+    pub canvas: (i32, i32),
+    pub seed: String,
+    pub injections: Vec<(u32, String)>,
+    pub variables: HashMap<String, Expr>,
+    pub animations: HashMap<String, Track>,
+    pub functions: HashMap<String, Function>,
+    pub palettes: HashMap<String, Palette>,
+    pub operations: HashMap<char, Vec<Operation>>,
+    pub sources: HashMap<char, Vec<String>>,
+    pub camera: Camera,
+    pub tropism: Option<Tropism>,
+    pub viewport: Option<Viewport>,
+    pub autofit: Option<Autofit>,
+    pub curves: Option<Curves>,
+    pub strict: Option<Strict>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        let LSystemRenderer {
+            lsystem,
+            iter,
+            initial_pos,
+            initial_rot,
+            initial_thickness,
+            background_color,
+            initial_color,
+            initial_stroke,
+            canvas,
+            seed,
+            injections,
+            variables,
+            animations,
+            functions,
+            palettes,
+            operations,
+            sources,
+            camera,
+            tropism,
+            viewport,
+            autofit,
+            curves,
+            strict,
+            ..
+        } = LSystemRenderer::default();
+
+        RendererConfig {
+            lsystem,
+            iter,
+            initial_pos,
+            initial_rot,
+            initial_thickness,
+            background_color,
+            initial_color,
+            initial_stroke,
+            canvas,
+            seed,
+            injections,
+            variables,
+            animations,
+            functions,
+            palettes,
+            operations,
+            sources,
+            camera,
+            tropism,
+            viewport,
+            autofit,
+            curves,
+            strict,
         }
     }
 }
 
 impl LSystemRenderer {
-    pub fn new(
-        lsystem: LSystem,
-        iter: usize,
-        initial_pos: (f64, f64),
-        initial_rot: f64,
-        initial_thickness: f64,
-        background_color: (f64, f64, f64, f64),
-        initial_color: (f64, f64, f64, f64),
-        canvas: (i32, i32),
-        seed: String,
-        injections: Vec<(u32, String)>,
-        variables: HashMap<String, f64>,
-        operations: HashMap<char, Vec<Operation>>,
-    ) -> Self {
+    pub fn new(config: RendererConfig) -> Self {
+        let RendererConfig {
+            lsystem,
+            iter,
+            initial_pos,
+            initial_rot,
+            initial_thickness,
+            background_color,
+            initial_color,
+            initial_stroke,
+            canvas,
+            seed,
+            injections,
+            variables,
+            animations,
+            functions,
+            palettes,
+            operations,
+            sources,
+            camera,
+            tropism,
+            viewport,
+            autofit,
+            curves,
+            strict,
+        } = config;
         let expression = lsystem.expand(iter);
+        let program = Program::default();
         let rng = expressions::get_rng(&seed);
         let rng = Rc::new(RefCell::new(rng));
 
-        Self {
+        let mut renderer = Self {
             lsystem,
             iter,
             initial_pos,
@@ -133,14 +356,30 @@ impl LSystemRenderer {
             initial_thickness,
             background_color,
             initial_color,
+            initial_stroke,
             canvas,
             seed,
             injections,
             variables,
+            animations,
+            functions,
+            palettes,
             operations,
+            sources,
+            camera,
+            tropism,
+            viewport,
+            autofit,
+            curves,
+            strict,
             expression,
+            program,
             rng,
-        }
+            cached_frame: RefCell::default(),
+        };
+        renderer.update_program();
+
+        renderer
     }
 
     pub fn update_expr(&mut self) {
@@ -159,9 +398,9 @@ impl LSystemRenderer {
 
         // Iterate over the expression and injections simultaneously
         let mut expr_index = 0;
-        let mut injection_iter = self.injections.iter().filter(|injection| injection.0 <= expression.len() as u32);
+        let injection_iter = self.injections.iter().filter(|injection| injection.0 <= expression.len() as u32);
 
-        while let Some((injection_index, injection_value)) = injection_iter.next() {
+        for (injection_index, injection_value) in injection_iter {
             // Append the characters from the expression until the injection index
             injected_expression.push_str(&expression[expr_index..(*injection_index as usize)]);
 
@@ -176,9 +415,135 @@ impl LSystemRenderer {
         injected_expression.push_str(&expression[expr_index..]);
 
         self.expression = injected_expression;
+        self.cached_frame.take();
+    }
+
+    /// Compiles the expressions of the operations and functions.
+    /// Changes to the variables, palettes, camera, tropism
+    /// and viewport are only drawn after calling it.
+    pub fn update_program(&mut self) {
+        self.program = Program::compile(self);
+        self.cached_frame.take();
     }
 
     pub fn update_rng(&mut self) {
         *self.rng.borrow_mut() = expressions::get_rng(&self.seed);
+        self.cached_frame.take();
+    }
+
+    /// Geometry of the frame described by the context.
+    /// The last one is re-used if neither the settings nor a value read by the program have changed,
+    /// it is discarded by `update_expr`, `update_program` and `update_rng`.
+    pub fn display_list(&self, variables: &mut ExprContext) -> Result<Rc<DisplayList>, RenderError> {
+        let (display_list, _) = self.display_list_within(variables, &Budget::default())?;
+        Ok(display_list)
+    }
+
+    /// Geometry of the frame, drawn until the budget runs out.
+    /// Partial frames are not cached.
+    pub fn display_list_within(
+        &self,
+        variables: &mut ExprContext,
+        budget: &Budget,
+    ) -> Result<(Rc<DisplayList>, RenderStatus), RenderError> {
+        // Animated variables are computed from FRAME and TIME
+        self.animate(variables);
+
+        let settings = self.settings();
+        let inputs = self.program.inputs(&*variables);
+        if let (Some(inputs), Some((cached_settings, cached_inputs, display_list))) =
+            (&inputs, &*self.cached_frame.borrow())
+        {
+            if settings == *cached_settings && inputs == cached_inputs {
+                return Ok((display_list.clone(), RenderStatus::Complete));
+            }
+        }
+
+        let (mut display_list, status) = turtle::interpret(self, variables, budget)?;
+        if let Some(autofit) = &self.autofit {
+            let canvas = (self.canvas.0 as f64, self.canvas.1 as f64);
+            display_list.fit(canvas, autofit);
+        }
+
+        let display_list = Rc::new(display_list);
+        if let (Some(inputs), RenderStatus::Complete) = (inputs, status) {
+            *self.cached_frame.borrow_mut() = Some((settings, inputs, display_list.clone()));
+        }
+
+        Ok((display_list, status))
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            initial_pos: self.initial_pos,
+            initial_rot: self.initial_rot,
+            initial_thickness: self.initial_thickness,
+            background_color: self.background_color,
+            initial_color: self.initial_color,
+            initial_stroke: self.initial_stroke.clone(),
+            canvas: self.canvas,
+            autofit: self.autofit,
+            curves: self.curves,
+            strict: self.strict,
+        }
+    }
+
+    /// Sets the value of every animated variable
+    /// according to the FRAME and TIME of the context.
+    pub fn animate(&self, variables: &mut ExprContext) {
+        let frame = variables.get_var("FRAME").unwrap_or(0.0);
+        let time = variables.get_var("TIME").unwrap_or(0.0);
+
+        for (key, track) in self.animations.iter() {
+            variables.var(key, track.value_at(frame, time));
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_frame() {
+        let mut fractal = LSystemRenderer {
+            lsystem: LSystem::new("F", &[]),
+            operations: [('F', vec![Operation::Forward("10".parse().unwrap())])].into(),
+            ..Default::default()
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        let mut variables = ExprContext::new();
+        let first = fractal.display_list(&mut variables).unwrap();
+        let second = fractal.display_list(&mut variables).unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+
+        // Settings changed without any update are drawn
+        fractal.background_color = (0.0, 0.0, 0.0, 1.0);
+        let third = fractal.display_list(&mut variables).unwrap();
+        assert!(!Rc::ptr_eq(&second, &third));
+        assert_ne!(second.primitives[0], third.primitives[0]);
+
+        fractal.canvas = (100, 100);
+        let fourth = fractal.display_list(&mut variables).unwrap();
+        assert_ne!(third.primitives[0], fourth.primitives[0]);
+    }
+
+    #[test]
+    fn new() {
+        let fractal = LSystemRenderer::new(RendererConfig {
+            lsystem: LSystem::new("F", &[('F', "FF".to_string())]),
+            iter: 2,
+            operations: [('F', vec![Operation::Forward("size".parse().unwrap())])].into(),
+            variables: [("size".to_string(), "3".parse().unwrap())].into(),
+            ..Default::default()
+        });
+        assert_eq!(fractal.expression, "FFFF");
+        assert_eq!(fractal.canvas, LSystemRenderer::default().canvas);
+
+        let mut variables = ExprContext::new();
+        let list = fractal.display_list(&mut variables).unwrap();
+        assert!(!list.primitives.is_empty());
+    }
+}
@@ -0,0 +1,1039 @@
+use crate::expressions;
+use crate::expressions::color::{self, Rgba};
+use crate::functions::{Function, MAX_CALL_DEPTH};
+use crate::camera::Camera;
+use crate::display_list::Viewport;
+use crate::{Color, Label, LabelPart, LSystemRenderer, Operation, Tropism};
+use meval::tokenizer::{Operation as BinOp, Token};
+use meval::{ContextProvider, Expr, FuncEvalError};
+use rand_pcg::Pcg64;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+
+// Slots of the variables set by the renderer
+pub const INDEX: usize = 0;
+pub const LENGTH: usize = 1;
+pub const DEPTH: usize = 2;
+pub const POSX: usize = 3;
+pub const POSY: usize = 4;
+pub const FRAME: usize = 5;
+pub const TIME: usize = 6;
+pub const POSZ: usize = 7;
+
+const FIXED_SLOTS: [&str; 8] = [
+    "INDEX", "LENGTH", "DEPTH", "POSX", "POSY", "FRAME", "TIME", "POSZ",
+];
+
+pub type CompiledOperation = Operation<Compiled, usize>;
+
+/// Configuration with every expression compiled.
+/// Variables are stored in slots and referenced by index.
+#[derive(Debug, Default)]
+pub struct Program {
+    // Name of each slot
+    slots: Vec<String>,
+    slot_index: HashMap<String, usize>,
+    // Slots saved and restored by push and pop
+    tracked: Vec<usize>,
+    functions: Vec<CompiledFunction>,
+    function_index: HashMap<String, usize>,
+    // Names of the functions of the external context
+    externals: Vec<String>,
+    palettes: Vec<Vec<Rgba>>,
+    palette_index: HashMap<String, usize>,
+    // Errors raised by the `Fail` instruction
+    errors: Vec<EvalError>,
+    // Slots read by the code
+    loaded: BTreeSet<usize>,
+    // Whether the code calls `rand` or functions of the context
+    impure: bool,
+    // Initial values of the declared variables, in the order they are evaluated
+    pub variables: Vec<(usize, Compiled)>,
+    pub operations: HashMap<char, Vec<CompiledOperation>>,
+    pub camera: Camera<Compiled>,
+    pub tropism: Option<Tropism<Compiled>>,
+    pub viewport: Option<Viewport<Compiled>>,
+}
+
+/// Bytecode of an expression, which leaves a number on the stack,
+/// or the four channels of a color when compiled by [`Program::compile_color`].
+#[derive(Debug, Clone, Default)]
+pub struct Compiled {
+    code: Box<[Instr]>,
+}
+
+// Never evaluated, since there is no projection
+impl Default for Camera<Compiled> {
+    fn default() -> Self {
+        Self {
+            projection: None,
+            yaw: Compiled::default(),
+            pitch: Compiled::default(),
+            distance: Compiled::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CompiledFunction {
+    name: String,
+    arity: usize,
+    code: Compiled,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Instr {
+    Const(f64),
+    Load(usize),
+    // Argument of the current function call
+    Arg(usize),
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Func1(fn(f64) -> f64),
+    Func2(fn(f64, f64) -> f64),
+    FuncN(fn(&[f64]) -> f64, usize),
+    Rand,
+    // Pops the arguments and pushes the four channels of a color
+    Color(fn(&[f64]) -> Rgba, usize),
+    // Pops two colors and the position between them
+    Mix,
+    Palette,
+    Gradient,
+    // User function
+    Call(usize),
+    // Function of the external context: name and arguments count
+    External(usize, usize),
+    // Error raised after evaluating the arguments
+    Fail(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    Func1(fn(f64) -> f64),
+    Func2(fn(f64, f64) -> f64),
+    // At least one argument
+    FuncN(fn(&[f64]) -> f64),
+    Rand,
+    // Color from numbers, with the number of arguments
+    Color(fn(&[f64]) -> Rgba, usize),
+    // Two colors and a position
+    Mix,
+    // Palette index and position
+    Palette,
+    Gradient,
+}
+
+// Same built-ins as meval::Context::new(), plus rand and the color functions
+fn builtin(name: &str) -> Option<Builtin> {
+    use Builtin::*;
+
+    let builtin = match name {
+        "sqrt" => Func1(f64::sqrt),
+        "exp" => Func1(f64::exp),
+        "ln" => Func1(f64::ln),
+        "abs" => Func1(f64::abs),
+        "sin" => Func1(f64::sin),
+        "cos" => Func1(f64::cos),
+        "tan" => Func1(f64::tan),
+        "asin" => Func1(f64::asin),
+        "acos" => Func1(f64::acos),
+        "atan" => Func1(f64::atan),
+        "sinh" => Func1(f64::sinh),
+        "cosh" => Func1(f64::cosh),
+        "tanh" => Func1(f64::tanh),
+        "asinh" => Func1(f64::asinh),
+        "acosh" => Func1(f64::acosh),
+        "atanh" => Func1(f64::atanh),
+        "floor" => Func1(f64::floor),
+        "ceil" => Func1(f64::ceil),
+        "round" => Func1(f64::round),
+        "signum" => Func1(f64::signum),
+        "atan2" => Func2(f64::atan2),
+        "max" => FuncN(|xs| xs.iter().fold(f64::NEG_INFINITY, |m, &x| m.max(x))),
+        "min" => FuncN(|xs| xs.iter().fold(f64::INFINITY, |m, &x| m.min(x))),
+        "rgb" => Color(|xs| color::rgb(xs[0], xs[1], xs[2]), 3),
+        "rgba" => Color(|xs| color::rgba(xs[0], xs[1], xs[2], xs[3]), 4),
+        "hsl" => Color(|xs| color::hsl(xs[0], xs[1], xs[2]), 3),
+        "hsv" => Color(|xs| color::hsv(xs[0], xs[1], xs[2]), 3),
+        "mix" => Mix,
+        "palette" => Palette,
+        "gradient" => Gradient,
+        "rand" => Rand,
+        _ => return None,
+    };
+
+    Some(builtin)
+}
+
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" => Some(std::f64::consts::PI),
+        "e" => Some(std::f64::consts::E),
+        _ => None,
+    }
+}
+
+// Expression tree used for constant folding
+enum Node {
+    Const(f64),
+    Load(usize),
+    Arg(usize),
+    Neg(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    Builtin(Builtin, Vec<Node>),
+    // Built-in returning a color, with its name
+    Color(String, Builtin, Vec<Node>),
+    Call(usize, Vec<Node>),
+    External(usize, Vec<Node>),
+    Fail(usize, Vec<Node>),
+}
+
+impl Program {
+    pub fn compile(fractal: &LSystemRenderer) -> Self {
+        let mut program = Program::default();
+
+        for name in FIXED_SLOTS {
+            program.slot(name);
+        }
+
+        // Palette names evaluate to the index of the palette
+        let mut palettes: Vec<&String> = fractal.palettes.keys().collect();
+        palettes.sort();
+        for name in palettes {
+            program.palette_index.insert(name.to_string(), program.palettes.len());
+            program.palettes.push(fractal.palettes[name].clone());
+        }
+
+        // Declared and animated variables are restored by pop
+        let mut tracked: Vec<&String> = fractal
+            .variables
+            .keys()
+            .chain(fractal.animations.keys())
+            .collect();
+        tracked.sort();
+        tracked.dedup();
+        program.tracked = tracked.into_iter().map(|name| program.slot(name)).collect();
+
+        // Variables assigned by operations shadow the constants
+        for operations in fractal.operations.values() {
+            program.declare_assigned(operations);
+        }
+
+        // Functions are indexed first so that they can call each other
+        let mut names: Vec<&String> = fractal.functions.keys().collect();
+        names.sort();
+        for name in &names {
+            let index = program.functions.len();
+            program.function_index.insert(name.to_string(), index);
+            program.functions.push(CompiledFunction {
+                name: name.to_string(),
+                arity: fractal.functions[*name].params.len(),
+                code: Compiled::default(),
+            });
+        }
+        for (index, name) in names.iter().enumerate() {
+            let function = &fractal.functions[*name];
+            program.functions[index].code = program.compile_function(function);
+        }
+
+        // Declared variables are evaluated after the other ones they use
+        let mut pending: Vec<&String> = fractal.variables.keys().collect();
+        pending.sort();
+        while !pending.is_empty() {
+            let uses_pending = |name: &String| {
+                fractal.variables[name].iter().any(|token| {
+                    matches!(token, Token::Var(var) if var != name && pending.contains(&var))
+                })
+            };
+
+            // Within a cycle, the first one fails as an unknown variable
+            let next = pending.iter().position(|name| !uses_pending(name)).unwrap_or(0);
+            let name = pending.remove(next);
+            let slot = program.slot(name);
+            let value = program.compile_expr(&fractal.variables[name]);
+            program.variables.push((slot, value));
+        }
+
+        for (c, operations) in fractal.operations.iter() {
+            let operations = program.compile_operations(operations);
+            program.operations.insert(*c, operations);
+        }
+
+        program.camera = Camera {
+            projection: fractal.camera.projection,
+            yaw: program.compile_expr(&fractal.camera.yaw),
+            pitch: program.compile_expr(&fractal.camera.pitch),
+            distance: program.compile_expr(&fractal.camera.distance),
+        };
+
+        program.tropism = fractal.tropism.as_ref().map(|tropism| Tropism {
+            direction: tropism.direction,
+            susceptibility: program.compile_expr(&tropism.susceptibility),
+        });
+
+        program.viewport = fractal.viewport.as_ref().map(|viewport| Viewport {
+            zoom: program.compile_expr(&viewport.zoom),
+            center: (
+                program.compile_expr(&viewport.center.0),
+                program.compile_expr(&viewport.center.1),
+            ),
+            angle: program.compile_expr(&viewport.angle),
+        });
+
+        program
+    }
+
+    /// Slot of a variable, created if it does not exist.
+    pub fn slot(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.slot_index.get(name) {
+            return *slot;
+        }
+
+        let slot = self.slots.len();
+        self.slots.push(name.to_string());
+        self.slot_index.insert(name.to_string(), slot);
+        slot
+    }
+
+    pub fn slot_of(&self, name: &str) -> Option<usize> {
+        self.slot_index.get(name).copied()
+    }
+
+    pub fn slot_names(&self) -> &[String] {
+        &self.slots
+    }
+
+    pub fn tracked(&self) -> &[usize] {
+        &self.tracked
+    }
+
+    /// Values of the context which can change the result of the program,
+    /// `None` if the result can change even when they are the same.
+    pub fn inputs(&self, context: &dyn ContextProvider) -> Option<Vec<Option<f64>>> {
+        if self.impure {
+            return None;
+        }
+
+        // The position variables are always set by the turtle
+        let inputs = self
+            .loaded
+            .iter()
+            .filter(|slot| !matches!(**slot, INDEX..=POSY | POSZ))
+            .map(|slot| context.get_var(&self.slots[*slot]))
+            .collect();
+
+        Some(inputs)
+    }
+
+    fn declare_assigned(&mut self, operations: &[Operation]) {
+        for operation in operations {
+            match operation {
+                Operation::SetVar(name, _) => {
+                    self.slot(name);
+                }
+                Operation::If(_, then_branch, else_branch) => {
+                    self.declare_assigned(then_branch);
+                    self.declare_assigned(else_branch);
+                }
+                Operation::Repeat(_, body) => self.declare_assigned(body),
+                _ => {}
+            }
+        }
+    }
+
+    fn compile_operations(&mut self, operations: &[Operation]) -> Vec<CompiledOperation> {
+        operations
+            .iter()
+            .map(|op| self.compile_operation(op))
+            .collect()
+    }
+
+    fn compile_operation(&mut self, operation: &Operation) -> CompiledOperation {
+        match operation {
+            Operation::Forward(expr) => Operation::Forward(self.compile_expr(expr)),
+            Operation::Jump(expr) => Operation::Jump(self.compile_expr(expr)),
+            Operation::Dot(expr) => Operation::Dot(self.compile_expr(expr)),
+            Operation::Rotate(expr) => Operation::Rotate(self.compile_expr(expr)),
+            Operation::Pitch(expr) => Operation::Pitch(self.compile_expr(expr)),
+            Operation::Roll(expr) => Operation::Roll(self.compile_expr(expr)),
+            Operation::Thickness(expr) => Operation::Thickness(self.compile_expr(expr)),
+            Operation::Tropism(expr) => Operation::Tropism(self.compile_expr(expr)),
+            Operation::LineCap(cap) => Operation::LineCap(*cap),
+            Operation::LineJoin(join) => Operation::LineJoin(*join),
+            Operation::MiterLimit(expr) => Operation::MiterLimit(self.compile_expr(expr)),
+            Operation::Dash(dashes) => {
+                Operation::Dash(dashes.iter().map(|expr| self.compile_expr(expr)).collect())
+            }
+            Operation::Ignore(expr) => Operation::Ignore(self.compile_expr(expr)),
+            Operation::PushStack => Operation::PushStack,
+            Operation::PopStack => Operation::PopStack,
+            Operation::BeginPolygon => Operation::BeginPolygon,
+            Operation::Vertex => Operation::Vertex,
+            Operation::EndPolygon => Operation::EndPolygon,
+            Operation::SetColor(Color::Static(color)) => Operation::SetColor(Color::Static(*color)),
+            Operation::SetColor(Color::Dynamic(expr)) => {
+                Operation::SetColor(Color::Dynamic(self.compile_color(expr)))
+            }
+            Operation::Text(label) => Operation::Text(Label {
+                parts: label
+                    .parts
+                    .iter()
+                    .map(|part| match part {
+                        LabelPart::Literal(text) => LabelPart::Literal(text.clone()),
+                        LabelPart::Symbol => LabelPart::Symbol,
+                        LabelPart::Value(expr, decimals) => {
+                            LabelPart::Value(self.compile_expr(expr), *decimals)
+                        }
+                    })
+                    .collect(),
+                size: self.compile_expr(&label.size),
+                anchor: label.anchor,
+                follow_heading: label.follow_heading,
+            }),
+            Operation::SetVar(name, expr) => {
+                let expr = self.compile_expr(expr);
+                Operation::SetVar(self.slot(name), expr)
+            }
+            Operation::If(condition, then_branch, else_branch) => Operation::If(
+                self.compile_expr(condition),
+                self.compile_operations(then_branch),
+                self.compile_operations(else_branch),
+            ),
+            Operation::Repeat(count, body) => {
+                Operation::Repeat(self.compile_expr(count), self.compile_operations(body))
+            }
+        }
+    }
+
+    /// Compiles an expression of the configuration.
+    pub fn compile_expr(&mut self, expr: &Expr) -> Compiled {
+        self.compile_with_params(expr, &[], false)
+    }
+
+    /// Compiles an expression returning a color, evaluated by [`Machine::eval_color`].
+    pub fn compile_color(&mut self, expr: &Expr) -> Compiled {
+        self.compile_with_params(expr, &[], true)
+    }
+
+    fn compile_function(&mut self, function: &Function) -> Compiled {
+        self.compile_with_params(&function.body, &function.params, false)
+    }
+
+    fn compile_with_params(&mut self, expr: &Expr, params: &[String], color: bool) -> Compiled {
+        let mut stack: Vec<Node> = vec![];
+
+        for token in expr.iter() {
+            let node = match token {
+                Token::Number(v) => Node::Const(*v),
+                Token::Var(name) => {
+                    if let Some(i) = params.iter().position(|param| param == name) {
+                        Node::Arg(i)
+                    } else if let Some(palette) = self.palette_index.get(name) {
+                        Node::Const(*palette as f64)
+                    } else if let Some(slot) = self.slot_of(name) {
+                        Node::Load(slot)
+                    } else if let Some(v) = constant(name) {
+                        Node::Const(v)
+                    } else {
+                        // Only known at runtime, e.g. set by a context
+                        Node::Load(self.slot(name))
+                    }
+                }
+                Token::Unary(op) => {
+                    let x = pop(&mut stack);
+                    match op {
+                        BinOp::Minus => Node::Neg(Box::new(x)),
+                        _ => x,
+                    }
+                }
+                Token::Binary(op) => {
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+                    Node::Binary(*op, Box::new(left), Box::new(right))
+                }
+                Token::Func(name, n) => {
+                    let n = n.unwrap_or(0);
+                    let args = stack.split_off(stack.len().saturating_sub(n));
+                    self.function_node(name, args)
+                }
+                // Not present in RPN
+                Token::LParen | Token::RParen | Token::Comma => continue,
+            };
+
+            stack.push(fold(node));
+        }
+
+        let mut code = vec![];
+        if let Some(node) = stack.pop() {
+            match check(&node, color) {
+                Ok(()) => emit(&node, &mut code),
+                Err(error) => {
+                    code.push(Instr::Fail(self.errors.len()));
+                    self.errors.push(error);
+                }
+            }
+        }
+
+        for instr in &code {
+            match instr {
+                Instr::Load(slot) => {
+                    self.loaded.insert(*slot);
+                }
+                Instr::Rand | Instr::External(..) => self.impure = true,
+                _ => {}
+            }
+        }
+
+        Compiled {
+            code: code.into_boxed_slice(),
+        }
+    }
+
+    fn function_node(&mut self, name: &str, args: Vec<Node>) -> Node {
+        if let Some(index) = self.function_index.get(name) {
+            let arity = self.functions[*index].arity;
+
+            return if arity == args.len() {
+                Node::Call(*index, args)
+            } else {
+                self.fail(name, FuncEvalError::NumberArgs(arity), args)
+            };
+        }
+
+        let Some(builtin) = builtin(name) else {
+            let index = self.externals.len();
+            self.externals.push(name.to_string());
+            return Node::External(index, args);
+        };
+
+        let expected = match builtin {
+            Builtin::Func1(_) => 1,
+            Builtin::Func2(_) | Builtin::Rand | Builtin::Palette | Builtin::Gradient => 2,
+            Builtin::Mix => 3,
+            Builtin::Color(_, arity) => arity,
+            Builtin::FuncN(_) if args.is_empty() => {
+                return self.fail(name, FuncEvalError::TooFewArguments, args)
+            }
+            Builtin::FuncN(_) => args.len(),
+        };
+
+        if expected != args.len() {
+            return self.fail(name, FuncEvalError::NumberArgs(expected), args);
+        }
+
+        match builtin {
+            Builtin::Color(..) | Builtin::Mix | Builtin::Palette | Builtin::Gradient => {
+                Node::Color(name.to_string(), builtin, args)
+            }
+            _ => Node::Builtin(builtin, args),
+        }
+    }
+
+    fn fail(&mut self, name: &str, error: FuncEvalError, args: Vec<Node>) -> Node {
+        let index = self.errors.len();
+        self.errors.push(meval::Error::Function(name.to_string(), error).into());
+        Node::Fail(index, args)
+    }
+}
+
+fn pop(stack: &mut Vec<Node>) -> Node {
+    // meval only builds valid RPN
+    stack.pop().unwrap_or(Node::Const(f64::NAN))
+}
+
+fn binary(op: BinOp, left: f64, right: f64) -> f64 {
+    match op {
+        BinOp::Plus => left + right,
+        BinOp::Minus => left - right,
+        BinOp::Times => left * right,
+        BinOp::Div => left / right,
+        BinOp::Rem => left % right,
+        BinOp::Pow => left.powf(right),
+    }
+}
+
+fn constants(args: &[Node]) -> Option<Vec<f64>> {
+    args.iter()
+        .map(|arg| match arg {
+            Node::Const(v) => Some(*v),
+            _ => None,
+        })
+        .collect()
+}
+
+// Precomputes the nodes whose operands are all constant
+fn fold(node: Node) -> Node {
+    match node {
+        Node::Neg(x) => match *x {
+            Node::Const(v) => Node::Const(-v),
+            x => Node::Neg(Box::new(x)),
+        },
+        Node::Binary(op, left, right) => match (&*left, &*right) {
+            (Node::Const(l), Node::Const(r)) => Node::Const(binary(op, *l, *r)),
+            _ => Node::Binary(op, left, right),
+        },
+        Node::Builtin(builtin, args) => {
+            let Some(values) = constants(&args) else {
+                return Node::Builtin(builtin, args);
+            };
+
+            match builtin {
+                Builtin::Func1(f) => Node::Const(f(values[0])),
+                Builtin::Func2(f) => Node::Const(f(values[0], values[1])),
+                Builtin::FuncN(f) => Node::Const(f(&values)),
+                // Not pure, or not a number
+                Builtin::Rand
+                | Builtin::Color(..)
+                | Builtin::Mix
+                | Builtin::Palette
+                | Builtin::Gradient => Node::Builtin(builtin, args),
+            }
+        }
+        node => node,
+    }
+}
+
+// Checks that the colors are only where they are expected:
+// at the root of a color expression and as the first arguments of `mix`
+fn check(node: &Node, color: bool) -> Result<(), EvalError> {
+    match node {
+        // Raises its own error
+        Node::Fail(..) => Ok(()),
+        Node::Color(name, _, _) if !color => Err(EvalError::NotANumber(name.clone())),
+        Node::Color(_, builtin, args) => {
+            let colors = match builtin {
+                Builtin::Mix => 2,
+                _ => 0,
+            };
+            args.iter().enumerate().try_for_each(|(i, arg)| check(arg, i < colors))
+        }
+        _ if color => Err(EvalError::NotAColor),
+        Node::Const(_) | Node::Load(_) | Node::Arg(_) => Ok(()),
+        Node::Neg(x) => check(x, false),
+        Node::Binary(_, left, right) => check(left, false).and(check(right, false)),
+        Node::Builtin(_, args) | Node::Call(_, args) | Node::External(_, args) => {
+            args.iter().try_for_each(|arg| check(arg, false))
+        }
+    }
+}
+
+fn emit(node: &Node, code: &mut Vec<Instr>) {
+    match node {
+        Node::Const(v) => code.push(Instr::Const(*v)),
+        Node::Load(slot) => code.push(Instr::Load(*slot)),
+        Node::Arg(i) => code.push(Instr::Arg(*i)),
+        Node::Neg(x) => {
+            emit(x, code);
+            code.push(Instr::Neg);
+        }
+        Node::Binary(op, left, right) => {
+            emit(left, code);
+            emit(right, code);
+            code.push(match op {
+                BinOp::Plus => Instr::Add,
+                BinOp::Minus => Instr::Sub,
+                BinOp::Times => Instr::Mul,
+                BinOp::Div => Instr::Div,
+                BinOp::Rem => Instr::Rem,
+                BinOp::Pow => Instr::Pow,
+            });
+        }
+        Node::Builtin(builtin, args) | Node::Color(_, builtin, args) => {
+            args.iter().for_each(|arg| emit(arg, code));
+            code.push(match builtin {
+                Builtin::Func1(f) => Instr::Func1(*f),
+                Builtin::Func2(f) => Instr::Func2(*f),
+                Builtin::FuncN(f) => Instr::FuncN(*f, args.len()),
+                Builtin::Rand => Instr::Rand,
+                Builtin::Color(f, arity) => Instr::Color(*f, *arity),
+                Builtin::Mix => Instr::Mix,
+                Builtin::Palette => Instr::Palette,
+                Builtin::Gradient => Instr::Gradient,
+            });
+        }
+        Node::Call(index, args) => {
+            args.iter().for_each(|arg| emit(arg, code));
+            code.push(Instr::Call(*index));
+        }
+        Node::External(index, args) => {
+            args.iter().for_each(|arg| emit(arg, code));
+            code.push(Instr::External(*index, args.len()));
+        }
+        Node::Fail(error, args) => {
+            args.iter().for_each(|arg| emit(arg, code));
+            code.push(Instr::Fail(*error));
+        }
+    }
+}
+
+/// Failure of a compiled expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    Expression(meval::Error),
+    // User functions calling each other deeper than `MAX_CALL_DEPTH`
+    RecursionTooDeep(String),
+    // Color function used where a number is expected
+    NotANumber(String),
+    // Number used where a color is expected
+    NotAColor,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Expression(error) => write!(f, "{error}"),
+            EvalError::RecursionTooDeep(name) => write!(
+                f,
+                "function `{name}`: calls nested more than {MAX_CALL_DEPTH} deep"
+            ),
+            EvalError::NotANumber(name) => {
+                write!(f, "function `{name}`: returns a color, which is not a number")
+            }
+            EvalError::NotAColor => write!(f, "expected a color, e.g. `rgb(r, g, b)`"),
+        }
+    }
+}
+
+impl Error for EvalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EvalError::Expression(error) => Some(error),
+            EvalError::RecursionTooDeep(_) | EvalError::NotANumber(_) | EvalError::NotAColor => None,
+        }
+    }
+}
+
+impl From<meval::Error> for EvalError {
+    fn from(error: meval::Error) -> Self {
+        EvalError::Expression(error)
+    }
+}
+
+/// Evaluation state of a program: the value of every slot
+/// and the stack of the virtual machine.
+pub struct Machine<'a> {
+    program: &'a Program,
+    slots: Vec<Option<f64>>,
+    stack: Vec<f64>,
+    rng: &'a RefCell<Pcg64>,
+    context: &'a dyn ContextProvider,
+}
+
+impl<'a> Machine<'a> {
+    /// Creates a machine whose slots are initialized from the context.
+    pub fn new(
+        program: &'a Program,
+        rng: &'a RefCell<Pcg64>,
+        context: &'a dyn ContextProvider,
+    ) -> Self {
+        let slots = program
+            .slots
+            .iter()
+            .map(|name| context.get_var(name))
+            .collect();
+
+        Self {
+            program,
+            slots,
+            stack: Vec::with_capacity(16),
+            rng,
+            context,
+        }
+    }
+
+    pub fn tracked(&self) -> &'a [usize] {
+        &self.program.tracked
+    }
+
+    pub fn get(&self, slot: usize) -> Option<f64> {
+        self.slots[slot]
+    }
+
+    pub fn set(&mut self, slot: usize, value: f64) {
+        self.slots[slot] = Some(value);
+    }
+
+    pub fn eval(&mut self, expr: &Compiled) -> Result<f64, EvalError> {
+        self.stack.clear();
+        self.run(&expr.code, 0, 0)
+    }
+
+    /// Evaluates an expression compiled by [`Program::compile_color`].
+    pub fn eval_color(&mut self, expr: &Compiled) -> Result<Rgba, EvalError> {
+        self.stack.clear();
+        self.exec(&expr.code, 0, 0)?;
+
+        match self.stack[..] {
+            [.., r, g, b, a] => Ok((r, g, b, a)),
+            _ => Err(EvalError::NotAColor),
+        }
+    }
+
+    // Executes the code and returns the value left on top of the stack
+    fn run(&mut self, code: &[Instr], base: usize, depth: usize) -> Result<f64, EvalError> {
+        let start = self.stack.len();
+        self.exec(code, base, depth)?;
+
+        let v = self.stack.pop().unwrap_or(f64::NAN);
+        self.stack.truncate(start);
+        Ok(v)
+    }
+
+    // Executes the code, leaving its result on the stack.
+    // The arguments of the current call start at `base`.
+    fn exec(&mut self, code: &[Instr], base: usize, depth: usize) -> Result<(), EvalError> {
+        let program = self.program;
+
+        for instr in code {
+            let stack = &mut self.stack;
+
+            match *instr {
+                Instr::Const(v) => stack.push(v),
+                Instr::Load(slot) => match self.slots[slot] {
+                    Some(v) => stack.push(v),
+                    None => {
+                        let name = program.slots[slot].clone();
+                        return Err(meval::Error::UnknownVariable(name).into());
+                    }
+                },
+                Instr::Arg(i) => stack.push(stack[base + i]),
+                Instr::Neg => {
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(-x);
+                }
+                Instr::Add => binary_instr(stack, BinOp::Plus),
+                Instr::Sub => binary_instr(stack, BinOp::Minus),
+                Instr::Mul => binary_instr(stack, BinOp::Times),
+                Instr::Div => binary_instr(stack, BinOp::Div),
+                Instr::Rem => binary_instr(stack, BinOp::Rem),
+                Instr::Pow => binary_instr(stack, BinOp::Pow),
+                Instr::Func1(f) => {
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(f(x));
+                }
+                Instr::Func2(f) => {
+                    let y = stack.pop().unwrap_or_default();
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(f(x, y));
+                }
+                Instr::FuncN(f, n) => {
+                    let args = stack.len() - n;
+                    let v = f(&stack[args..]);
+                    stack.truncate(args);
+                    stack.push(v);
+                }
+                Instr::Rand => {
+                    let upper = stack.pop().unwrap_or_default();
+                    let lower = stack.pop().unwrap_or_default();
+                    let mut rng = self.rng.borrow_mut();
+                    stack.push(expressions::rand(&mut rng, lower, upper));
+                }
+                Instr::Color(f, n) => {
+                    let args = stack.len() - n;
+                    let color = f(&stack[args..]);
+                    stack.truncate(args);
+                    push_color(stack, color);
+                }
+                Instr::Mix => {
+                    let t = stack.pop().unwrap_or_default();
+                    let c2 = pop_color(stack);
+                    let c1 = pop_color(stack);
+                    push_color(stack, color::mix(c1, c2, t));
+                }
+                Instr::Palette | Instr::Gradient => {
+                    let t = stack.pop().unwrap_or_default();
+                    let palette = stack.pop().unwrap_or_default();
+                    let colors = program
+                        .palettes
+                        .get(palette as usize)
+                        .map(|colors| colors.as_slice())
+                        .unwrap_or_default();
+
+                    let color = match instr {
+                        Instr::Palette => color::palette(colors, t),
+                        _ => color::gradient(colors, t),
+                    };
+                    push_color(stack, color);
+                }
+                Instr::Call(index) => {
+                    let function = &program.functions[index];
+                    if depth >= MAX_CALL_DEPTH {
+                        return Err(EvalError::RecursionTooDeep(function.name.clone()));
+                    }
+
+                    let args = stack.len() - function.arity;
+                    let v = self.run(&function.code.code, args, depth + 1)?;
+                    self.stack.truncate(args);
+                    self.stack.push(v);
+                }
+                Instr::External(index, n) => {
+                    let name = &program.externals[index];
+                    let args = stack.len() - n;
+                    let v = self
+                        .context
+                        .eval_func(name, &stack[args..])
+                        .map_err(|err| meval::Error::Function(name.clone(), err))?;
+                    stack.truncate(args);
+                    stack.push(v);
+                }
+                Instr::Fail(error) => {
+                    return Err(program.errors[error].clone());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn binary_instr(stack: &mut Vec<f64>, op: BinOp) {
+    let right = stack.pop().unwrap_or_default();
+    let left = stack.pop().unwrap_or_default();
+    stack.push(binary(op, left, right));
+}
+
+fn push_color(stack: &mut Vec<f64>, color: Rgba) {
+    stack.extend([color.0, color.1, color.2, color.3]);
+}
+
+fn pop_color(stack: &mut Vec<f64>) -> Rgba {
+    let a = stack.pop().unwrap_or_default();
+    let b = stack.pop().unwrap_or_default();
+    let g = stack.pop().unwrap_or_default();
+    let r = stack.pop().unwrap_or_default();
+    (r, g, b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::ExprContext;
+
+    #[test]
+    fn same_results_as_meval() {
+        let expressions = [
+            "1 + 2 * 3 - 4 / 5",
+            "-x ^ 2 % 7",
+            "sin(x) * cos(pi / 4) + atan2(x, 2)",
+            "max(1, x, 3) - min(x)",
+            "signum(4 - x * x) * abs(-x) + e",
+        ];
+
+        let mut context = ExprContext::new();
+        context.var("x", 1.7);
+
+        let mut program = Program::default();
+        let compiled: Vec<Compiled> = expressions
+            .iter()
+            .map(|expr| program.compile_expr(&expr.parse().unwrap()))
+            .collect();
+
+        let rng = RefCell::new(expressions::get_rng("seed"));
+        let mut machine = Machine::new(&program, &rng, &context);
+
+        for (expr, compiled) in expressions.iter().zip(&compiled) {
+            let expected = meval::eval_str_with_context(expr, &context).unwrap();
+            assert_eq!(machine.eval(compiled).unwrap(), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn colors() {
+        let mut program = Program::default();
+        let mut compile = |expr: &str| program.compile_color(&expr.parse().unwrap());
+        let opaque = compile("rgb(255, 51 * x, 0)");
+        let mixed = compile("mix(rgba(0, 0, 0, 0.5), hsl(0, 1, 0.5), x / 2)");
+        let number = compile("x + 1");
+
+        let mut context = ExprContext::new();
+        context.var("x", 2.0);
+        let rng = RefCell::new(expressions::get_rng("seed"));
+        let mut machine = Machine::new(&program, &rng, &context);
+
+        assert_eq!(machine.eval_color(&opaque), Ok((1.0, 0.4, 0.0, 1.0)));
+        let (r, g, b, a) = machine.eval_color(&mixed).unwrap();
+        assert!((r - 1.0).abs() + g + b < 1e-4 && a == 1.0, "{:?}", (r, g, b, a));
+        assert_eq!(machine.eval_color(&number), Err(EvalError::NotAColor));
+
+        // Colors are not numbers, even inside of a color
+        let mut program = Program::default();
+        let channel = program.compile_color(&"rgb(hsl(0, 1, 0.5), 0, 0)".parse().unwrap());
+        let sum = program.compile_expr(&"rgb(1, 2, 3) + 1".parse().unwrap());
+        let mut machine = Machine::new(&program, &rng, &context);
+
+        let not_a_number = Err(EvalError::NotANumber("hsl".to_string()));
+        assert_eq!(machine.eval_color(&channel), not_a_number);
+        assert_eq!(machine.eval(&sum), Err(EvalError::NotANumber("rgb".to_string())));
+    }
+
+    #[test]
+    fn constant_folding() {
+        let mut program = Program::default();
+        let compiled = program.compile_expr(&"sin(pi / 2) * 2 + max(1, 3)".parse().unwrap());
+        assert!(matches!(*compiled.code, [Instr::Const(v)] if v == 5.0));
+
+        let compiled = program.compile_expr(&"rand(0, 1) * 2".parse().unwrap());
+        assert_eq!(compiled.code.len(), 5);
+    }
+
+    #[test]
+    fn functions() {
+        // f0(x) = f1(x) + 1, f1(x) = f2(x) + 1, ... and the last one is x
+        let chain = |length: usize| {
+            let mut fractal = LSystemRenderer::default();
+            for i in 0..length {
+                let body = match i + 1 < length {
+                    true => format!("f{}(x) + 1", i + 1),
+                    false => "x".to_string(),
+                };
+                let function = Function {
+                    params: vec!["x".to_string()],
+                    body: body.parse().unwrap(),
+                };
+                fractal.functions.insert(format!("f{i}"), function);
+            }
+
+            let mut program = Program::compile(&fractal);
+            let compiled = program.compile_expr(&"f0(2) * 2".parse().unwrap());
+
+            let context = ExprContext::new();
+            let rng = RefCell::new(expressions::get_rng("seed"));
+            let mut machine = Machine::new(&program, &rng, &context);
+            machine.eval(&compiled)
+        };
+
+        assert_eq!(chain(3), Ok(8.0));
+        assert_eq!(chain(MAX_CALL_DEPTH), Ok(2.0 * (MAX_CALL_DEPTH as f64 + 1.0)));
+
+        let error = chain(MAX_CALL_DEPTH + 1).unwrap_err();
+        assert_eq!(error, EvalError::RecursionTooDeep(format!("f{MAX_CALL_DEPTH}")));
+        assert!(error.to_string().contains("nested more than"));
+    }
+
+    #[test]
+    fn errors() {
+        let mut program = Program::default();
+        let unknown = program.compile_expr(&"y + 1".parse().unwrap());
+        let arity = program.compile_expr(&"sin(1, 2)".parse().unwrap());
+
+        let context = ExprContext::new();
+        let rng = RefCell::new(expressions::get_rng("seed"));
+        let mut machine = Machine::new(&program, &rng, &context);
+
+        assert_eq!(
+            machine.eval(&unknown),
+            Err(meval::Error::UnknownVariable("y".to_string()).into())
+        );
+        assert_eq!(
+            machine.eval(&arity),
+            Err(meval::Error::Function("sin".to_string(), FuncEvalError::NumberArgs(1)).into())
+        );
+    }
+}
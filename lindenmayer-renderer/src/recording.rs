@@ -0,0 +1,361 @@
+use crate::backend::num;
+use crate::canvas::{Canvas, CanvasResult, LineCap, LineJoin, TextAnchor};
+use crate::display_list::Transform;
+use std::cell::{Ref, RefCell};
+use std::fmt;
+
+/// Call made to a canvas, with its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    Stroke,
+    Save,
+    Restore,
+    Translate(f64, f64),
+    Rotate(f64),
+    Scale(f64, f64),
+    SetTransform(Transform),
+    ClipRect(f64, f64, f64, f64),
+    ClipPath,
+    SetLineWidth(f64),
+    SetLineCap(LineCap),
+    SetLineJoin(LineJoin),
+    SetMiterLimit(f64),
+    SetDash(Vec<f64>),
+    Rectangle(f64, f64, f64, f64),
+    SetColor(f64, f64, f64, f64),
+    Arc(f64, f64, f64),
+    Fill,
+    ClosePath,
+    CurveTo(f64, f64, f64, f64, f64, f64),
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        size: f64,
+        angle: f64,
+        anchor: TextAnchor,
+    },
+}
+
+// Numbers are written with at most 4 decimals, so that the log
+// does not change with the rounding errors of each platform
+struct Num(f64);
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", num(self.0, 4))
+    }
+}
+
+/// One line, with the name of the method and its arguments.
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Call::MoveTo(x, y) => write!(f, "move_to {} {}", Num(*x), Num(*y)),
+            Call::LineTo(x, y) => write!(f, "line_to {} {}", Num(*x), Num(*y)),
+            Call::Stroke => write!(f, "stroke"),
+            Call::Save => write!(f, "save"),
+            Call::Restore => write!(f, "restore"),
+            Call::Translate(dx, dy) => write!(f, "translate {} {}", Num(*dx), Num(*dy)),
+            Call::Rotate(angle) => write!(f, "rotate {}", Num(*angle)),
+            Call::Scale(sx, sy) => write!(f, "scale {} {}", Num(*sx), Num(*sy)),
+            Call::SetTransform(t) => write!(
+                f,
+                "set_transform {} {} {} {} {} {}",
+                Num(t.xx),
+                Num(t.yx),
+                Num(t.xy),
+                Num(t.yy),
+                Num(t.x0),
+                Num(t.y0)
+            ),
+            Call::ClipRect(x, y, width, height) => {
+                write!(f, "clip_rect {} {} {} {}", Num(*x), Num(*y), Num(*width), Num(*height))
+            }
+            Call::ClipPath => write!(f, "clip_path"),
+            Call::SetLineWidth(width) => write!(f, "set_line_width {}", Num(*width)),
+            Call::SetLineCap(cap) => write!(f, "set_line_cap {cap:?}"),
+            Call::SetLineJoin(join) => write!(f, "set_line_join {join:?}"),
+            Call::SetMiterLimit(limit) => write!(f, "set_miter_limit {}", Num(*limit)),
+            Call::SetDash(dashes) => {
+                write!(f, "set_dash")?;
+                for dash in dashes {
+                    write!(f, " {}", Num(*dash))?;
+                }
+                Ok(())
+            }
+            Call::Rectangle(x, y, width, height) => {
+                write!(f, "rectangle {} {} {} {}", Num(*x), Num(*y), Num(*width), Num(*height))
+            }
+            Call::SetColor(r, g, b, a) => {
+                write!(f, "set_color {} {} {} {}", Num(*r), Num(*g), Num(*b), Num(*a))
+            }
+            Call::Arc(x, y, r) => write!(f, "arc {} {} {}", Num(*x), Num(*y), Num(*r)),
+            Call::Fill => write!(f, "fill"),
+            Call::ClosePath => write!(f, "close_path"),
+            Call::CurveTo(x1, y1, x2, y2, x3, y3) => write!(
+                f,
+                "curve_to {} {} {} {} {} {}",
+                Num(*x1),
+                Num(*y1),
+                Num(*x2),
+                Num(*y2),
+                Num(*x3),
+                Num(*y3)
+            ),
+            Call::Text {
+                x,
+                y,
+                text,
+                size,
+                angle,
+                anchor,
+            } => write!(
+                f,
+                "text {} {} {:?} {} {} {anchor:?}",
+                Num(*x),
+                Num(*y),
+                text,
+                Num(*size),
+                Num(*angle)
+            ),
+        }
+    }
+}
+
+/// Canvas which draws nothing and records every call made to it,
+/// e.g. to compare the output of a fractal with the expected one.
+#[derive(Default)]
+pub struct RecordingCanvas {
+    calls: RefCell<Vec<Call>>,
+    // Number of calls after which every call fails
+    fail_after: Option<usize>,
+}
+
+impl RecordingCanvas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Canvas which fails once it has recorded the given number of calls,
+    /// e.g. to check how the errors of a backend are handled.
+    pub fn failing_after(calls: usize) -> Self {
+        Self {
+            calls: RefCell::default(),
+            fail_after: Some(calls),
+        }
+    }
+
+    pub fn calls(&self) -> Ref<'_, Vec<Call>> {
+        self.calls.borrow()
+    }
+
+    pub fn into_calls(self) -> Vec<Call> {
+        self.calls.into_inner()
+    }
+
+    /// The calls, one on each line.
+    pub fn log(&self) -> String {
+        self.calls.borrow().iter().map(|call| format!("{call}\n")).collect()
+    }
+
+    fn record(&self, call: Call) -> CanvasResult {
+        let mut calls = self.calls.borrow_mut();
+        if self.fail_after.is_some_and(|max| calls.len() >= max) {
+            return Err(format!("cannot {call}").into());
+        }
+
+        calls.push(call);
+        Ok(())
+    }
+}
+
+impl Canvas for RecordingCanvas {
+    fn move_to(&self, x: f64, y: f64) -> CanvasResult {
+        self.record(Call::MoveTo(x, y))
+    }
+
+    fn line_to(&self, x: f64, y: f64) -> CanvasResult {
+        self.record(Call::LineTo(x, y))
+    }
+
+    fn stroke(&self) -> CanvasResult {
+        self.record(Call::Stroke)
+    }
+
+    fn save(&self) -> CanvasResult {
+        self.record(Call::Save)
+    }
+
+    fn restore(&self) -> CanvasResult {
+        self.record(Call::Restore)
+    }
+
+    fn translate(&self, dx: f64, dy: f64) -> CanvasResult {
+        self.record(Call::Translate(dx, dy))
+    }
+
+    fn rotate(&self, angle: f64) -> CanvasResult {
+        self.record(Call::Rotate(angle))
+    }
+
+    fn scale(&self, sx: f64, sy: f64) -> CanvasResult {
+        self.record(Call::Scale(sx, sy))
+    }
+
+    fn set_transform(&self, transform: &Transform) -> CanvasResult {
+        self.record(Call::SetTransform(*transform))
+    }
+
+    fn clip_rect(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        self.record(Call::ClipRect(x, y, width, height))
+    }
+
+    fn clip_path(&self) -> CanvasResult {
+        self.record(Call::ClipPath)
+    }
+
+    fn set_line_width(&self, thickness: f64) -> CanvasResult {
+        self.record(Call::SetLineWidth(thickness))
+    }
+
+    fn set_line_cap(&self, cap: LineCap) -> CanvasResult {
+        self.record(Call::SetLineCap(cap))
+    }
+
+    fn set_line_join(&self, join: LineJoin) -> CanvasResult {
+        self.record(Call::SetLineJoin(join))
+    }
+
+    fn set_miter_limit(&self, limit: f64) -> CanvasResult {
+        self.record(Call::SetMiterLimit(limit))
+    }
+
+    fn set_dash(&self, dashes: &[f64]) -> CanvasResult {
+        self.record(Call::SetDash(dashes.to_vec()))
+    }
+
+    fn rectangle(&self, x: f64, y: f64, width: f64, height: f64) -> CanvasResult {
+        self.record(Call::Rectangle(x, y, width, height))
+    }
+
+    fn set_color(&self, r: f64, g: f64, b: f64, a: f64) -> CanvasResult {
+        self.record(Call::SetColor(r, g, b, a))
+    }
+
+    fn arc(&self, x: f64, y: f64, r: f64) -> CanvasResult {
+        self.record(Call::Arc(x, y, r))
+    }
+
+    fn fill(&self) -> CanvasResult {
+        self.record(Call::Fill)
+    }
+
+    fn close_path(&self) -> CanvasResult {
+        self.record(Call::ClosePath)
+    }
+
+    fn curve_to(&self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> CanvasResult {
+        self.record(Call::CurveTo(x1, y1, x2, y2, x3, y3))
+    }
+
+    fn text(
+        &self,
+        x: f64,
+        y: f64,
+        text: &str,
+        size: f64,
+        angle: f64,
+        anchor: TextAnchor,
+    ) -> CanvasResult {
+        self.record(Call::Text {
+            x,
+            y,
+            text: text.to_string(),
+            size,
+            angle,
+            anchor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{ExprContext, RenderError};
+    use crate::display_list::{DisplayList, Primitive, StrokeStyle};
+    use crate::{LSystem, LSystemRenderer, Label, LabelPart, Operation};
+
+    #[test]
+    fn log() {
+        let list = DisplayList {
+            primitives: vec![Primitive::Segment {
+                from: (0.0, 0.5),
+                to: (1.0 / 3.0, -0.00001),
+                width: 2.0,
+                color: (1.0, 0.0, 0.0, 1.0),
+                stroke: StrokeStyle::default(),
+                index: 0,
+                depth: 1,
+            }],
+            view: None,
+        };
+
+        let canvas = RecordingCanvas::new();
+        list.replay(&canvas).unwrap();
+        assert_eq!(
+            canvas.log(),
+            "set_line_cap Butt\n\
+            set_line_join Miter\n\
+            set_miter_limit 10\n\
+            set_dash\n\
+            set_line_width 2\n\
+            set_color 1 0 0 1\n\
+            move_to 0 0.5\n\
+            line_to 0.3333 0\n\
+            stroke\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let label = Label {
+            parts: vec![LabelPart::Literal("leaf".to_string())],
+            size: "12".parse().unwrap(),
+            anchor: TextAnchor::Middle,
+            follow_heading: true,
+        };
+        let mut fractal = LSystemRenderer {
+            lsystem: LSystem::new("F[T]", &[]),
+            operations: [
+                ('F', vec![Operation::Forward("10".parse().unwrap())]),
+                ('[', vec![Operation::PushStack]),
+                (']', vec![Operation::PopStack]),
+                ('T', vec![Operation::Text(label)]),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        let canvas = RecordingCanvas::new();
+        (&canvas as &dyn Canvas).draw_fractal(&fractal, &mut ExprContext::new()).unwrap();
+        let calls = canvas.into_calls();
+        assert!(calls.iter().any(|call| matches!(call, Call::Text { .. })));
+
+        // The first error of the canvas stops the drawing, whichever call it is
+        for (i, call) in calls.iter().enumerate() {
+            let canvas = RecordingCanvas::failing_after(i);
+            let error = (&canvas as &dyn Canvas)
+                .draw_fractal(&fractal, &mut ExprContext::new())
+                .unwrap_err();
+
+            assert!(matches!(error, RenderError::Canvas(_)));
+            assert_eq!(error.to_string(), format!("canvas error: cannot {call}"));
+            assert_eq!(canvas.calls()[..], calls[..i]);
+        }
+    }
+}
@@ -0,0 +1,1198 @@
+use crate::backend::num;
+use crate::budget::{Budget, RenderStatus, StopReason};
+use crate::camera::{self, Vector, View};
+use crate::canvas::{ExecutionError, ExprContext, OperationError, RenderError};
+use crate::curves::Curves;
+use crate::display_list::{DisplayList, Point, Primitive, StrokeStyle, ViewTransform};
+use crate::expressions::color::Rgba;
+use crate::program::{self, CompiledOperation, EvalError, Machine};
+use crate::{Color, LSystemRenderer, LabelPart, Operation, Strict};
+use meval::ContextProvider;
+use std::ops::ControlFlow;
+
+/// Upper bound of `repeat` iterations executed for a single symbol,
+/// so that a huge or nested count cannot hang the renderer.
+/// Going beyond it fails with [`ExecutionError::TooManyIterations`].
+const MAX_REPEAT_ITERATIONS: u32 = 100_000;
+
+// State saved by push
+#[derive(Clone)]
+struct TurtleState {
+    pos: (f64, f64),
+    rot: f64,
+    // Position and orientation of the 3D turtle
+    pos3: Vector,
+    heading: Vector,
+    left: Vector,
+    up: Vector,
+    thickness: f64,
+    color: Rgba,
+    stroke: StrokeStyle,
+    // How much the heading bends towards the tropism
+    susceptibility: f64,
+}
+
+struct Turtle {
+    state: TurtleState,
+    // Direction of the tropism
+    tropism: Vector,
+    // Camera of the 3D turtle, none for the 2D one
+    view: Option<View>,
+    // Stack errors are reported when set
+    strict: Option<Strict>,
+    depth: i32,
+    // Number of operations executed
+    operations: usize,
+    // Index of the current symbol
+    index: usize,
+    symbol: char,
+    // Number of chars to skip
+    ignore_counter: u32,
+    // Remaining repeat iterations for the current symbol
+    iterations: u32,
+    // Stack of the tracked variables, in the order of `Program::tracked`
+    stack1: Vec<Vec<f64>>,
+    // Stack of the turtle states
+    stack2: Vec<TurtleState>,
+    // Vertices of the polygons being recorded, innermost last
+    polygons: Vec<Vec<Point>>,
+    // Number of open polygons at each push
+    polygon_marks: Vec<usize>,
+    // Segments are joined into paths when set
+    curves: Option<Curves>,
+    // Path of the current branch
+    path: Option<OpenPath>,
+    // Paths of the parent branches, resumed by pop
+    paths: Vec<Option<OpenPath>>,
+}
+
+// Error of an operation, possibly nested in blocks
+struct Failure {
+    error: ExecutionError,
+    // Positions of the operation, from the list of the symbol to the innermost block
+    path: Vec<usize>,
+    // Position and depth of the turtle before the failing operation
+    before: Option<(Vector, i32)>,
+}
+
+impl From<ExecutionError> for Failure {
+    fn from(error: ExecutionError) -> Self {
+        Failure {
+            error,
+            path: vec![],
+            before: None,
+        }
+    }
+}
+
+impl From<EvalError> for Failure {
+    fn from(error: EvalError) -> Self {
+        ExecutionError::from(error).into()
+    }
+}
+
+// Path being recorded, it continues while the segments are connected
+// and drawn with the same thickness and color
+struct OpenPath {
+    points: Vec<Point>,
+    width: f64,
+    thickness: f64,
+    color: Rgba,
+    stroke: StrokeStyle,
+    index: usize,
+    depth: usize,
+}
+
+/// Runs the turtle over the expression of the fractal, until the budget runs out.
+/// The animated variables must already be set in the context.
+pub(crate) fn interpret(
+    fractal: &LSystemRenderer,
+    variables: &ExprContext,
+    budget: &Budget,
+) -> Result<(DisplayList, RenderStatus), RenderError> {
+    let program = &fractal.program;
+    let pos = fractal.initial_pos;
+
+    let mut machine = Machine::new(program, &fractal.rng, variables);
+
+    // Set length variable
+    machine.set(program::LENGTH, fractal.expression.len() as f64);
+    // Set depth variable
+    machine.set(program::DEPTH, 0f64);
+    // Set pos variables
+    machine.set(program::POSX, pos.0);
+    machine.set(program::POSY, pos.1);
+    machine.set(program::POSZ, 0f64);
+
+    // Add initial values, animated variables override them
+    for (slot, value) in program.variables.iter() {
+        let value = machine.eval(value)?;
+        machine.set(*slot, value);
+    }
+    for key in fractal.animations.keys() {
+        if let (Some(slot), Some(value)) = (program.slot_of(key), variables.get_var(key)) {
+            machine.set(slot, value);
+        }
+    }
+
+    // Without configuration, the tropism of the branches is the gravity
+    let (tropism, susceptibility) = match &program.tropism {
+        Some(tropism) => (tropism.direction, machine.eval(&tropism.susceptibility)?),
+        None => ([0.0, 1.0, 0.0], 0.0),
+    };
+
+    // The 3D turtle starts on the plane of the canvas,
+    // with the same heading as the 2D one and facing the viewer
+    let rot = fractal.initial_rot;
+    let state = TurtleState {
+        pos,
+        rot,
+        pos3: [pos.0, pos.1, 0.0],
+        heading: [-rot.sin(), -rot.cos(), 0.0],
+        left: [-rot.cos(), rot.sin(), 0.0],
+        up: [0.0, 0.0, -1.0],
+        thickness: fractal.initial_thickness,
+        color: fractal.initial_color,
+        stroke: fractal.initial_stroke.clone(),
+        susceptibility,
+    };
+
+    let view = match program.camera.projection {
+        Some(projection) => {
+            let camera = &program.camera;
+            let center = [fractal.canvas.0 as f64 / 2.0, fractal.canvas.1 as f64 / 2.0, 0.0];
+            let yaw = machine.eval(&camera.yaw)?;
+            let pitch = machine.eval(&camera.pitch)?;
+            let distance = machine.eval(&camera.distance)?;
+
+            Some(View::new(projection, center, yaw, pitch, distance))
+        }
+        None => None,
+    };
+
+    let mut turtle = Turtle {
+        state,
+        tropism,
+        view,
+        strict: fractal.strict,
+        depth: 0,
+        operations: 0,
+        index: 0,
+        symbol: ' ',
+        ignore_counter: 0,
+        iterations: MAX_REPEAT_ITERATIONS,
+        stack1: program
+            .tracked()
+            .iter()
+            .map(|slot| machine.get(*slot).into_iter().collect())
+            .collect(),
+        stack2: vec![],
+        polygons: vec![],
+        polygon_marks: vec![],
+        curves: fractal.curves,
+        path: None,
+        paths: vec![],
+    };
+
+    let mut display_list = DisplayList::default();
+
+    if let Some(viewport) = &program.viewport {
+        display_list.view = Some(ViewTransform {
+            zoom: machine.eval(&viewport.zoom)?,
+            center: (machine.eval(&viewport.center.0)?, machine.eval(&viewport.center.1)?),
+            angle: machine.eval(&viewport.angle)?,
+            canvas: (fractal.canvas.0 as f64, fractal.canvas.1 as f64),
+        });
+    }
+
+    // Fill background
+    display_list.push(Primitive::Fill {
+        origin: (0.0, 0.0),
+        size: (fractal.canvas.0 as f64, fractal.canvas.1 as f64),
+        color: fractal.background_color,
+    });
+
+    let mut status = RenderStatus::Complete;
+
+    // Execute each operation
+    'symbols: for (index, c) in fractal.expression.chars().enumerate() {
+        // The symbols drawn so far are kept
+        if let Some(reason) = budget.check(index, turtle.operations, display_list.primitives.len()) {
+            status = RenderStatus::Partial(reason);
+            break;
+        }
+
+        // Skip chars if an ignore action has been called
+        if turtle.ignore_counter > 0 {
+            turtle.ignore_counter -= 1;
+            continue;
+        }
+
+        // Update variables
+        machine.set(program::INDEX, index as f64);
+        turtle.index = index;
+        turtle.symbol = c;
+
+        let operations = program.operations.get(&c);
+
+        if let Some(operations) = operations {
+            turtle.iterations = MAX_REPEAT_ITERATIONS;
+
+            let flow = execute(operations, &mut turtle, &mut machine, &mut display_list, budget)
+                .map_err(|failure| turtle.error(failure, fractal))?;
+
+            if let ControlFlow::Break(reason) = flow {
+                status = RenderStatus::Partial(reason);
+                break 'symbols;
+            }
+        }
+    }
+
+    // Fill the polygons which have not been closed
+    while !turtle.polygons.is_empty() {
+        turtle.end_polygon(&mut display_list);
+    }
+
+    // Stroke the paths of the branches which have not been popped
+    loop {
+        turtle.end_path(&mut display_list);
+        match turtle.paths.pop() {
+            Some(path) => turtle.path = path,
+            None => break,
+        }
+    }
+
+    Ok((display_list, status))
+}
+
+// Breaks when the budget runs out, which can happen in the middle of a symbol
+fn execute(
+    operations: &[CompiledOperation],
+    turtle: &mut Turtle,
+    machine: &mut Machine,
+    display_list: &mut DisplayList,
+    budget: &Budget,
+) -> Result<ControlFlow<StopReason>, Failure> {
+    for (position, op) in operations.iter().enumerate() {
+        if let Some(reason) = budget.check(turtle.operations, turtle.operations, display_list.primitives.len()) {
+            return Ok(ControlFlow::Break(reason));
+        }
+        turtle.operations += 1;
+
+        let before = (turtle.position(), turtle.depth);
+        let flow = execute_operation(op, turtle, machine, display_list, budget).map_err(|mut failure| {
+            // The innermost operation is the one which failed
+            failure.path.insert(0, position);
+            failure.before.get_or_insert(before);
+            failure
+        })?;
+        if flow.is_break() {
+            return Ok(flow);
+        }
+    }
+
+    Ok(ControlFlow::Continue(()))
+}
+
+fn execute_operation(
+    op: &CompiledOperation,
+    turtle: &mut Turtle,
+    machine: &mut Machine,
+    display_list: &mut DisplayList,
+    budget: &Budget,
+) -> Result<ControlFlow<StopReason>, Failure> {
+    match op {
+        Operation::Forward(expr) => {
+            let length = machine.eval(expr)?;
+            turtle.forward(length, true, display_list);
+            turtle.bend();
+
+            // Update POS variables
+            turtle.set_position_variables(machine);
+        }
+        Operation::Jump(expr) => {
+            let length = machine.eval(expr)?;
+            turtle.forward(length, false, display_list);
+
+            // Update POS variables
+            turtle.set_position_variables(machine);
+        }
+        Operation::Dot(expr) => {
+            let radius = machine.eval(expr)?;
+            let state = &turtle.state;
+
+            let projected = match &turtle.view {
+                Some(view) => view.project(state.pos3),
+                None => Some((state.pos, 1.0)),
+            };
+
+            if let Some((center, scale)) = projected {
+                display_list.push(Primitive::Dot {
+                    center,
+                    radius: radius * scale,
+                    color: state.color,
+                    index: turtle.index,
+                    depth: turtle.depth as usize,
+                });
+            }
+        }
+        Operation::Rotate(expr) => {
+            let angle = machine.eval(expr)?;
+            let state = &mut turtle.state;
+
+            if turtle.view.is_some() {
+                (state.heading, state.left) = camera::rotate(state.heading, state.left, angle);
+            } else {
+                state.rot += angle;
+            }
+        }
+        Operation::Pitch(expr) => {
+            // The 2D turtle cannot leave the plane
+            let angle = machine.eval(expr)?;
+            let state = &mut turtle.state;
+
+            if turtle.view.is_some() {
+                (state.heading, state.up) = camera::rotate(state.heading, state.up, angle);
+            }
+        }
+        Operation::Roll(expr) => {
+            let angle = machine.eval(expr)?;
+            let state = &mut turtle.state;
+
+            if turtle.view.is_some() {
+                (state.left, state.up) = camera::rotate(state.left, state.up, angle);
+            }
+        }
+        Operation::Thickness(expr) => {
+            turtle.state.thickness = machine.eval(expr)?;
+        }
+        Operation::Tropism(expr) => {
+            turtle.state.susceptibility = machine.eval(expr)?;
+        }
+        Operation::LineCap(cap) => {
+            turtle.state.stroke.cap = *cap;
+        }
+        Operation::LineJoin(join) => {
+            turtle.state.stroke.join = *join;
+        }
+        Operation::MiterLimit(expr) => {
+            turtle.state.stroke.miter_limit = machine.eval(expr)?.max(1.0);
+        }
+        Operation::Dash(exprs) => {
+            let mut dashes = Vec::with_capacity(exprs.len());
+            for expr in exprs {
+                dashes.push(machine.eval(expr)?);
+            }
+
+            // Patterns which cannot be drawn give solid lines
+            let valid = dashes.iter().all(|dash| *dash >= 0.0) && dashes.iter().sum::<f64>() > 0.0;
+            if !valid {
+                dashes.clear();
+            }
+            turtle.state.stroke.dashes = dashes.into();
+        }
+        Operation::Ignore(expr) => {
+            let v = machine.eval(expr)?;
+            turtle.ignore_counter = v as u32;
+        }
+        Operation::PushStack => {
+            let max_depth = turtle.strict.and_then(|strict| strict.max_depth);
+            if let Some(max_depth) = max_depth.filter(|max| turtle.depth as usize >= *max) {
+                return Err(ExecutionError::StackOverflow(max_depth).into());
+            }
+
+            for (slot, value) in machine.tracked().iter().zip(&mut turtle.stack1) {
+                if let Some(v) = machine.get(*slot) {
+                    value.push(v);
+                }
+            }
+            turtle.stack2.push(turtle.state.clone());
+            turtle.polygon_marks.push(turtle.polygons.len());
+            turtle.paths.push(turtle.path.take());
+
+            // Update depth value
+            turtle.depth += 1;
+            machine.set(program::DEPTH, turtle.depth as f64);
+        }
+        Operation::PopStack => {
+            // Without a matching push the pop does nothing
+            if turtle.stack2.is_empty() && turtle.strict.is_some() {
+                return Err(ExecutionError::StackUnderflow.into());
+            }
+
+            if let Some(old_state) = turtle.stack2.pop() {
+                // Polygons started in the branch end with it
+                let mark = turtle.polygon_marks.pop().unwrap_or_default();
+                while turtle.polygons.len() > mark {
+                    turtle.end_polygon(display_list);
+                }
+
+                // The path of the parent continues where it was left
+                turtle.end_path(display_list);
+                turtle.path = turtle.paths.pop().flatten();
+
+                // restore variables
+                turtle.state = old_state;
+                for (slot, value) in machine.tracked().iter().zip(&mut turtle.stack1) {
+                    if let Some(v) = value.pop() {
+                        machine.set(*slot, v);
+                    }
+                }
+
+                // Update depth variable
+                turtle.depth -= 1;
+                machine.set(program::DEPTH, turtle.depth as f64);
+            }
+        }
+        Operation::BeginPolygon => {
+            turtle.polygons.push(vec![]);
+        }
+        Operation::Vertex => {
+            let vertex = match &turtle.view {
+                Some(view) => view.project(turtle.state.pos3).map(|(point, _)| point),
+                None => Some(turtle.state.pos),
+            };
+
+            if let (Some(polygon), Some(vertex)) = (turtle.polygons.last_mut(), vertex) {
+                polygon.push(vertex);
+            }
+        }
+        Operation::EndPolygon => {
+            turtle.end_polygon(display_list);
+        }
+        Operation::SetColor(Color::Static(color)) => {
+            turtle.state.color = *color;
+        }
+        Operation::SetColor(Color::Dynamic(expr)) => {
+            turtle.state.color = machine.eval_color(expr)?;
+        }
+        Operation::Text(label) => {
+            let mut text = String::new();
+            for part in &label.parts {
+                match part {
+                    LabelPart::Literal(literal) => text.push_str(literal),
+                    LabelPart::Symbol => text.push(turtle.symbol),
+                    LabelPart::Value(expr, decimals) => {
+                        text.push_str(&num(machine.eval(expr)?, *decimals))
+                    }
+                }
+            }
+            let size = machine.eval(&label.size)?;
+
+            if let Some((position, angle, scale)) = turtle.label_position() {
+                display_list.push(Primitive::Text {
+                    position,
+                    text,
+                    size: size * scale,
+                    angle: if label.follow_heading { angle } else { 0.0 },
+                    anchor: label.anchor,
+                    color: turtle.state.color,
+                    index: turtle.index,
+                    depth: turtle.depth as usize,
+                });
+            }
+        }
+        Operation::SetVar(slot, expr) => {
+            let v = machine.eval(expr)?;
+            machine.set(*slot, v);
+        }
+        Operation::If(condition, then_branch, else_branch) => {
+            let branch = if machine.eval(condition)? != 0.0 {
+                then_branch
+            } else {
+                else_branch
+            };
+
+            let flow = execute(branch, turtle, machine, display_list, budget)?;
+            if flow.is_break() {
+                return Ok(flow);
+            }
+        }
+        Operation::Repeat(count, body) => {
+            let count = machine.eval(count)?;
+
+            for _ in 0..(count as u32) {
+                if turtle.iterations == 0 {
+                    return Err(ExecutionError::TooManyIterations(MAX_REPEAT_ITERATIONS).into());
+                }
+                turtle.iterations -= 1;
+
+                let flow = execute(body, turtle, machine, display_list, budget)?;
+                if flow.is_break() {
+                    return Ok(flow);
+                }
+            }
+        }
+    }
+
+    Ok(ControlFlow::Continue(()))
+}
+
+// Operations of the blocks of an `if` or a `repeat`, the else branch after the then branch
+fn nested(operation: &CompiledOperation) -> impl Iterator<Item = &CompiledOperation> {
+    let (first, second): (&[_], &[_]) = match operation {
+        Operation::If(_, then_branch, else_branch) => (then_branch, else_branch),
+        Operation::Repeat(_, body) => (body, &[]),
+        _ => (&[], &[]),
+    };
+
+    first.iter().chain(second)
+}
+
+// Number of operations, counting the nested ones
+fn count(operation: &CompiledOperation) -> usize {
+    1 + nested(operation).map(count).sum::<usize>()
+}
+
+// Index of the operation at `path` when every operation is listed
+// before the ones of its blocks, as in the sources of the symbol
+fn source_index<'a>(mut operations: impl Iterator<Item = &'a CompiledOperation>, path: &[usize]) -> usize {
+    let Some((&position, path)) = path.split_first() else {
+        return 0;
+    };
+
+    let before = operations.by_ref().take(position).map(count).sum::<usize>();
+    match operations.next() {
+        Some(operation) if !path.is_empty() => before + 1 + source_index(nested(operation), path),
+        _ => before,
+    }
+}
+
+impl Turtle {
+    // Moves forward, adding a segment to the display list if `draw` is set
+    fn forward(&mut self, length: f64, draw: bool, display_list: &mut DisplayList) {
+        let state = &mut self.state;
+
+        let segment = match &self.view {
+            None => {
+                let start_pos = state.pos;
+                let (pos, rot) = (state.pos, state.rot);
+                state.pos = (pos.0 - length * rot.sin(), pos.1 - length * rot.cos());
+
+                Some((start_pos, state.pos, state.thickness))
+            }
+            Some(view) => {
+                let start_pos = state.pos3;
+                state.pos3 = camera::add(state.pos3, camera::scale(state.heading, length));
+
+                // Segments going behind the eye are not drawn
+                match (view.project(start_pos), view.project(state.pos3)) {
+                    (Some((from, s1)), Some((to, s2))) => {
+                        Some((from, to, state.thickness * (s1 + s2) / 2.0))
+                    }
+                    _ => None,
+                }
+            }
+        };
+
+        match segment {
+            Some((from, to, width)) if draw => self.segment(from, to, width, display_list),
+            // A jump breaks the path
+            _ => self.end_path(display_list),
+        }
+    }
+
+    // Rotates the heading towards the tropism, by the susceptibility
+    // times the sine of the angle between them
+    fn bend(&mut self) {
+        let state = &mut self.state;
+        if state.susceptibility == 0.0 {
+            return;
+        }
+
+        if self.view.is_none() {
+            // Positive rotations turn towards the left vector
+            let left = [-state.rot.cos(), state.rot.sin(), 0.0];
+            state.rot += state.susceptibility * camera::dot(self.tropism, left);
+            return;
+        }
+
+        let axis = camera::cross(state.heading, self.tropism);
+        let norm = camera::dot(axis, axis).sqrt();
+        if norm < f64::EPSILON {
+            return;
+        }
+
+        let axis = camera::scale(axis, 1.0 / norm);
+        let angle = state.susceptibility * norm;
+        state.heading = camera::rotate_around(state.heading, axis, angle);
+        state.left = camera::rotate_around(state.left, axis, angle);
+        state.up = camera::rotate_around(state.up, axis, angle);
+    }
+
+    fn segment(&mut self, from: Point, to: Point, width: f64, display_list: &mut DisplayList) {
+        let state = &self.state;
+
+        if self.curves.is_none() {
+            display_list.push(Primitive::Segment {
+                from,
+                to,
+                width,
+                color: state.color,
+                stroke: state.stroke.clone(),
+                index: self.index,
+                depth: self.depth as usize,
+            });
+            return;
+        }
+
+        if let Some(path) = &mut self.path {
+            let connected = path.points.last() == Some(&from);
+            let same_style = path.thickness == state.thickness
+                && path.color == state.color
+                && path.stroke == state.stroke;
+            if connected && same_style {
+                path.points.push(to);
+                return;
+            }
+        }
+
+        self.end_path(display_list);
+        self.path = Some(OpenPath {
+            points: vec![from, to],
+            width,
+            thickness: self.state.thickness,
+            color: self.state.color,
+            stroke: self.state.stroke.clone(),
+            index: self.index,
+            depth: self.depth as usize,
+        });
+    }
+
+    // Strokes the path of the current branch
+    fn end_path(&mut self, display_list: &mut DisplayList) {
+        let (Some(path), Some(curves)) = (self.path.take(), &self.curves) else {
+            return;
+        };
+
+        display_list.push(Primitive::Path {
+            start: path.points[0],
+            segments: curves.segments(&path.points),
+            width: path.width,
+            color: path.color,
+            stroke: path.stroke,
+            index: path.index,
+            depth: path.depth,
+        });
+    }
+
+    // Fills the innermost polygon, if it has an area
+    fn end_polygon(&mut self, display_list: &mut DisplayList) {
+        let Some(points) = self.polygons.pop() else {
+            return;
+        };
+
+        if points.len() >= 3 {
+            display_list.push(Primitive::Polygon {
+                points,
+                color: self.state.color,
+                index: self.index,
+                depth: self.depth as usize,
+            });
+        }
+    }
+
+    // Position on the canvas, angle of the heading and scale of the lengths
+    fn label_position(&self) -> Option<(Point, f64, f64)> {
+        let state = &self.state;
+
+        let Some(view) = &self.view else {
+            let angle = (-state.rot.cos()).atan2(-state.rot.sin());
+            return Some((state.pos, angle, 1.0));
+        };
+
+        let (position, scale) = view.project(state.pos3)?;
+        let ahead = view
+            .project(camera::add(state.pos3, state.heading))
+            .map_or(position, |(point, _)| point);
+        let angle = (ahead.1 - position.1).atan2(ahead.0 - position.0);
+
+        Some((position, angle, scale))
+    }
+
+    // Locates the failure of an operation of the current symbol
+    fn error(&self, failure: Failure, fractal: &LSystemRenderer) -> RenderError {
+        let Failure { error, path, before } = failure;
+        let (position, depth) = before.unwrap_or((self.position(), self.depth));
+
+        let operations = fractal.program.operations.get(&self.symbol);
+        let source = match (operations, fractal.sources.get(&self.symbol)) {
+            (Some(operations), Some(sources)) => sources.get(source_index(operations.iter(), &path)).cloned(),
+            _ => None,
+        };
+
+        RenderError::Operation(Box::new(OperationError {
+            error,
+            symbol: self.symbol,
+            index: self.index,
+            operation: path.first().copied().unwrap_or_default(),
+            path: path.get(1..).unwrap_or_default().to_vec(),
+            source: source.unwrap_or_default(),
+            position,
+            depth,
+        }))
+    }
+
+    fn position(&self) -> Vector {
+        let (x, y) = self.state.pos;
+        if self.view.is_some() {
+            self.state.pos3
+        } else {
+            [x, y, 0.0]
+        }
+    }
+
+    fn set_position_variables(&self, machine: &mut Machine) {
+        if self.view.is_some() {
+            let [x, y, z] = self.state.pos3;
+            machine.set(program::POSX, x);
+            machine.set(program::POSY, y);
+            machine.set(program::POSZ, z);
+        } else {
+            machine.set(program::POSX, self.state.pos.0);
+            machine.set(program::POSY, self.state.pos.1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::CancelToken;
+
+    fn render(axiom: &str, operations: Vec<(char, Vec<Operation>)>) -> DisplayList {
+        render_with(LSystemRenderer::default(), axiom, operations)
+    }
+
+    fn render_with(
+        fractal: LSystemRenderer,
+        axiom: &str,
+        operations: Vec<(char, Vec<Operation>)>,
+    ) -> DisplayList {
+        let mut fractal = LSystemRenderer {
+            lsystem: lindenmayer_engine::LSystem::new(axiom, &[]),
+            operations: operations.into_iter().collect(),
+            ..fractal
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        interpret(&fractal, &ExprContext::new(), &Budget::default()).unwrap().0
+    }
+
+    #[test]
+    fn polygons() {
+        let length = || Operation::Jump("10".parse().unwrap());
+        let turn = || Operation::Rotate("pi / 2".parse().unwrap());
+        let operations = vec![
+            ('{', vec![Operation::BeginPolygon]),
+            ('}', vec![Operation::EndPolygon]),
+            ('.', vec![Operation::Vertex]),
+            ('J', vec![length()]),
+            ('+', vec![turn()]),
+            ('[', vec![Operation::PushStack]),
+            (']', vec![Operation::PopStack]),
+        ];
+
+        // The inner polygon is closed by the pop, before the outer one
+        let list = render("{.J.[{.J.+J.]+J.}", operations);
+        let polygons: Vec<usize> = list
+            .primitives
+            .iter()
+            .filter_map(|primitive| match primitive {
+                Primitive::Polygon { points, .. } => Some(points.len()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(polygons, vec![3, 3]);
+    }
+
+    #[test]
+    fn paths() {
+        let operations = vec![
+            ('F', vec![Operation::Forward("10".parse().unwrap())]),
+            ('f', vec![Operation::Jump("10".parse().unwrap())]),
+            ('+', vec![Operation::Rotate("pi / 2".parse().unwrap())]),
+            ('[', vec![Operation::PushStack]),
+            (']', vec![Operation::PopStack]),
+        ];
+        let fractal = LSystemRenderer {
+            curves: Some(Curves::default()),
+            ..Default::default()
+        };
+
+        // The trunk continues after the branch, the jump starts a new path
+        let list = render_with(fractal, "FF[+FF]FfF", operations);
+        let paths: Vec<(usize, usize)> = list
+            .primitives
+            .iter()
+            .filter_map(|primitive| match primitive {
+                Primitive::Path { segments, index, .. } => Some((*index, segments.len())),
+                Primitive::Segment { .. } => panic!("segment in a path"),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(paths, vec![(4, 2), (0, 3), (9, 1)]);
+    }
+
+    #[test]
+    fn stroke_styles() {
+        let operations = vec![
+            ('F', vec![Operation::Forward("10".parse().unwrap())]),
+            ('d', vec![Operation::Dash(vec!["2".parse().unwrap(), "1 + 1".parse().unwrap()])]),
+            ('r', vec![Operation::LineCap(crate::canvas::LineCap::Round)]),
+            ('[', vec![Operation::PushStack]),
+            (']', vec![Operation::PopStack]),
+        ];
+
+        // The style of the branch is restored by the pop
+        let list = render("rF[dF]F", operations);
+        let dashes: Vec<Vec<f64>> = list
+            .primitives
+            .iter()
+            .filter_map(|primitive| match primitive {
+                Primitive::Segment { stroke, .. } => {
+                    assert_eq!(stroke.cap, crate::canvas::LineCap::Round);
+                    Some(stroke.dashes.to_vec())
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(dashes, vec![vec![], vec![2.0, 2.0], vec![]]);
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(num(2.0, 2), "2");
+        assert_eq!(num(0.1 + 0.2, 2), "0.3");
+        assert_eq!(num(-0.001, 2), "0");
+
+        let label = crate::Label {
+            parts: vec![
+                LabelPart::Symbol,
+                LabelPart::Literal(" at ".to_string()),
+                LabelPart::Value("INDEX * 1.5".parse().unwrap(), 1),
+            ],
+            size: "10".parse().unwrap(),
+            anchor: crate::canvas::TextAnchor::Middle,
+            follow_heading: true,
+        };
+        let list = render("FX", vec![('X', vec![Operation::Text(label)])]);
+
+        // The turtle heads up, the text reads from the bottom to the top
+        let texts: Vec<(&str, f64)> = list
+            .primitives
+            .iter()
+            .filter_map(|primitive| match primitive {
+                Primitive::Text { text, angle, .. } => Some((text.as_str(), *angle)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec![("X at 1.5", -std::f64::consts::FRAC_PI_2)]);
+    }
+
+    #[test]
+    fn tropism() {
+        let operations = || {
+            vec![
+                ('F', vec![Operation::Forward("10".parse().unwrap())]),
+                ('+', vec![Operation::Rotate("pi / 2".parse().unwrap())]),
+                ('t', vec![Operation::Tropism("0.5".parse().unwrap())]),
+                ('[', vec![Operation::PushStack]),
+                (']', vec![Operation::PopStack]),
+            ]
+        };
+        let directions = |list: DisplayList| -> Vec<Point> {
+            list.primitives
+                .iter()
+                .filter_map(|primitive| match primitive {
+                    Primitive::Segment { from, to, .. } => Some((to.0 - from.0, to.1 - from.1)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // The branch bends down, towards the gravity, the trunk goes on straight up
+        let flat = directions(render("[+tFF]FF", operations()));
+        assert!(flat[0].1.abs() < 1e-9);
+        assert!(flat[1].0 < 0.0 && flat[1].1 > 1.0);
+        assert!(flat[2].0.abs() < 1e-9 && flat[3].0.abs() < 1e-9);
+
+        // The same in 3D
+        let fractal = LSystemRenderer {
+            camera: crate::camera::Camera {
+                projection: Some(crate::camera::Projection::Orthographic),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let solid = directions(render_with(fractal, "[+tFF]FF", operations()));
+        for (a, b) in flat.iter().zip(&solid) {
+            assert!((a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut fractal = LSystemRenderer {
+            lsystem: lindenmayer_engine::LSystem::new("FFJ", &[]),
+            operations: [
+                ('F', vec![Operation::Forward("10".parse().unwrap())]),
+                (
+                    'J',
+                    vec![
+                        Operation::PushStack,
+                        Operation::Jump("sin(INDEX) * Z".parse().unwrap()),
+                    ],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            sources: [('J', vec!["push".to_string(), "jump sin(INDEX) * Z".to_string()])]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        let result = interpret(&fractal, &ExprContext::new(), &Budget::default());
+        let Err(RenderError::Operation(error)) = result else {
+            panic!("the jump should fail");
+        };
+        assert_eq!((error.symbol, error.index, error.operation, error.depth), ('J', 2, 1, 1));
+        assert!(error
+            .to_string()
+            .starts_with("symbol 'J' (op 2: jump sin(INDEX) * Z) at index 2: "));
+    }
+
+    #[test]
+    fn nested_errors() {
+        let expr = |text: &str| text.parse().unwrap();
+        // forward 10; repeat 2 { jump 5; if DEPTH { } else { forward 10 * Z } }
+        let operations = vec![
+            Operation::Forward(expr("10")),
+            Operation::Repeat(
+                expr("2"),
+                vec![
+                    Operation::Jump(expr("5")),
+                    Operation::If(expr("DEPTH"), vec![], vec![Operation::Forward(expr("10 * Z"))]),
+                ],
+            ),
+        ];
+        let sources = [
+            "forward 10",
+            "repeat 2 { jump 5; if DEPTH { } else { forward 10 * Z } }",
+            "jump 5",
+            "if DEPTH { } else { forward 10 * Z }",
+            "forward 10 * Z",
+        ];
+
+        let mut fractal = LSystemRenderer {
+            lsystem: lindenmayer_engine::LSystem::new("J", &[]),
+            operations: [('J', operations)].into_iter().collect(),
+            sources: [('J', sources.map(String::from).to_vec())].into_iter().collect(),
+            ..Default::default()
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        let result = interpret(&fractal, &ExprContext::new(), &Budget::default());
+        let Err(RenderError::Operation(error)) = result else {
+            panic!("the forward in the else branch should fail");
+        };
+        // Turtle after the first jump, before the failing forward
+        let (x, y) = fractal.initial_pos;
+        assert_eq!((error.operation, &error.path[..]), (1, &[1, 0][..]));
+        assert_eq!(error.position, [x, y - 15.0, 0.0]);
+        assert!(error
+            .to_string()
+            .starts_with("symbol 'J' (op 2.2.1: forward 10 * Z) at index 0: "));
+    }
+
+    #[test]
+    fn strict() {
+        let operations = || {
+            vec![
+                ('F', vec![Operation::Forward("10".parse().unwrap())]),
+                ('[', vec![Operation::PushStack]),
+                (']', vec![Operation::PopStack]),
+            ]
+        };
+
+        // Extra pops are ignored, the depth never goes below zero
+        let list = render("F]]F[F]", operations());
+        let depths = list
+            .primitives
+            .iter()
+            .filter(|primitive| matches!(primitive, Primitive::Segment { .. }))
+            .map(Primitive::depth)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [Some(0), Some(0), Some(1)]);
+
+        let strict = |axiom: &str, max_depth| {
+            let mut fractal = LSystemRenderer {
+                lsystem: lindenmayer_engine::LSystem::new(axiom, &[]),
+                operations: operations().into_iter().collect(),
+                strict: Some(Strict { max_depth }),
+                ..Default::default()
+            };
+            fractal.update_expr();
+            fractal.update_program();
+
+            match interpret(&fractal, &ExprContext::new(), &Budget::default()) {
+                Err(RenderError::Operation(error)) => Some((error.index, error.error)),
+                _ => None,
+            }
+        };
+
+        assert!(matches!(strict("F[F]]", None), Some((4, ExecutionError::StackUnderflow))));
+        assert!(matches!(strict("[[[F]]]", Some(2)), Some((2, ExecutionError::StackOverflow(2)))));
+        assert!(strict("[[F]]", Some(2)).is_none());
+    }
+
+    #[test]
+    fn variables() {
+        let mut fractal = LSystemRenderer {
+            lsystem: lindenmayer_engine::LSystem::new("F", &[]),
+            operations: [('F', vec![Operation::Forward("length".parse().unwrap())])]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let function = crate::functions::Function {
+            params: vec!["x".to_string()],
+            body: "x * 2".parse().unwrap(),
+        };
+        fractal.functions.insert("double".to_string(), function);
+
+        // The variables are evaluated after the ones they use
+        for (name, value) in [("length", "width + 1"), ("width", "double(FRAME)")] {
+            fractal.variables.insert(name.to_string(), value.parse().unwrap());
+        }
+        fractal.update_expr();
+        fractal.update_program();
+
+        let mut context = ExprContext::new();
+        context.var("FRAME", 2.0);
+        let (list, _) = interpret(&fractal, &context, &Budget::default()).unwrap();
+        let Some(Primitive::Segment { from, to, .. }) = list.primitives.last() else {
+            panic!("{:?}", list.primitives);
+        };
+        assert_eq!(from.1 - to.1, 5.0);
+    }
+
+    #[test]
+    fn repeat() {
+        let repeat = |count: &str| {
+            let mut fractal = LSystemRenderer {
+                lsystem: lindenmayer_engine::LSystem::new("R", &[]),
+                operations: [(
+                    'R',
+                    vec![Operation::Repeat(
+                        count.parse().unwrap(),
+                        vec![Operation::Forward("1".parse().unwrap())],
+                    )],
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            };
+            fractal.update_expr();
+            fractal.update_program();
+
+            interpret(&fractal, &ExprContext::new(), &Budget::default())
+        };
+
+        let (list, _) = repeat("3").unwrap();
+        assert_eq!(list.primitives.len(), 4);
+
+        // The count is not cut short
+        let Err(RenderError::Operation(error)) = repeat("1e9") else {
+            panic!("the repeat should fail");
+        };
+        assert!(matches!(error.error, ExecutionError::TooManyIterations(MAX_REPEAT_ITERATIONS)));
+    }
+
+    #[test]
+    fn budget() {
+        let mut fractal = LSystemRenderer {
+            lsystem: lindenmayer_engine::LSystem::new("F[FFFF]F", &[]),
+            operations: [
+                ('F', vec![Operation::Forward("10".parse().unwrap())]),
+                ('[', vec![Operation::PushStack]),
+                (']', vec![Operation::PopStack]),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        let run = |budget: Budget| {
+            let (list, status) = interpret(&fractal, &ExprContext::new(), &budget).unwrap();
+            // The background is not drawn by the turtle
+            (list.primitives.len() - 1, status)
+        };
+
+        assert_eq!(run(Budget::default()), (6, RenderStatus::Complete));
+
+        let operations = Budget {
+            max_operations: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(run(operations), (2, RenderStatus::Partial(StopReason::Operations)));
+
+        let primitives = Budget {
+            max_primitives: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(run(primitives), (3, RenderStatus::Partial(StopReason::Primitives)));
+
+        // The token is checked at the first symbol
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let cancelled = Budget {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        assert_eq!(run(cancelled), (0, RenderStatus::Partial(StopReason::Cancelled)));
+
+        // The budget is checked between the iterations of a symbol
+        let mut fractal = LSystemRenderer {
+            lsystem: lindenmayer_engine::LSystem::new("R", &[]),
+            operations: [(
+                'R',
+                vec![Operation::Repeat(
+                    "50000".parse().unwrap(),
+                    vec![Operation::Forward("1".parse().unwrap())],
+                )],
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        fractal.update_expr();
+        fractal.update_program();
+
+        let primitives = Budget {
+            max_primitives: Some(101),
+            ..Default::default()
+        };
+        let (list, status) = interpret(&fractal, &ExprContext::new(), &primitives).unwrap();
+        assert_eq!(list.primitives.len(), 101);
+        assert_eq!(status, RenderStatus::Partial(StopReason::Primitives));
+
+        let operations = Budget {
+            max_operations: Some(10),
+            ..Default::default()
+        };
+        let (list, status) = interpret(&fractal, &ExprContext::new(), &operations).unwrap();
+        assert_eq!(list.primitives.len(), 10);
+        assert_eq!(status, RenderStatus::Partial(StopReason::Operations));
+    }
+}
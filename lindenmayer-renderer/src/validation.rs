@@ -0,0 +1,173 @@
+use crate::{LSystemRenderer, Operation};
+use std::collections::HashMap;
+use std::fmt;
+
+/// String in which an unbalanced push or pop was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackSource {
+    Axiom,
+    Rule(char),
+    // Position in the list of the injections
+    Injection(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackIssueKind {
+    // Index of the first symbol which pops more states than were pushed
+    Underflow(usize),
+    // Number of states which are never popped
+    Unclosed(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackIssue {
+    pub source: StackSource,
+    pub kind: StackIssueKind,
+}
+
+impl fmt::Display for StackIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source {
+            StackSource::Axiom => write!(f, "axiom: ")?,
+            StackSource::Rule(symbol) => write!(f, "rule '{symbol}': ")?,
+            StackSource::Injection(i) => write!(f, "injection {}: ", i + 1)?,
+        }
+
+        match self.kind {
+            StackIssueKind::Underflow(index) => write!(f, "pop without a matching push at index {index}"),
+            StackIssueKind::Unclosed(1) => write!(f, "push without a matching pop"),
+            StackIssueKind::Unclosed(n) => write!(f, "{n} pushes without a matching pop"),
+        }
+    }
+}
+
+/// Looks for unbalanced push and pop symbols in the axiom, the rules and the injections.
+/// If each of them is balanced, so is every expansion.
+/// Strings with symbols whose effect on the stack depends on the values are not checked.
+pub fn check_stack(fractal: &LSystemRenderer) -> Vec<StackIssue> {
+    let effects: HashMap<char, Option<Effect>> = fractal
+        .operations
+        .iter()
+        .map(|(symbol, operations)| (*symbol, effect(operations)))
+        .collect();
+
+    let mut rules = fractal.lsystem.rules.iter().collect::<Vec<_>>();
+    rules.sort_by_key(|(symbol, _)| **symbol);
+
+    let strings = std::iter::once((StackSource::Axiom, &fractal.lsystem.axiom))
+        .chain(rules.into_iter().map(|(symbol, rule)| (StackSource::Rule(*symbol), rule)))
+        .chain(
+            fractal
+                .injections
+                .iter()
+                .enumerate()
+                .map(|(i, injection)| (StackSource::Injection(i), &injection.1)),
+        );
+
+    strings
+        .filter_map(|(source, string)| {
+            let kind = check_string(string, &effects)?;
+            Some(StackIssue { source, kind })
+        })
+        .collect()
+}
+
+// Lowest depth reached, relative to the starting one, and final depth
+type Effect = (i32, i32);
+
+fn check_string(string: &str, effects: &HashMap<char, Option<Effect>>) -> Option<StackIssueKind> {
+    let mut depth = 0;
+
+    for (index, c) in string.chars().enumerate() {
+        // Symbols without operations leave the stack unchanged
+        let Some(effect) = effects.get(&c) else {
+            continue;
+        };
+        let (lowest, net) = (*effect)?;
+
+        if depth + lowest < 0 {
+            return Some(StackIssueKind::Underflow(index));
+        }
+        depth += net;
+    }
+
+    (depth > 0).then_some(StackIssueKind::Unclosed(depth as usize))
+}
+
+// Effect of the operations on the stack, none if it depends on the values
+fn effect(operations: &[Operation]) -> Option<Effect> {
+    let mut lowest = 0;
+    let mut depth = 0;
+
+    for operation in operations {
+        let (op_lowest, op_net) = match operation {
+            Operation::PushStack => (0, 1),
+            Operation::PopStack => (-1, -1),
+            // The skipped symbols are not known
+            Operation::Ignore(_) => return None,
+            Operation::If(_, then, otherwise) => {
+                let then = effect(then)?;
+                let otherwise = effect(otherwise)?;
+                if then.1 != otherwise.1 {
+                    return None;
+                }
+                (then.0.min(otherwise.0), then.1)
+            }
+            Operation::Repeat(_, operations) => match effect(operations)? {
+                // The block can run any number of times
+                (lowest, 0) => (lowest, 0),
+                _ => return None,
+            },
+            _ => (0, 0),
+        };
+
+        lowest = lowest.min(depth + op_lowest);
+        depth += op_net;
+    }
+
+    Some((lowest, depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LSystem;
+
+    #[test]
+    fn unbalanced() {
+        let rules = [
+            ('F', String::from("F[+F]F")),
+            ('X', String::from("F]X")),
+            ('Y', String::from("[F[X]")),
+        ];
+        let fractal = LSystemRenderer {
+            lsystem: LSystem::new("X", &rules),
+            operations: HashMap::from([
+                ('[', vec![Operation::PushStack]),
+                (']', vec![Operation::PopStack]),
+            ]),
+            injections: vec![(0, String::from("]["))],
+            ..Default::default()
+        };
+
+        let issues = check_stack(&fractal);
+        assert_eq!(
+            issues,
+            vec![
+                StackIssue {
+                    source: StackSource::Rule('X'),
+                    kind: StackIssueKind::Underflow(1),
+                },
+                StackIssue {
+                    source: StackSource::Rule('Y'),
+                    kind: StackIssueKind::Unclosed(1),
+                },
+                StackIssue {
+                    source: StackSource::Injection(0),
+                    kind: StackIssueKind::Underflow(0),
+                },
+            ]
+        );
+        assert_eq!(issues[0].to_string(), "rule 'X': pop without a matching push at index 1");
+    }
+}
@@ -0,0 +1,161 @@
+use lindenmayer_examples::{all, variables, FRAMES};
+use lindenmayer_renderer::canvas::ExprContext;
+use lindenmayer_renderer::meval::{self, Expr};
+use lindenmayer_renderer::program::{Compiled, CompiledOperation, EvalError, Machine};
+use lindenmayer_renderer::{Color, LSystemRenderer, LabelPart, Operation};
+
+// Turtle variables of a few states: INDEX, DEPTH, POSX, POSY and POSZ
+const STATES: [[f64; 5]; 3] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0],
+    [17.0, 3.0, 120.5, -40.25, 8.0],
+    [4096.0, 12.0, 612.0, 333.0, -75.5],
+];
+
+// Expression, its code and whether it is a color
+type Pair<'a> = (&'a Expr, &'a Compiled, bool);
+
+// Expressions of the operations, with the code they were compiled to
+fn operation_pairs<'a>(operations: &'a [Operation], compiled: &'a [CompiledOperation], pairs: &mut Vec<Pair<'a>>) {
+    for (operation, compiled) in operations.iter().zip(compiled) {
+        match (operation, compiled) {
+            (Operation::Forward(expr), Operation::Forward(code))
+            | (Operation::Jump(expr), Operation::Jump(code))
+            | (Operation::Dot(expr), Operation::Dot(code))
+            | (Operation::Rotate(expr), Operation::Rotate(code))
+            | (Operation::Pitch(expr), Operation::Pitch(code))
+            | (Operation::Roll(expr), Operation::Roll(code))
+            | (Operation::Thickness(expr), Operation::Thickness(code))
+            | (Operation::Tropism(expr), Operation::Tropism(code))
+            | (Operation::MiterLimit(expr), Operation::MiterLimit(code))
+            | (Operation::Ignore(expr), Operation::Ignore(code))
+            | (Operation::SetVar(_, expr), Operation::SetVar(_, code)) => pairs.push((expr, code, false)),
+            (Operation::SetColor(Color::Dynamic(expr)), Operation::SetColor(Color::Dynamic(code))) => {
+                pairs.push((expr, code, true))
+            }
+            (Operation::Dash(exprs), Operation::Dash(code)) => {
+                pairs.extend(exprs.iter().zip(code).map(|(expr, code)| (expr, code, false)))
+            }
+            (Operation::Text(label), Operation::Text(code)) => {
+                pairs.push((&label.size, &code.size, false));
+                for part in label.parts.iter().zip(&code.parts) {
+                    if let (LabelPart::Value(expr, _), LabelPart::Value(code, _)) = part {
+                        pairs.push((expr, code, false));
+                    }
+                }
+            }
+            (Operation::If(expr, then_ops, else_ops), Operation::If(code, then_code, else_code)) => {
+                pairs.push((expr, code, false));
+                operation_pairs(then_ops, then_code, pairs);
+                operation_pairs(else_ops, else_code, pairs);
+            }
+            (Operation::Repeat(expr, body), Operation::Repeat(code, body_code)) => {
+                pairs.push((expr, code, false));
+                operation_pairs(body, body_code, pairs);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Every expression of the fractal, with the code they were compiled to
+fn pairs(renderer: &LSystemRenderer) -> Vec<Pair<'_>> {
+    let program = &renderer.program;
+    let mut pairs = vec![];
+
+    for (slot, code) in &program.variables {
+        pairs.push((&renderer.variables[&program.slot_names()[*slot]], code, false));
+    }
+    for (symbol, operations) in &renderer.operations {
+        operation_pairs(operations, &program.operations[symbol], &mut pairs);
+    }
+
+    let mut numbers = vec![];
+    if renderer.camera.projection.is_some() {
+        let (camera, code) = (&renderer.camera, &program.camera);
+        numbers.extend([(&camera.yaw, &code.yaw), (&camera.pitch, &code.pitch)]);
+        numbers.push((&camera.distance, &code.distance));
+    }
+    if let (Some(tropism), Some(code)) = (&renderer.tropism, &program.tropism) {
+        numbers.push((&tropism.susceptibility, &code.susceptibility));
+    }
+    if let (Some(viewport), Some(code)) = (&renderer.viewport, &program.viewport) {
+        numbers.extend([(&viewport.zoom, &code.zoom), (&viewport.angle, &code.angle)]);
+        numbers.extend([(&viewport.center.0, &code.center.0), (&viewport.center.1, &code.center.1)]);
+    }
+    pairs.extend(numbers.into_iter().map(|(expr, code)| (expr, code, false)));
+
+    pairs
+}
+
+// Channel of a color evaluated by meval, whose `rgb` and `rgba` return one of their arguments.
+// The examples do not use the other color functions.
+fn channel(expr: &Expr, context: &ExprContext, channel: usize) -> Result<f64, meval::Error> {
+    let mut context = context.clone();
+    context.func3("rgb", move |r, g, b| [r, g, b, 1.0][channel]);
+    context.funcn("rgba", move |args| args[channel], 4);
+
+    let value = expr.eval_with_context(&context)?;
+    Ok(match channel {
+        3 => value.clamp(0.0, 1.0),
+        _ => (value / 255.0).clamp(0.0, 1.0),
+    })
+}
+
+fn same(meval: Result<f64, meval::Error>, compiled: Result<f64, EvalError>) -> bool {
+    match (meval, compiled) {
+        (Ok(a), Ok(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (Err(a), Err(EvalError::Expression(b))) => a == b,
+        _ => false,
+    }
+}
+
+#[test]
+fn same_values_as_meval() {
+    let mut compared = 0;
+    let mut mismatches = vec![];
+
+    for (name, renderer) in all() {
+        let pairs = pairs(&renderer);
+
+        let frames = FRAMES.into_iter().flat_map(|frame| STATES.map(|state| (frame, state)));
+        for ((frame, time), [index, depth, x, y, z]) in frames {
+            let mut context = variables(frame, time);
+            renderer.animate(&mut context);
+            context.var("LENGTH", renderer.expression.len() as f64);
+            context.var("INDEX", index).var("DEPTH", depth);
+            context.var("POSX", x).var("POSY", y).var("POSZ", z);
+
+            // Declared variables are set as the turtle does, without overriding the animated ones
+            for (slot, _) in &renderer.program.variables {
+                let variable = &renderer.program.slot_names()[*slot];
+                if !renderer.animations.contains_key(variable) {
+                    if let Ok(value) = renderer.variables[variable].eval_with_context(&context) {
+                        context.var(variable, value);
+                    }
+                }
+            }
+
+            let mut machine = Machine::new(&renderer.program, &renderer.rng, &context);
+            for (expr, code, color) in &pairs {
+                let (expected, values) = if *color {
+                    let expected = (0..4).map(|i| channel(expr, &context, i)).collect();
+                    let values = match machine.eval_color(code) {
+                        Ok((r, g, b, a)) => [r, g, b, a].map(Ok).to_vec(),
+                        Err(error) => vec![Err(error); 4],
+                    };
+                    (expected, values)
+                } else {
+                    (vec![expr.eval_with_context(&context)], vec![machine.eval(code)])
+                };
+
+                if !expected.iter().zip(&values).all(|(a, b)| same(a.clone(), b.clone())) {
+                    mismatches.push(format!("{name} at frame {frame}: {expected:?} != {values:?}"));
+                }
+                compared += 1;
+            }
+        }
+    }
+
+    assert!(compared > 0);
+    assert!(mismatches.is_empty(), "values differ from meval: {mismatches:#?}");
+}
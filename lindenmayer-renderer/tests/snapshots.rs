@@ -0,0 +1,62 @@
+use lindenmayer_examples::{hash, variables, FRAMES};
+use lindenmayer_renderer::canvas::Canvas;
+use lindenmayer_renderer::recording::RecordingCanvas;
+use lindenmayer_renderer::LSystemRenderer;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// Set to write the current output as the expected one
+const UPDATE_ENV: &str = "UPDATE_SNAPSHOTS";
+
+// Calls written in full in the snapshot, the rest is only in the hash
+const LOGGED_CALLS: usize = 1000;
+
+fn snapshot(renderer: &LSystemRenderer) -> String {
+    let mut snapshot = String::new();
+    for (frame, time) in FRAMES {
+        let canvas = RecordingCanvas::new();
+        (&canvas as &dyn Canvas).draw_fractal(renderer, &mut variables(frame, time)).unwrap();
+
+        let calls = canvas.calls();
+        let log = canvas.log();
+        writeln!(snapshot, "frame {frame} time {time}").unwrap();
+        writeln!(snapshot, "calls {} hash {:016x}", calls.len(), hash(&log)).unwrap();
+        for call in calls.iter().take(LOGGED_CALLS) {
+            writeln!(snapshot, "{call}").unwrap();
+        }
+        if calls.len() > LOGGED_CALLS {
+            writeln!(snapshot, "...").unwrap();
+        }
+        snapshot.push('\n');
+    }
+
+    snapshot
+}
+
+#[test]
+fn examples() {
+    let snapshots = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let update = std::env::var_os(UPDATE_ENV).is_some();
+
+    if update {
+        fs::create_dir_all(&snapshots).unwrap();
+    }
+
+    let mut mismatches = vec![];
+    for (name, renderer) in lindenmayer_examples::all() {
+        let snapshot = snapshot(&renderer);
+
+        let expected = snapshots.join(format!("{name}.snap"));
+        if update {
+            fs::write(&expected, &snapshot).unwrap();
+        } else if fs::read_to_string(&expected).ok().as_deref() != Some(snapshot.as_str()) {
+            mismatches.push(name);
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "calls differ from the snapshots of {mismatches:?}, run with {UPDATE_ENV}=1 to update them"
+    );
+}